@@ -0,0 +1,179 @@
+//! Renders a WAV file through Myplug without a host. Parameters are passed as `id=value` pairs
+//! using the parameter IDs from `MyplugParams`:
+//!
+//! ```shell
+//! cargo run --release --features render --bin render -- in.wav out.wav mode=ping-pong feedback=0.7
+//! ```
+
+use nih_plug::prelude::*;
+use std::process::ExitCode;
+
+use MYPLUG::{BufferTask, Myplug, MyplugParams, ParamOverrides, Playhead};
+
+const USAGE: &str = "Usage: render <input.wav> <output.wav> [--tempo <bpm>] [--tail <seconds>] \
+                     [<param-id>=<value>...]";
+
+/// The number of samples processed at a time.
+const BLOCK_SIZE: usize = 512;
+
+/// How long the output keeps going after the input has ended by default, in seconds.
+const DEFAULT_TAIL_SECONDS: f32 = 2.0;
+
+/// Stands in for the host during `initialize()`.
+struct OfflineInitContext;
+
+impl InitContext<Myplug> for OfflineInitContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute(&self, _task: BufferTask) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}\n\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut paths = Vec::new();
+    let mut overrides = ParamOverrides::default();
+    let mut tempo = None;
+    let mut tail_seconds = DEFAULT_TAIL_SECONDS;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            "--tempo" => tempo = Some(parse_option(args.next(), "--tempo")?),
+            "--tail" => tail_seconds = parse_option(args.next(), "--tail")?,
+            _ => match arg.split_once('=') {
+                Some((id, value)) => overrides.insert(id, value),
+                None => paths.push(arg),
+            },
+        }
+    }
+    let [input_path, output_path]: [String; 2] = paths
+        .try_into()
+        .map_err(|_| String::from("Expected an input and an output file"))?;
+
+    let params = MyplugParams::with_overrides(&overrides);
+    let errors = overrides.errors(&params);
+    if !errors.is_empty() {
+        return Err(errors.join(", "));
+    }
+
+    let mut reader = hound::WavReader::open(&input_path)
+        .map_err(|err| format!("Could not open '{input_path}': {err}"))?;
+    let spec = reader.spec();
+    let samples: Result<Vec<f32>, _> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect()
+        }
+    };
+    let samples = samples.map_err(|err| format!("Could not read '{input_path}': {err}"))?;
+
+    let num_input_channels = spec.channels as usize;
+    let layout = <Myplug>::AUDIO_IO_LAYOUTS
+        .iter()
+        .find(|layout| {
+            layout.main_input_channels.map(NonZeroU32::get) == Some(spec.channels as u32)
+        })
+        .ok_or_else(|| format!("Files with {num_input_channels} channels are not supported"))?;
+    let num_output_channels = layout
+        .main_output_channels
+        .map(NonZeroU32::get)
+        .unwrap_or_default() as usize;
+
+    let mut plugin = <Myplug>::new(params);
+    let buffer_config = BufferConfig {
+        sample_rate: spec.sample_rate as f32,
+        min_buffer_size: None,
+        max_buffer_size: BLOCK_SIZE as u32,
+        process_mode: ProcessMode::Offline,
+    };
+    if !plugin.initialize(layout, &buffer_config, &mut OfflineInitContext) {
+        return Err(String::from("The plugin could not be initialized"));
+    }
+    plugin.reset();
+
+    // The input is deinterleaved and padded with silence so the repeats can ring out. The
+    // plugin's latency is rendered as well and then trimmed from the start of the output.
+    let latency = plugin.latency() as usize;
+    let input_len = samples.len() / num_input_channels;
+    let len = input_len + (tail_seconds * spec.sample_rate as f32) as usize + latency;
+    let mut channels: Vec<Vec<f32>> = (0..num_output_channels)
+        .map(|channel| {
+            let mut channel_samples = vec![0.0; len];
+            if channel < num_input_channels {
+                for (sample, frame) in channel_samples
+                    .iter_mut()
+                    .zip(samples.chunks_exact(num_input_channels))
+                {
+                    *sample = frame[channel];
+                }
+            }
+
+            channel_samples
+        })
+        .collect();
+
+    // The file is rendered as if the host's transport started playing at its first sample
+    let playhead = Playhead {
+        tempo,
+        bar_beats: None,
+        pos_beats: Some(0.0),
+        playing: true,
+    };
+    for block_start in (0..len).step_by(BLOCK_SIZE) {
+        let block_end = (block_start + BLOCK_SIZE).min(len);
+        let mut block: Vec<&mut [f32]> = channels
+            .iter_mut()
+            .map(|channel| &mut channel[block_start..block_end])
+            .collect();
+        let playhead = playhead.advance(block_start, spec.sample_rate as f32);
+        plugin.process_channels(&mut block, None, playhead);
+    }
+
+    let output_spec = hound::WavSpec {
+        channels: num_output_channels as u16,
+        sample_rate: spec.sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let write_error = |err: hound::Error| format!("Could not write '{output_path}': {err}");
+    let mut writer = hound::WavWriter::create(&output_path, output_spec).map_err(write_error)?;
+    for sample_idx in latency..len {
+        for channel in &channels {
+            writer
+                .write_sample(channel[sample_idx])
+                .map_err(write_error)?;
+        }
+    }
+    writer.finalize().map_err(write_error)?;
+
+    Ok(())
+}
+
+fn parse_option<T: std::str::FromStr>(value: Option<String>, option: &str) -> Result<T, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("{option} expects a number"))
+}
@@ -0,0 +1,101 @@
+// These process four samples at a time so the compiler can turn the loops into SIMD
+// instructions, followed by a scalar loop for the remaining samples. All slices need to have the
+// same length.
+
+/// The number of samples processed at once.
+const LANES: usize = 4;
+
+/// Write `input * gain` to `output`.
+pub fn multiply(output: &mut [f32], input: &[f32], gain: &[f32]) {
+    let mut output_chunks = output.chunks_exact_mut(LANES);
+    let mut input_chunks = input.chunks_exact(LANES);
+    let mut gain_chunks = gain.chunks_exact(LANES);
+    for ((output, input), gain) in (&mut output_chunks)
+        .zip(&mut input_chunks)
+        .zip(&mut gain_chunks)
+    {
+        for lane in 0..LANES {
+            output[lane] = input[lane] * gain[lane];
+        }
+    }
+
+    for ((output, input), gain) in output_chunks
+        .into_remainder()
+        .iter_mut()
+        .zip(input_chunks.remainder())
+        .zip(gain_chunks.remainder())
+    {
+        *output = input * gain;
+    }
+}
+
+/// Mix the dry and wet signals into `output`, which contains the unprocessed input. While
+/// bypassed the wet signal is mixed into the unprocessed input instead, so the repeats can ring
+/// out.
+pub fn mix(output: &mut [f32], dry: &[f32], dry_gain: &[f32], wet: &[f32], bypass: &[f32]) {
+    fn mix_sample(unprocessed: f32, dry: f32, dry_gain: f32, wet: f32, bypass: f32) -> f32 {
+        let processed = dry * dry_gain + wet;
+        let bypassed = unprocessed + wet;
+        processed + (bypassed - processed) * bypass
+    }
+
+    let mut output_chunks = output.chunks_exact_mut(LANES);
+    let mut dry_chunks = dry.chunks_exact(LANES);
+    let mut dry_gain_chunks = dry_gain.chunks_exact(LANES);
+    let mut wet_chunks = wet.chunks_exact(LANES);
+    let mut bypass_chunks = bypass.chunks_exact(LANES);
+    for ((((output, dry), dry_gain), wet), bypass) in (&mut output_chunks)
+        .zip(&mut dry_chunks)
+        .zip(&mut dry_gain_chunks)
+        .zip(&mut wet_chunks)
+        .zip(&mut bypass_chunks)
+    {
+        for lane in 0..LANES {
+            output[lane] = mix_sample(
+                output[lane],
+                dry[lane],
+                dry_gain[lane],
+                wet[lane],
+                bypass[lane],
+            );
+        }
+    }
+
+    for ((((output, dry), dry_gain), wet), bypass) in output_chunks
+        .into_remainder()
+        .iter_mut()
+        .zip(dry_chunks.remainder())
+        .zip(dry_gain_chunks.remainder())
+        .zip(wet_chunks.remainder())
+        .zip(bypass_chunks.remainder())
+    {
+        *output = mix_sample(*output, *dry, *dry_gain, *wet, *bypass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiply_covers_the_remainder() {
+        let input = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let gain = [2.0; 6];
+        let mut output = [0.0; 6];
+        multiply(&mut output, &input, &gain);
+
+        assert_eq!(output, [2.0, 4.0, 6.0, 8.0, 10.0, 12.0]);
+    }
+
+    #[test]
+    fn mix_crossfades_to_the_bypassed_signal() {
+        let mut output = [1.0; 5];
+        let dry = [0.5; 5];
+        let dry_gain = [0.5; 5];
+        let wet = [0.25; 5];
+        let bypass = [0.0, 0.0, 0.0, 0.0, 1.0];
+        mix(&mut output, &dry, &dry_gain, &wet, &bypass);
+
+        assert_eq!(output, [0.5, 0.5, 0.5, 0.5, 1.25]);
+    }
+}
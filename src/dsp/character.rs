@@ -0,0 +1,239 @@
+use super::{time_constant_coefficient, Crusher, OnePole, Rng, Sample};
+use crate::params::{Character, Saturation};
+
+/// The envelope floor of the compander. Quieter signals aren't boosted any further when they're
+/// compressed, which keeps the noise floor from exploding in silence.
+const COMPANDER_FLOOR: f32 = 1e-4;
+/// The compander's attack and release times in seconds. The release is slow enough that the
+/// noise floor audibly breathes with the repeats, like on real bucket brigade delays.
+const COMPANDER_ATTACK_SECONDS: f32 = 0.001;
+const COMPANDER_RELEASE_SECONDS: f32 = 0.03;
+/// The tape hiss's level in decibels for brand new and for completely worn out tape.
+const HISS_NEW_DB: f32 = -90.0;
+const HISS_WORN_DB: f32 = -54.0;
+/// The band the hiss is filtered to.
+const HISS_LOW_HZ: f32 = 800.0;
+const HISS_HIGH_HZ: f32 = 9000.0;
+/// The hiss fades in as the signal on the tape rises up to this level, so it's only heard
+/// along with the repeats and not while the buffer is empty. This is well above the loudest
+/// hiss so the hiss that's fed back can't keep the gate open by itself.
+const HISS_GATE_LEVEL: f32 = 0.01;
+/// How long the hiss keeps going after the signal has died down, in seconds.
+const HISS_GATE_RELEASE_SECONDS: f32 = 0.25;
+
+/// How a character model colors the signal written into the delay line, converted for the
+/// current sample rate.
+#[derive(Debug, Clone, Copy)]
+pub struct CharacterSettings {
+    character: Character,
+    /// The coefficient of the bandwidth limiting filters.
+    bandwidth: f32,
+    /// Whether the signal is compressed before and expanded after the medium.
+    companding: bool,
+    /// The bit depth and sample rate of the converters, if the medium is digital.
+    converter: Option<(f32, f32)>,
+    /// The level of the medium's noise floor as a gain.
+    noise: f32,
+    /// How hard the medium saturates.
+    saturation: Option<(Saturation, f32)>,
+    /// The tape hiss's level as a gain, and the coefficients of its filters and its gate.
+    hiss: f32,
+    hiss_low: f32,
+    hiss_high: f32,
+    hiss_gate_release: f32,
+    compander_attack: f32,
+    compander_release: f32,
+    sample_rate: f32,
+}
+
+impl CharacterSettings {
+    /// Convert a character model's settings to `sample_rate`. `age` in `[0, 1]` sets how loud the
+    /// tape model's hiss is.
+    pub fn new(character: Character, age: f32, sample_rate: f32) -> Self {
+        let (bandwidth_hz, companding, converter, noise_db, saturation) = match character {
+            Character::Clean => (sample_rate / 2.0, false, None, None, None),
+            Character::Digital12 => (
+                12000.0,
+                true,
+                Some((12.0, 31250.0)),
+                None,
+                Some((Saturation::Tanh, 0.8)),
+            ),
+            Character::Bbd => (
+                5000.0,
+                true,
+                None,
+                Some(-72.0),
+                Some((Saturation::Tanh, 0.8)),
+            ),
+            Character::Tape => (9000.0, false, None, None, Some((Saturation::Tanh, 1.2))),
+        };
+
+        Self {
+            character,
+            bandwidth: OnePole::coefficient(bandwidth_hz.min(sample_rate * 0.45), sample_rate),
+            companding,
+            converter,
+            noise: noise_db.map_or(0.0, |db| 10.0f32.powf(db / 20.0)),
+            saturation,
+            hiss: if character == Character::Tape {
+                10.0f32.powf((HISS_NEW_DB + (HISS_WORN_DB - HISS_NEW_DB) * age) / 20.0)
+            } else {
+                0.0
+            },
+            hiss_low: OnePole::coefficient(HISS_LOW_HZ, sample_rate),
+            hiss_high: OnePole::coefficient(HISS_HIGH_HZ.min(sample_rate * 0.45), sample_rate),
+            hiss_gate_release: time_constant_coefficient(HISS_GATE_RELEASE_SECONDS, sample_rate),
+            compander_attack: time_constant_coefficient(COMPANDER_ATTACK_SECONDS, sample_rate),
+            compander_release: time_constant_coefficient(COMPANDER_RELEASE_SECONDS, sample_rate),
+            sample_rate,
+        }
+    }
+}
+
+/// Emulates the medium of a vintage delay in the feedback path. The signal is band limited,
+/// optionally compressed, passed through the medium's converters, noise and saturation, and then
+/// expanded and band limited again, on every repeat.
+pub struct CharacterModel {
+    /// The anti-aliasing and reconstruction filters, two one-pole filters each.
+    pre_filters: [OnePole; 2],
+    post_filters: [OnePole; 2],
+    /// The compressor's and the expander's envelopes.
+    compressor: f32,
+    expander: f32,
+    converter: Crusher,
+    rng: Rng,
+    /// The tape hiss's band-pass filter and gate.
+    hiss_filters: [OnePole; 2],
+    hiss_gate: f32,
+}
+
+impl CharacterModel {
+    pub fn new() -> Self {
+        Self {
+            pre_filters: Default::default(),
+            post_filters: Default::default(),
+            compressor: 0.0,
+            expander: 0.0,
+            converter: Crusher::default(),
+            rng: Rng::new(0xbbd),
+            hiss_filters: Default::default(),
+            hiss_gate: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn process(&mut self, input: Sample, settings: &CharacterSettings) -> Sample {
+        if settings.character == Character::Clean {
+            return input;
+        }
+
+        let mut sample = input;
+        for filter in &mut self.pre_filters {
+            sample = filter.lowpass(sample, settings.bandwidth);
+        }
+        // The compressor halves the dynamic range in decibels, and the expander restores it
+        if settings.companding {
+            self.compressor = follow(self.compressor, sample, settings);
+            sample /= self.compressor.max(COMPANDER_FLOOR).sqrt() as Sample;
+        }
+
+        if let Some((bits, rate)) = settings.converter {
+            sample = self
+                .converter
+                .process(sample, bits, rate, settings.sample_rate);
+        }
+        sample += (self.rng.next_bipolar() * settings.noise) as Sample;
+        if settings.hiss > 0.0 {
+            sample += self.hiss(input, settings);
+        }
+        if let Some((saturation, drive)) = settings.saturation {
+            sample = saturation.process(sample, drive) / drive as Sample;
+        }
+
+        if settings.companding {
+            self.expander = follow(self.expander, sample, settings);
+            sample *= self.expander.max(COMPANDER_FLOOR.sqrt()) as Sample;
+        }
+        for filter in &mut self.post_filters {
+            sample = filter.lowpass(sample, settings.bandwidth);
+        }
+
+        sample
+    }
+}
+
+impl CharacterModel {
+    /// The next sample of tape hiss, which follows the level of the signal on the tape.
+    fn hiss(&mut self, input: Sample, settings: &CharacterSettings) -> Sample {
+        let level = (super::to_f32(input).abs() / HISS_GATE_LEVEL).min(1.0);
+        self.hiss_gate = if level > self.hiss_gate {
+            level
+        } else {
+            self.hiss_gate + (level - self.hiss_gate) * settings.hiss_gate_release
+        };
+
+        let [low, high] = &mut self.hiss_filters;
+        let noise = (self.rng.next_bipolar() * settings.hiss) as Sample;
+        let noise = low.highpass(high.lowpass(noise, settings.hiss_high), settings.hiss_low);
+
+        noise * self.hiss_gate as Sample
+    }
+}
+
+/// Advance one of the compander's envelopes.
+fn follow(envelope: f32, sample: Sample, settings: &CharacterSettings) -> f32 {
+    let level = super::to_f32(sample).abs();
+    let coefficient = if level > envelope {
+        settings.compander_attack
+    } else {
+        settings.compander_release
+    };
+
+    envelope + (level - envelope) * coefficient
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::to_f32;
+    use std::f32::consts::TAU;
+
+    fn peak(character: Character, amplitude: f32) -> f32 {
+        let settings = CharacterSettings::new(character, 0.0, 48000.0);
+        let mut model = CharacterModel::new();
+        (0..48000)
+            .map(|i| {
+                let input = amplitude * (TAU * 200.0 * i as f32 / 48000.0).sin();
+                to_f32(model.process(input as Sample, &settings))
+            })
+            .skip(24000)
+            .fold(0.0, |peak, sample| sample.abs().max(peak))
+    }
+
+    #[test]
+    fn models_keep_quiet_signals_at_their_level() {
+        assert_eq!(peak(Character::Clean, 0.1), 0.1);
+        for character in [Character::Digital12, Character::Bbd, Character::Tape] {
+            let peak = peak(character, 0.1);
+            assert!((peak - 0.1).abs() < 0.01, "{character:?} peaked at {peak}");
+        }
+    }
+
+    #[test]
+    fn hiss_is_gated_by_the_signal() {
+        let settings = CharacterSettings::new(Character::Tape, 1.0, 48000.0);
+        let mut model = CharacterModel::new();
+        for _ in 0..48000 {
+            assert_eq!(model.process(0.0, &settings), 0.0);
+        }
+
+        let with_signal = (0..4800)
+            .map(|_| to_f32(model.process(0.5, &settings)) - 0.5 * 1.2f32.tanh() / 1.2)
+            .fold(0.0f32, |peak, sample| sample.abs().max(peak));
+        assert!(with_signal > 1e-4);
+    }
+}
@@ -0,0 +1,374 @@
+use super::character::CharacterModel;
+use super::frequency_shifter::FrequencyShifter;
+use super::oversampling::{LatencyCompensation, Oversampled};
+use super::{
+    flush_denormal, Biquad, Crusher, DcBlocker, LoopFilter, OnePole, PitchShifter, Sample,
+};
+use crate::params::Interpolation;
+use nih_plug::nih_debug_assert;
+
+/// A single channel's ring buffer together with its write and read positions. The buffer's
+/// length is always a power of two, so positions can be wrapped by masking them.
+pub struct DelayLine {
+    pub buffer: Vec<Sample>,
+    /// The buffer's length minus one.
+    mask: usize,
+    /// The position the next sample gets written to.
+    pub iterdelay: usize,
+    /// The position the primary tap reads from.
+    pub iterrepeats: usize,
+    /// The delay length in samples the read position was last computed for.
+    pub prev: usize,
+    /// The previous read position while crossfading to a new delay time. This keeps moving
+    /// alongside `iterrepeats` until `fade_remaining` reaches zero.
+    pub fade_head: usize,
+    pub fade_remaining: usize,
+    /// In glide mode this fractional offset is added to `iterrepeats` when a delay time change
+    /// happens, and it then slews back to zero.
+    pub glide_offset: f32,
+    /// The position within the detune window of the first of the two detuned read heads, in
+    /// `[0, 1)`.
+    pub detune_phase: f32,
+
+    /// The current frame's input and primary tap. The feedback path is written after all
+    /// channels have been read so it can be routed between channels.
+    pub frame_input: Sample,
+    pub frame_tap: Sample,
+
+    /// Damping filters applied to the signal written back into the buffer.
+    pub low_cut: OnePole,
+    pub high_cut: OnePole,
+    /// The high shelf that makes every repeat a little darker than the last one.
+    pub tilt: OnePole,
+    /// The parametric bell in the feedback path.
+    pub bell: Biquad,
+    /// Damps the string in Karplus-Strong mode, and the resonance in comb mode.
+    pub loop_filter: LoopFilter,
+    pub comb_damping: OnePole,
+    /// Emulates a vintage delay's medium in the feedback path.
+    pub character: CharacterModel,
+    /// Reduces the feedback path's bit depth and sample rate.
+    pub crusher: Crusher,
+    /// Removes DC offset from the signal written back into the buffer.
+    pub dc_blocker: DcBlocker,
+    /// Pitch shifts the feedback path in shimmer mode.
+    pub shimmer: PitchShifter,
+    /// Ring modulates or frequency shifts the feedback path.
+    pub shifter: FrequencyShifter,
+
+    /// The oversampled nonlinearities, Ring Feed's multiplication and the feedback path's
+    /// waveshaping.
+    pub ring: Oversampled<2>,
+    pub saturation: Oversampled<1>,
+    /// Delays the signals that aren't oversampled by the same amount as the ones that are.
+    pub dry_compensation: LatencyCompensation<f32>,
+    pub wet_compensation: LatencyCompensation<f32>,
+    pub input_compensation: LatencyCompensation<f32>,
+    pub tap_compensation: LatencyCompensation<Sample>,
+}
+
+impl DelayLine {
+    /// Create a delay line with a buffer of `len` samples, and with grains of
+    /// `shimmer_window_len` samples for the shimmer pitch shifter. `len` needs to be a power of
+    /// two.
+    pub fn new(len: usize, shimmer_window_len: usize) -> Self {
+        nih_debug_assert!(len.is_power_of_two());
+
+        Self {
+            buffer: vec![0.0; len],
+            mask: len - 1,
+            iterdelay: 0,
+            iterrepeats: 0,
+            prev: 0,
+            fade_head: 0,
+            fade_remaining: 0,
+            glide_offset: 0.0,
+            detune_phase: 0.0,
+
+            frame_input: 0.0,
+            frame_tap: 0.0,
+
+            low_cut: OnePole::default(),
+            high_cut: OnePole::default(),
+            tilt: OnePole::default(),
+            bell: Biquad::default(),
+            loop_filter: LoopFilter::default(),
+            comb_damping: OnePole::default(),
+            character: CharacterModel::new(),
+            crusher: Crusher::default(),
+            dc_blocker: DcBlocker::default(),
+            shimmer: PitchShifter::new(shimmer_window_len),
+            shifter: FrequencyShifter::default(),
+
+            ring: Oversampled::default(),
+            saturation: Oversampled::default(),
+            dry_compensation: LatencyCompensation::default(),
+            wet_compensation: LatencyCompensation::default(),
+            input_compensation: LatencyCompensation::default(),
+            tap_compensation: LatencyCompensation::default(),
+        }
+    }
+
+    /// Clear the buffer and move all read and write positions back to the start. This reuses
+    /// the existing buffer so it can be called from the audio thread.
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.reset_positions();
+    }
+
+    /// Swap in a new buffer that has already been cleared, and move all positions back to the
+    /// start. The old buffer is left in `buffer` so it can be deallocated off the audio thread.
+    pub fn swap_buffer(&mut self, buffer: &mut Vec<Sample>) {
+        nih_debug_assert!(buffer.len().is_power_of_two());
+
+        std::mem::swap(&mut self.buffer, buffer);
+        self.mask = self.buffer.len() - 1;
+        self.reset_positions();
+    }
+
+    fn reset_positions(&mut self) {
+        self.iterdelay = 0;
+        self.iterrepeats = 0;
+        self.prev = 0;
+        self.fade_head = 0;
+        self.fade_remaining = 0;
+        self.glide_offset = 0.0;
+        self.detune_phase = 0.0;
+
+        self.reset_filters();
+    }
+
+    /// Replace NaN and infinite samples in the buffer with silence, and reset everything in the
+    /// feedback path that could have picked them up. Returns the number of replaced samples.
+    pub fn scrub(&mut self) -> usize {
+        let mut num_scrubbed = 0;
+        for sample in &mut self.buffer {
+            if !sample.is_finite() {
+                *sample = 0.0;
+                num_scrubbed += 1;
+            }
+        }
+        self.reset_filters();
+
+        num_scrubbed
+    }
+
+    fn reset_filters(&mut self) {
+        self.frame_input = 0.0;
+        self.frame_tap = 0.0;
+
+        self.low_cut = OnePole::default();
+        self.high_cut = OnePole::default();
+        self.tilt = OnePole::default();
+        self.bell = Biquad::default();
+        self.loop_filter = LoopFilter::default();
+        self.comb_damping = OnePole::default();
+        self.character.reset();
+        self.crusher = Crusher::default();
+        self.dc_blocker = DcBlocker::default();
+        self.shimmer.reset();
+        self.shifter = FrequencyShifter::default();
+
+        self.ring = Oversampled::default();
+        self.saturation = Oversampled::default();
+        self.dry_compensation = LatencyCompensation::default();
+        self.wet_compensation = LatencyCompensation::default();
+        self.input_compensation = LatencyCompensation::default();
+        self.tap_compensation = LatencyCompensation::default();
+    }
+
+    /// The buffer index `offset` samples behind the write position. Offsets wrap around the
+    /// buffer, so an offset of a whole buffer length refers to the write position itself.
+    pub fn position(&self, offset: usize) -> usize {
+        self.wrap(self.iterdelay.wrapping_sub(offset))
+    }
+
+    /// Read the sample that was written `offset` samples ago.
+    pub fn read(&self, offset: usize) -> Sample {
+        self.buffer[self.position(offset)]
+    }
+
+    /// Write a sample `offset` samples behind the write position. This is normally zero, but the
+    /// oversampled feedback path writes its output as far back as the oversampling delayed it.
+    /// The write position only moves on [`tick()`][Self::tick()]. Denormals are flushed to zero so
+    /// quiet tails don't slow down processing.
+    pub fn write_behind(&mut self, offset: usize, sample: Sample) {
+        let pos = self.position(offset);
+        self.buffer[pos] = flush_denormal(sample);
+    }
+
+    /// Move the write position to `pos`, wrapped to the buffer's length, and the primary read
+    /// position along with it so the delay time stays the same. The buffer's contents are kept.
+    pub fn move_write_position(&mut self, pos: usize) {
+        self.iterdelay = self.wrap(pos);
+        self.iterrepeats = self.position(self.prev);
+        self.fade_remaining = 0;
+    }
+
+    /// Move the primary read position by `delta` samples, wrapping around either end of the
+    /// buffer.
+    pub fn shift_read(&mut self, delta: isize) {
+        self.iterrepeats = self.wrap(self.iterrepeats.wrapping_add_signed(delta));
+    }
+
+    /// Advance the write position, the primary read position, and the read position that's being
+    /// faded out by one sample. This is called once per sample after the frame has been written.
+    pub fn tick(&mut self) {
+        self.iterdelay = self.wrap(self.iterdelay + 1);
+        self.iterrepeats = self.wrap(self.iterrepeats + 1);
+        if self.fade_remaining > 0 {
+            self.fade_remaining -= 1;
+            self.fade_head = self.wrap(self.fade_head + 1);
+        }
+    }
+
+    /// Read from a fractional position in the buffer. The position is wrapped to the buffer's
+    /// length.
+    pub fn read_fractional(&self, pos: f32, interpolation: Interpolation) -> Sample {
+        interpolation.read_with(pos, |index| self.buffer[self.wrap(index as usize)])
+    }
+
+    /// Read from a fractional position with two read heads that run at `ratio` times the speed of
+    /// the write position. The heads drift up to `window` samples behind `pos`, and they're
+    /// faded in and out half a window apart so they can jump back without clicks.
+    pub fn read_detuned(
+        &mut self,
+        pos: f32,
+        ratio: f32,
+        window: f32,
+        interpolation: Interpolation,
+    ) -> Sample {
+        self.detune_phase = (self.detune_phase + (1.0 - ratio) / window).rem_euclid(1.0);
+        [0.0, 0.5]
+            .into_iter()
+            .map(|head_offset| {
+                let phase = (self.detune_phase + head_offset).fract();
+                let gain = (phase * std::f32::consts::PI).sin().powi(2);
+                self.read_fractional(pos - phase * window, interpolation) * gain as Sample
+            })
+            .sum()
+    }
+
+    /// Wrap a position to the buffer's length. Positions that have wrapped around below zero as
+    /// an unsigned integer end up at the end of the buffer, since the length is a power of two.
+    fn wrap(&self, pos: usize) -> usize {
+        pos & self.mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_reads_wrap_around() {
+        let mut line = DelayLine::new(4, 16);
+        line.buffer.copy_from_slice(&[1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!(line.read_fractional(1.5, Interpolation::Linear), 2.5);
+        // Halfway between the last and the first sample
+        assert_eq!(line.read_fractional(3.5, Interpolation::Linear), 2.5);
+        assert_eq!(line.read_fractional(-0.5, Interpolation::Linear), 2.5);
+        assert_eq!(line.read_fractional(5.0, Interpolation::Linear), 2.0);
+    }
+
+    #[test]
+    fn detuned_heads_crossfade_at_constant_gain() {
+        let mut line = DelayLine::new(64, 16);
+        line.buffer.fill(0.5);
+
+        for _ in 0..1000 {
+            let sample = line.read_detuned(32.0, 1.01, 16.0, Interpolation::Linear);
+            assert!((sample - 0.5).abs() < 1e-5, "{sample}");
+        }
+    }
+
+    #[test]
+    fn ticking_wraps_around() {
+        let mut line = DelayLine::new(4, 16);
+        for sample in [1.0, 2.0, 3.0, 4.0, 5.0] {
+            line.write_behind(0, sample);
+            line.tick();
+        }
+
+        assert_eq!(line.iterdelay, 1);
+        assert_eq!(line.buffer, [5.0, 2.0, 3.0, 4.0]);
+        assert_eq!(line.read(1), 5.0);
+        assert_eq!(line.read(2), 4.0);
+        assert_eq!(line.read(4), 2.0);
+    }
+
+    #[test]
+    fn moving_the_write_position_keeps_the_delay_time() {
+        let mut line = DelayLine::new(8, 16);
+        line.prev = 3;
+        line.iterrepeats = line.position(3);
+        line.shift_read(2);
+
+        line.move_write_position(17);
+        assert_eq!(line.iterdelay, 1);
+        assert_eq!(line.iterrepeats, 6);
+    }
+
+    #[test]
+    fn reads_behind_the_start_wrap_to_the_end() {
+        let mut line = DelayLine::new(4, 16);
+        line.buffer.copy_from_slice(&[1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!(line.position(1), 3);
+        assert_eq!(line.read(1), 4.0);
+        assert_eq!(line.read(3), 2.0);
+    }
+
+    #[test]
+    fn offsets_larger_than_the_buffer_wrap() {
+        let mut line = DelayLine::new(4, 16);
+        line.buffer.copy_from_slice(&[1.0, 2.0, 3.0, 4.0]);
+        line.iterdelay = 2;
+
+        assert_eq!(line.position(4), 2);
+        assert_eq!(line.read(5), line.read(1));
+        assert_eq!(line.read(4 * 1000 + 3), line.read(3));
+    }
+
+    #[test]
+    fn shifting_the_read_position_wraps_both_ways() {
+        let mut line = DelayLine::new(4, 16);
+        line.shift_read(-1);
+        assert_eq!(line.iterrepeats, 3);
+        line.shift_read(-9);
+        assert_eq!(line.iterrepeats, 2);
+        line.shift_read(3);
+        assert_eq!(line.iterrepeats, 1);
+
+        line.iterrepeats = 3;
+        line.tick();
+        assert_eq!(line.iterrepeats, 0);
+    }
+
+    #[test]
+    fn reset_keeps_the_buffer() {
+        let mut line = DelayLine::new(8, 16);
+        line.buffer.fill(1.0);
+        line.iterdelay = 5;
+        line.iterrepeats = 3;
+        let ptr = line.buffer.as_ptr();
+
+        line.reset();
+        assert_eq!(line.buffer.as_ptr(), ptr);
+        assert!(line.buffer.iter().all(|&sample| sample == 0.0));
+        assert_eq!((line.iterdelay, line.iterrepeats), (0, 0));
+    }
+
+    #[test]
+    fn scrubbing_only_replaces_non_finite_samples() {
+        let mut line = DelayLine::new(4, 16);
+        line.buffer
+            .copy_from_slice(&[1.0, Sample::NAN, Sample::INFINITY, -2.0]);
+        line.iterdelay = 2;
+
+        assert_eq!(line.scrub(), 2);
+        assert_eq!(line.buffer, [1.0, 0.0, 0.0, -2.0]);
+        assert_eq!(line.iterdelay, 2);
+    }
+}
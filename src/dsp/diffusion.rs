@@ -0,0 +1,140 @@
+use std::f32::consts::TAU;
+
+/// The allpass filters' delay times in seconds. These are mutually prime-ish so the echoes they
+/// add don't line up.
+const ALLPASS_SECONDS: [f32; 4] = [0.00477, 0.00359, 0.01273, 0.00931];
+/// The modulation rates of the allpass filters in Hertz, also chosen so they don't line up.
+const ALLPASS_RATES: [f32; 4] = [0.53, 0.71, 0.83, 1.13];
+/// How far the modulation moves the allpass delay times, in seconds.
+const MODULATION_SECONDS: f32 = 0.0003;
+/// The allpass gain at full diffusion. Higher values ring audibly at the allpass delay times.
+const MAX_ALLPASS_GAIN: f32 = 0.7;
+
+/// A Schroeder allpass filter with a modulated fractional delay time. The modulation keeps the
+/// diffused signal from sounding metallic.
+pub struct Allpass {
+    buffer: Vec<f32>,
+    write_pos: usize,
+    /// The delay time in samples without modulation.
+    delay: f32,
+    /// How far the delay time is modulated in samples.
+    depth: f32,
+    /// The modulation LFO's phase and increment per sample, in cycles.
+    phase: f32,
+    increment: f32,
+}
+
+impl Allpass {
+    /// Create an allpass filter with a delay time of `delay` samples that's modulated by up to
+    /// `depth` samples at `rate` cycles per sample.
+    pub fn new(delay: f32, depth: f32, rate: f32) -> Self {
+        Self {
+            buffer: vec![0.0; (delay + depth).ceil() as usize + 2],
+            write_pos: 0,
+            delay,
+            depth,
+            phase: 0.0,
+            increment: rate,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_pos = 0;
+        self.phase = 0.0;
+    }
+
+    /// Process a sample with an allpass gain of `gain`, which should be in `(-1, 1)`.
+    pub fn process(&mut self, input: f32, gain: f32) -> f32 {
+        let len = self.buffer.len();
+        self.phase = (self.phase + self.increment).fract();
+        let delay = self.delay + self.depth * (self.phase * TAU).sin();
+
+        // The delayed signal is read with linear interpolation
+        let read_pos = (self.write_pos as f32 - delay).rem_euclid(len as f32);
+        let index = read_pos as usize % len;
+        let fraction = read_pos.fract();
+        let delayed =
+            self.buffer[index] + (self.buffer[(index + 1) % len] - self.buffer[index]) * fraction;
+
+        // The tail is flushed to zero like in `flush_denormal()`, which works on `Sample`s
+        let feedback = input + delayed * gain;
+        self.buffer[self.write_pos] = if feedback.abs() < 1e-20 {
+            0.0
+        } else {
+            feedback
+        };
+        self.write_pos = (self.write_pos + 1) % len;
+
+        delayed - feedback * gain
+    }
+}
+
+/// A chain of modulated allpass filters that smears the discrete repeats into a wash.
+pub struct Diffuser {
+    allpasses: Vec<Allpass>,
+}
+
+impl Diffuser {
+    /// Create a diffuser for a single channel. Every channel gets slightly different delay times
+    /// so the diffusion also widens the stereo image.
+    pub fn new(channel: usize, sample_rate: f32) -> Self {
+        let spread = 1.0 + 0.07 * channel as f32;
+        let allpasses = ALLPASS_SECONDS
+            .iter()
+            .zip(ALLPASS_RATES)
+            .map(|(&seconds, rate)| {
+                Allpass::new(
+                    seconds * spread * sample_rate,
+                    MODULATION_SECONDS * sample_rate,
+                    rate * spread / sample_rate,
+                )
+            })
+            .collect();
+
+        Self { allpasses }
+    }
+
+    pub fn reset(&mut self) {
+        for allpass in &mut self.allpasses {
+            allpass.reset();
+        }
+    }
+
+    /// Diffuse a sample by `amount` in `[0, 1]`. At zero the input passes through untouched.
+    pub fn process(&mut self, input: f32, amount: f32) -> f32 {
+        let gain = amount * MAX_ALLPASS_GAIN;
+        let diffused = self
+            .allpasses
+            .iter_mut()
+            .fold(input, |sample, allpass| allpass.process(sample, gain));
+
+        input + (diffused - input) * amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allpass_keeps_the_energy() {
+        // Without modulation the impulse response of an allpass filter has the same energy as
+        // the impulse
+        let mut allpass = Allpass::new(7.0, 0.0, 0.0);
+        let energy: f32 = (0..2000)
+            .map(|i| allpass.process(if i == 0 { 1.0 } else { 0.0 }, 0.6).powi(2))
+            .sum();
+
+        assert!((energy - 1.0).abs() < 1e-4, "{energy}");
+    }
+
+    #[test]
+    fn no_diffusion_passes_through() {
+        let mut diffuser = Diffuser::new(0, 48000.0);
+        for i in 0..1000 {
+            let input = (i as f32 * 0.1).sin();
+            assert_eq!(diffuser.process(input, 0.0), input);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+/// The most steps a Euclidean rhythm can have.
+pub const MAX_STEPS: usize = 16;
+
+/// Whether `step` is a pulse in a Euclidean rhythm, which spreads `pulses` pulses as evenly as
+/// possible over `steps` steps. `rotation` moves the pattern that many steps earlier.
+pub fn is_pulse(step: usize, steps: usize, pulses: usize, rotation: usize) -> bool {
+    let steps = steps.max(1);
+    let pulses = pulses.min(steps);
+    ((step + rotation) % steps * pulses) % steps < pulses
+}
+
+/// The tap times of a Euclidean rhythm spread over a bar, or of the multi-tap mode's taps. Step
+/// zero is the input itself, so its tap is a whole bar later, where the rhythm starts over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TapPattern {
+    /// The taps' delay times in samples. Only the first `len` of these are used.
+    offsets: [usize; MAX_STEPS],
+    /// The taps' levels.
+    levels: [f32; MAX_STEPS],
+    len: usize,
+}
+
+impl TapPattern {
+    pub fn new(steps: usize, pulses: usize, rotation: usize, bar_samples: usize) -> Self {
+        let steps = steps.clamp(1, MAX_STEPS);
+        let mut offsets = [0; MAX_STEPS];
+        let mut len = 0;
+        for step in (0..steps).filter(|&step| is_pulse(step, steps, pulses, rotation)) {
+            offsets[len] = if step == 0 {
+                bar_samples
+            } else {
+                step * bar_samples / steps
+            };
+            len += 1;
+        }
+
+        let mut pattern = Self {
+            offsets,
+            levels: [0.0; MAX_STEPS],
+            len,
+        };
+        pattern.levels = [pattern.gain(); MAX_STEPS];
+        pattern
+    }
+
+    /// A pattern of freely placed taps. `taps` are the taps' times as a fraction of the pattern's
+    /// length along with their levels, and like the rhythm's first step a tap at the very start
+    /// lands a whole pattern later.
+    pub fn from_taps(taps: impl IntoIterator<Item = (f32, f32)>, pattern_samples: usize) -> Self {
+        let mut offsets = [0; MAX_STEPS];
+        let mut levels = [0.0; MAX_STEPS];
+        let mut len = 0;
+        for (time, level) in taps.into_iter().take(MAX_STEPS) {
+            let offset = (time.clamp(0.0, 1.0) * pattern_samples as f32).round() as usize;
+            offsets[len] = if offset == 0 { pattern_samples } else { offset };
+            levels[len] = level;
+            len += 1;
+        }
+
+        Self {
+            offsets,
+            levels,
+            len,
+        }
+    }
+
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets[..self.len]
+    }
+
+    pub fn levels(&self) -> &[f32] {
+        &self.levels[..self.len]
+    }
+
+    /// The level of a Euclidean rhythm's taps, so patterns with many pulses don't get much
+    /// louder than sparse ones.
+    pub fn gain(&self) -> f32 {
+        if self.len == 0 {
+            0.0
+        } else {
+            (self.len as f32).sqrt().recip()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rhythm(steps: usize, pulses: usize, rotation: usize) -> String {
+        (0..steps)
+            .map(|step| {
+                if is_pulse(step, steps, pulses, rotation) {
+                    'x'
+                } else {
+                    '.'
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spreads_the_pulses_evenly() {
+        assert_eq!(rhythm(8, 3, 0), "x..x..x.");
+        assert_eq!(rhythm(16, 4, 0), "x...x...x...x...");
+        assert_eq!(rhythm(5, 0, 0), ".....");
+        assert_eq!(rhythm(4, 9, 0), "xxxx");
+        assert_eq!(rhythm(8, 3, 1), "..x..x.x");
+    }
+
+    #[test]
+    fn taps_are_spread_over_the_bar() {
+        let pattern = TapPattern::new(8, 3, 0, 800);
+        assert_eq!(pattern.offsets(), [800, 300, 600]);
+
+        assert_eq!(TapPattern::new(8, 0, 0, 800).gain(), 0.0);
+    }
+
+    #[test]
+    fn free_taps_keep_their_levels() {
+        let pattern = TapPattern::from_taps([(0.25, 1.0), (0.0, 0.5), (1.0, 0.25)], 800);
+        assert_eq!(pattern.offsets(), [200, 800, 800]);
+        assert_eq!(pattern.levels(), [1.0, 0.5, 0.25]);
+    }
+}
@@ -0,0 +1,87 @@
+use std::f32::consts::TAU;
+
+/// Transform `re` and `im` in place with an iterative radix-2 FFT. Both need to have the same
+/// length, which must be a power of two.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let len = re.len();
+    debug_assert!(len.is_power_of_two() && im.len() == len);
+
+    // Bit reversed reordering, so the butterflies can work in place
+    let bits = len.trailing_zeros();
+    for i in 0..len {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= len {
+        let half = size / 2;
+        let (sin, cos) = (-TAU / size as f32).sin_cos();
+        for start in (0..len).step_by(size) {
+            let (mut twiddle_re, mut twiddle_im) = (1.0, 0.0);
+            for k in start..start + half {
+                let odd_re = re[k + half] * twiddle_re - im[k + half] * twiddle_im;
+                let odd_im = re[k + half] * twiddle_im + im[k + half] * twiddle_re;
+                re[k + half] = re[k] - odd_re;
+                im[k + half] = im[k] - odd_im;
+                re[k] += odd_re;
+                im[k] += odd_im;
+
+                (twiddle_re, twiddle_im) = (
+                    twiddle_re * cos - twiddle_im * sin,
+                    twiddle_re * sin + twiddle_im * cos,
+                );
+            }
+        }
+        size *= 2;
+    }
+}
+
+/// A Hann window of `len` samples.
+pub fn hann_window(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| 0.5 - 0.5 * (TAU * i as f32 / len as f32).cos())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_lands_in_its_bin() {
+        let len = 256;
+        let mut re: Vec<f32> = (0..len)
+            .map(|i| (TAU * 10.0 * i as f32 / len as f32).sin())
+            .collect();
+        let mut im = vec![0.0; len];
+        fft(&mut re, &mut im);
+
+        // A sine with an amplitude of one has half of its energy in both its positive and its
+        // negative frequency bin
+        for bin in 0..len {
+            let magnitude = re[bin].hypot(im[bin]) / len as f32;
+            let expected = if bin == 10 || bin == len - 10 {
+                0.5
+            } else {
+                0.0
+            };
+            assert!((magnitude - expected).abs() < 1e-4, "{bin}: {magnitude}");
+        }
+    }
+
+    #[test]
+    fn impulse_is_flat() {
+        let mut re = vec![0.0; 64];
+        let mut im = vec![0.0; 64];
+        re[0] = 1.0;
+        fft(&mut re, &mut im);
+
+        for bin in 0..64 {
+            assert!((re[bin] - 1.0).abs() < 1e-6 && im[bin].abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::f32::consts::TAU;
+
+use super::{flush_denormal, Sample};
+use crate::params::FeedbackShift;
+
+/// The allpass coefficients of the two paths of the Hilbert transformer. The paths' outputs are
+/// 90 degrees apart from about 20 Hz to 20 kHz at 44.1 kHz. These are Olli Niemitalo's
+/// coefficients, squared.
+const PATH_COEFFICIENTS: [[Sample; 4]; 2] = [
+    [0.479_400_9, 0.876_218_5, 0.976_597_6, 0.997_499_2],
+    [0.161_758_5, 0.733_028_9, 0.945_349_6, 0.990_599_1],
+];
+
+/// A second order allpass section with a single coefficient, `y[n] = a * (x[n] + y[n - 2]) -
+/// x[n - 2]`.
+#[derive(Default, Clone, Copy)]
+struct AllpassSection {
+    x: [Sample; 2],
+    y: [Sample; 2],
+}
+
+impl AllpassSection {
+    fn process(&mut self, input: Sample, coefficient: Sample) -> Sample {
+        let output = flush_denormal(coefficient * (input + self.y[1]) - self.x[1]);
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// An IIR Hilbert transformer that splits a signal into two parts that are 90 degrees apart.
+#[derive(Default)]
+pub struct Hilbert {
+    sections: [[AllpassSection; 4]; 2],
+    /// The first path is delayed by one more sample.
+    delayed: Sample,
+}
+
+impl Hilbert {
+    /// Returns the in-phase and the quadrature part of the input.
+    pub fn process(&mut self, input: Sample) -> (Sample, Sample) {
+        let [real, imaginary] = std::array::from_fn(|path| {
+            self.sections[path]
+                .iter_mut()
+                .zip(PATH_COEFFICIENTS[path])
+                .fold(input, |sample, (section, coefficient)| {
+                    section.process(sample, coefficient)
+                })
+        });
+        let real = std::mem::replace(&mut self.delayed, real);
+
+        (real, imaginary)
+    }
+}
+
+/// Shifts the repeats in the feedback path, so every pass moves them further and the echoes
+/// spiral up or down.
+#[derive(Default)]
+pub struct FrequencyShifter {
+    hilbert: Hilbert,
+    /// The carrier's phase, in `[0, 1)`.
+    phase: f32,
+}
+
+impl FrequencyShifter {
+    /// Shift or ring modulate a sample by `shift` Hertz. Negative shifts move the spectrum down,
+    /// and the ring modulator uses the magnitude.
+    pub fn process(
+        &mut self,
+        input: Sample,
+        mode: FeedbackShift,
+        shift: f32,
+        sample_rate: f32,
+    ) -> Sample {
+        if mode == FeedbackShift::Off {
+            return input;
+        }
+
+        self.phase = (self.phase + shift / sample_rate).rem_euclid(1.0);
+        let (sin, cos) = (self.phase * TAU).sin_cos();
+        match mode {
+            FeedbackShift::Off => input,
+            FeedbackShift::Ring => input * cos as Sample,
+            FeedbackShift::Frequency => {
+                let (real, imaginary) = self.hilbert.process(input);
+                real * cos as Sample + imaginary * sin as Sample
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::to_f32;
+
+    #[test]
+    fn hilbert_parts_are_in_quadrature() {
+        // The magnitude of a sine's analytic signal is constant
+        let sample_rate = 44100.0;
+        for frequency in [100.0, 1000.0, 10000.0] {
+            let mut hilbert = Hilbert::default();
+            for i in 0..8820 {
+                let input = (TAU * frequency * i as f32 / sample_rate).sin();
+                let (real, imaginary) = hilbert.process(input as Sample);
+                let magnitude = to_f32(real * real + imaginary * imaginary).sqrt();
+                if i > 4410 {
+                    assert!(
+                        (magnitude - 1.0).abs() < 0.02,
+                        "{frequency} Hz has a magnitude of {magnitude}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn positive_shifts_move_up() {
+        let sample_rate = 44100.0;
+        let crossings = |shift| {
+            let mut shifter = FrequencyShifter::default();
+            let output: Vec<f32> = (0..44100)
+                .map(|i| {
+                    let input = (TAU * 1000.0 * i as f32 / sample_rate).sin();
+                    to_f32(shifter.process(
+                        input as Sample,
+                        FeedbackShift::Frequency,
+                        shift,
+                        sample_rate,
+                    ))
+                })
+                .collect();
+
+            // Two zero crossings per cycle over one second
+            output
+                .windows(2)
+                .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+                .count() as f32
+                / 2.0
+        };
+
+        assert!((crossings(200.0) - 1200.0).abs() < 5.0);
+        assert!((crossings(-200.0) - 800.0).abs() < 5.0);
+    }
+}
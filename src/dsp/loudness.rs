@@ -0,0 +1,251 @@
+use std::f32::consts::PI;
+
+use super::{to_f32, Biquad, BiquadCoefficients, Sample};
+
+/// The loudness is measured in sub-blocks of this length, in seconds. The gating blocks and the
+/// short-term window are both made of sub-blocks.
+const SUB_BLOCK_SECONDS: f32 = 0.1;
+/// The number of sub-blocks in a 400 ms gating block, which overlap by 75%, and in the 3 s
+/// short-term window.
+const GATING_BLOCK_LEN: usize = 4;
+const SHORT_TERM_LEN: usize = 30;
+/// Gating blocks quieter than the absolute gate are ignored, and so are blocks more than the
+/// relative gate below the loudness of the remaining blocks.
+const ABSOLUTE_GATE_LUFS: f32 = -70.0;
+const RELATIVE_GATE_LU: f32 = -10.0;
+/// The gating blocks are counted in a histogram starting at the absolute gate, so the integrated
+/// loudness can run for hours without storing every block. The relative gate is applied with the
+/// histogram's resolution.
+const HISTOGRAM_RESOLUTION_LU: f32 = 0.1;
+const HISTOGRAM_BINS: usize = 800;
+
+/// The K-weighting filters from ITU-R BS.1770 for any sample rate: a high shelf for the head's
+/// acoustic effect followed by the RLB high-pass filter. These are the analog prototypes from
+/// libebur128, which match the coefficients in the standard at 48 kHz.
+fn k_weighting(sample_rate: f32) -> [BiquadCoefficients; 2] {
+    let k = (PI * 1_681.974_5 / sample_rate).tan();
+    let q = 0.707_175_24;
+    let vh = 10.0f32.powf(3.999_843_8 / 20.0);
+    let vb = vh.powf(0.499_666_78);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = BiquadCoefficients {
+        b0: (vh + vb * k / q + k * k) / a0,
+        b1: 2.0 * (k * k - vh) / a0,
+        b2: (vh - vb * k / q + k * k) / a0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+    };
+
+    let k = (PI * 38.135_47 / sample_rate).tan();
+    let q = 0.500_327;
+    let a0 = 1.0 + k / q + k * k;
+    let highpass = BiquadCoefficients {
+        b0: 1.0,
+        b1: -2.0,
+        b2: 1.0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+    };
+
+    [shelf, highpass]
+}
+
+/// How much a channel counts towards the loudness. With 5.1 and larger layouts the LFE channel
+/// is left out and the surround channels are weighted by 1.41.
+fn channel_weight(channel: usize, num_channels: usize) -> f32 {
+    match channel {
+        3 if num_channels >= 6 => 0.0,
+        4.. if num_channels >= 6 => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// Convert a weighted mean square to LUFS.
+fn lufs(power: f32) -> f32 {
+    if power > 0.0 {
+        -0.691 + 10.0 * power.log10()
+    } else {
+        f32::NEG_INFINITY
+    }
+}
+
+/// Measures the short-term and the integrated loudness following ITU-R BS.1770 and EBU R 128.
+pub struct LoudnessMeter {
+    coefficients: [BiquadCoefficients; 2],
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f32>,
+    sub_block_len: usize,
+    /// The number of frames and the sum of their weighted squares in the current sub-block.
+    pos: usize,
+    sum: f32,
+    /// The mean squares of the last sub-blocks as a ring buffer, and how many of them have been
+    /// measured so far, up to the buffer's length.
+    sub_blocks: [f32; SHORT_TERM_LEN],
+    sub_block: usize,
+    num_sub_blocks: usize,
+    /// The number of gating blocks in every histogram bin and the sum of their mean squares.
+    histogram: Vec<(u32, f64)>,
+}
+
+impl LoudnessMeter {
+    pub fn new(num_channels: usize, sample_rate: f32) -> Self {
+        Self {
+            coefficients: k_weighting(sample_rate),
+            filters: vec![Default::default(); num_channels],
+            weights: (0..num_channels)
+                .map(|channel| channel_weight(channel, num_channels))
+                .collect(),
+            sub_block_len: ((sample_rate * SUB_BLOCK_SECONDS).round() as usize).max(1),
+            pos: 0,
+            sum: 0.0,
+            sub_blocks: [0.0; SHORT_TERM_LEN],
+            sub_block: 0,
+            num_sub_blocks: 0,
+            histogram: vec![(0, 0.0); HISTOGRAM_BINS],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.fill(Default::default());
+        self.pos = 0;
+        self.sum = 0.0;
+        self.sub_blocks = [0.0; SHORT_TERM_LEN];
+        self.num_sub_blocks = 0;
+        self.reset_integrated();
+    }
+
+    /// Start measuring the integrated loudness over.
+    pub fn reset_integrated(&mut self) {
+        self.histogram.fill((0, 0.0));
+    }
+
+    /// Add a sample of every channel.
+    pub fn add_frame(&mut self, frame: &[f32]) {
+        let [shelf, highpass] = &self.coefficients;
+        for ((&sample, filters), weight) in frame.iter().zip(&mut self.filters).zip(&self.weights) {
+            let sample = filters[0].process(sample as Sample, shelf);
+            let sample = to_f32(filters[1].process(sample, highpass));
+            self.sum += weight * sample * sample;
+        }
+
+        self.pos += 1;
+        if self.pos == self.sub_block_len {
+            self.finish_sub_block();
+        }
+    }
+
+    /// The loudness of the last three seconds in LUFS, or negative infinity for silence.
+    pub fn short_term(&self) -> f32 {
+        lufs(self.mean_power(SHORT_TERM_LEN.min(self.num_sub_blocks)))
+    }
+
+    /// The gated loudness since the last reset in LUFS, or negative infinity if nothing has been
+    /// above the absolute gate yet.
+    pub fn integrated(&self) -> f32 {
+        let gated = |bins: &[(u32, f64)]| {
+            let (count, sum) = bins
+                .iter()
+                .fold((0, 0.0), |(count, sum), bin| (count + bin.0, sum + bin.1));
+            if count > 0 {
+                lufs((sum / count as f64) as f32)
+            } else {
+                f32::NEG_INFINITY
+            }
+        };
+
+        let ungated = gated(&self.histogram);
+        if ungated == f32::NEG_INFINITY {
+            return ungated;
+        }
+        let threshold = ungated + RELATIVE_GATE_LU;
+        let first_bin = ((threshold - ABSOLUTE_GATE_LUFS) / HISTOGRAM_RESOLUTION_LU).max(0.0);
+        gated(&self.histogram[(first_bin as usize).min(HISTOGRAM_BINS - 1)..])
+    }
+}
+
+impl LoudnessMeter {
+    fn finish_sub_block(&mut self) {
+        self.sub_block = (self.sub_block + 1) % SHORT_TERM_LEN;
+        self.sub_blocks[self.sub_block] = self.sum / self.pos as f32;
+        self.num_sub_blocks = (self.num_sub_blocks + 1).min(SHORT_TERM_LEN);
+        self.pos = 0;
+        self.sum = 0.0;
+
+        // Every sub-block completes a new gating block once there are enough of them
+        if self.num_sub_blocks >= GATING_BLOCK_LEN {
+            let power = self.mean_power(GATING_BLOCK_LEN);
+            let loudness = lufs(power);
+            if loudness > ABSOLUTE_GATE_LUFS {
+                let bin = ((loudness - ABSOLUTE_GATE_LUFS) / HISTOGRAM_RESOLUTION_LU) as usize;
+                let (count, sum) = &mut self.histogram[bin.min(HISTOGRAM_BINS - 1)];
+                *count += 1;
+                *sum += power as f64;
+            }
+        }
+    }
+
+    /// The mean power of the last `len` sub-blocks.
+    fn mean_power(&self, len: usize) -> f32 {
+        if len == 0 {
+            return 0.0;
+        }
+
+        (0..len)
+            .map(|age| self.sub_blocks[(self.sub_block + SHORT_TERM_LEN - age) % SHORT_TERM_LEN])
+            .sum::<f32>()
+            / len as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    fn sine(meter: &mut LoudnessMeter, level_db: f32, seconds: f32) {
+        let amplitude = 10.0f32.powf(level_db / 20.0);
+        for i in 0..(48000.0 * seconds) as usize {
+            let sample = amplitude * (TAU * 997.0 * i as f32 / 48000.0).sin();
+            meter.add_frame(&[sample, sample]);
+        }
+    }
+
+    #[test]
+    fn stereo_sine_reads_its_level() {
+        // The reference signal from EBU Tech 3341, a -23 dBFS sine in both channels is -23 LUFS
+        let mut meter = LoudnessMeter::new(2, 48000.0);
+        sine(&mut meter, -23.0, 10.0);
+
+        assert!(
+            (meter.short_term() + 23.0).abs() < 0.1,
+            "{}",
+            meter.short_term()
+        );
+        assert!(
+            (meter.integrated() + 23.0).abs() < 0.1,
+            "{}",
+            meter.integrated()
+        );
+    }
+
+    #[test]
+    fn quiet_passages_are_gated() {
+        // The -36 dBFS parts are more than 10 LU below the rest, so it doesn't pull the integrated
+        // loudness down
+        let mut meter = LoudnessMeter::new(2, 48000.0);
+        sine(&mut meter, -36.0, 10.0);
+        sine(&mut meter, -23.0, 60.0);
+        sine(&mut meter, -36.0, 10.0);
+
+        assert!(
+            (meter.integrated() + 23.0).abs() < 0.2,
+            "{}",
+            meter.integrated()
+        );
+        assert!(
+            (meter.short_term() + 36.0).abs() < 0.1,
+            "{}",
+            meter.short_term()
+        );
+    }
+}
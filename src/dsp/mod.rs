@@ -0,0 +1,636 @@
+use crate::params::{Interpolation, LfoShape};
+use std::f32::consts::{PI, TAU};
+
+pub mod block;
+pub mod character;
+pub mod delay_line;
+pub mod diffusion;
+pub mod euclidean;
+pub mod fft;
+pub mod frequency_shifter;
+pub mod loudness;
+pub mod modes;
+pub mod oversampling;
+pub mod reverb;
+pub mod stutter;
+
+pub use delay_line::DelayLine;
+
+/// The sample type used by the delay buffers and the feedback path. With the `f64` feature this
+/// is double precision, so rounding errors don't build up audibly at very high feedback amounts.
+#[cfg(feature = "f64")]
+pub type Sample = f64;
+#[cfg(not(feature = "f64"))]
+pub type Sample = f32;
+
+/// Samples quieter than this are flushed to zero. This is far below anything audible, but
+/// decaying feedback tails and filter states would otherwise end up as denormals, which are very
+/// slow to process.
+const DENORMAL_THRESHOLD: Sample = 1e-20;
+
+/// Replace denormal and near-denormal values with zero.
+pub fn flush_denormal(sample: Sample) -> Sample {
+    if sample.abs() < DENORMAL_THRESHOLD {
+        0.0
+    } else {
+        sample
+    }
+}
+
+/// Convert a [`Sample`] back to the `f32` the host and the editor work with.
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f32(sample: Sample) -> f32 {
+    sample as f32
+}
+
+/// A granular pitch shifter with two read heads that sweep through a short buffer half a window
+/// apart. Each head is faded in and out with a Hann window so the grains overlap seamlessly.
+pub struct PitchShifter {
+    buffer: Vec<Sample>,
+    write_pos: usize,
+    /// The position within the grain window, in `[0, 1)`.
+    phase: f32,
+}
+
+impl PitchShifter {
+    /// Create a pitch shifter with grains of `window_len` samples.
+    pub fn new(window_len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; window_len + 2],
+            write_pos: 0,
+            phase: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_pos = 0;
+        self.phase = 0.0;
+    }
+
+    /// Process a sample, shifting its pitch by `ratio`. A ratio of 2 shifts up an octave.
+    pub fn process(&mut self, input: Sample, ratio: f32, interpolation: Interpolation) -> Sample {
+        let len = self.buffer.len();
+        let window_len = (len - 2) as f32;
+        self.buffer[self.write_pos] = input;
+
+        // Reading faster than the write head shrinks the delay, which raises the pitch
+        self.phase = (self.phase + (1.0 - ratio) / window_len).rem_euclid(1.0);
+        let mut output = 0.0;
+        for head_offset in [0.0, 0.5] {
+            let phase = (self.phase + head_offset).fract();
+            let window = (phase * PI).sin().powi(2);
+            let read_pos = self.write_pos as f32 - phase * window_len;
+            output += interpolation.read(&self.buffer, read_pos) * window as Sample;
+        }
+
+        self.write_pos = (self.write_pos + 1) % len;
+
+        output
+    }
+}
+
+/// A peak envelope follower with separate attack and release times, and a hold time before the
+/// release starts.
+#[derive(Default)]
+pub struct EnvelopeFollower {
+    envelope: f32,
+    /// The number of samples left before the envelope starts releasing.
+    hold_remaining: usize,
+}
+
+impl EnvelopeFollower {
+    /// Process a sample using attack and release coefficients computed with
+    /// [`time_constant_coefficient()`]. After the input falls below the envelope, the envelope is
+    /// held for `hold` samples before it's released.
+    pub fn process(&mut self, input: f32, attack: f32, release: f32, hold: usize) -> f32 {
+        let input = input.abs();
+        if input > self.envelope {
+            self.envelope += (input - self.envelope) * attack;
+            self.hold_remaining = hold;
+        } else if self.hold_remaining > 0 {
+            self.hold_remaining -= 1;
+        } else {
+            self.envelope += (input - self.envelope) * release;
+        }
+
+        self.envelope
+    }
+}
+
+/// Compute the coefficient for a one-pole smoother that converges with a time constant of
+/// `seconds`.
+pub fn time_constant_coefficient(seconds: f32, sample_rate: f32) -> f32 {
+    1.0 - (-1.0 / (seconds * sample_rate)).exp()
+}
+
+/// A small xorshift pseudo-random number generator. This is cheap and deterministic, which is
+/// all the modulation sources need.
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // The state may never be zero
+        Self { state: seed.max(1) }
+    }
+
+    /// A random value in `[-1, 1]`.
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+
+        (self.state as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+/// Emulates tape transport instability with a slow wow LFO, a faster flutter LFO, and a random
+/// drift component. The output is a read position offset in `[0, 1]` that gets scaled to the
+/// modulation depth.
+pub struct WowFlutter {
+    wow_phase: f32,
+    flutter_phase: f32,
+    rng: Rng,
+    /// The drift slews towards a new random target every wow period.
+    drift: f32,
+    drift_target: f32,
+}
+
+impl WowFlutter {
+    pub fn new() -> Self {
+        Self {
+            wow_phase: 0.0,
+            flutter_phase: 0.0,
+            rng: Rng::new(0x5eed),
+            drift: 0.0,
+            drift_target: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn next(&mut self, rate: f32, sample_rate: f32) -> f32 {
+        let wow_increment = rate / sample_rate;
+        self.wow_phase += wow_increment;
+        if self.wow_phase >= 1.0 {
+            self.wow_phase -= 1.0;
+            self.drift_target = self.rng.next_bipolar();
+        }
+        // Flutter runs at a faster, non-harmonic rate so the two don't line up
+        self.flutter_phase = (self.flutter_phase + wow_increment * 7.3).fract();
+        self.drift += (self.drift_target - self.drift) * wow_increment;
+
+        let modulation = 0.6 * (self.wow_phase * TAU).sin()
+            + 0.15 * (self.flutter_phase * TAU).sin()
+            + 0.25 * self.drift;
+
+        (modulation + 1.0) / 2.0
+    }
+}
+
+/// The LFO that modulates the read position.
+pub struct Lfo {
+    /// The position within the current cycle, in `[0, 1)`.
+    phase: f32,
+    rng: Rng,
+    /// The random shape interpolates between these two values over every cycle, and the sample
+    /// and hold shape holds the second one.
+    random_from: f32,
+    random_to: f32,
+    /// The output after slew limiting.
+    output: f32,
+}
+
+impl Lfo {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            phase: 0.0,
+            rng: Rng::new(seed),
+            random_from: 0.0,
+            random_to: 0.0,
+            output: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self, seed: u32) {
+        *self = Self::new(seed);
+    }
+
+    /// Advance the LFO by `increment` cycles and return its output in `[-1, 1]`. The output
+    /// follows the waveform with a one-pole smoother using the `slew` coefficient.
+    pub fn next(&mut self, shape: LfoShape, increment: f32, slew: f32) -> f32 {
+        self.phase += increment;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            self.random_from = self.random_to;
+            self.random_to = self.rng.next_bipolar();
+        }
+
+        let target = match shape {
+            LfoShape::Sine => (self.phase * TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            LfoShape::Random => {
+                let t = (1.0 - (self.phase * PI).cos()) / 2.0;
+                self.random_from + (self.random_to - self.random_from) * t
+            }
+            LfoShape::SampleAndHold => self.random_to,
+        };
+        self.output += (target - self.output) * slew;
+
+        self.output
+    }
+}
+
+/// A value derived from parameters that's only recomputed when its inputs change, for filter
+/// coefficients that would otherwise cost transcendental functions every sample.
+pub struct Cached<K, V> {
+    cached: Option<(K, V)>,
+}
+
+impl<K, V> Default for Cached<K, V> {
+    fn default() -> Self {
+        Self { cached: None }
+    }
+}
+
+impl<K: PartialEq + Copy, V: Copy> Cached<K, V> {
+    pub fn get(&mut self, inputs: K, compute: impl FnOnce(K) -> V) -> V {
+        match self.cached {
+            Some((cached_inputs, value)) if cached_inputs == inputs => value,
+            _ => {
+                let value = compute(inputs);
+                self.cached = Some((inputs, value));
+                value
+            }
+        }
+    }
+}
+
+/// A one-pole filter. The same state can be used either as a low-pass or as a high-pass filter.
+#[derive(Default)]
+pub struct OnePole {
+    z1: Sample,
+}
+
+impl OnePole {
+    /// Compute the filter coefficient for a cutoff frequency in Hertz.
+    pub fn coefficient(cutoff: f32, sample_rate: f32) -> f32 {
+        1.0 - (-TAU * cutoff / sample_rate).exp()
+    }
+
+    pub fn lowpass(&mut self, input: Sample, coefficient: f32) -> Sample {
+        self.z1 = flush_denormal(self.z1 + (input - self.z1) * coefficient as Sample);
+        self.z1
+    }
+
+    pub fn highpass(&mut self, input: Sample, coefficient: f32) -> Sample {
+        input - self.lowpass(input, coefficient)
+    }
+
+    /// A first-order high shelf that scales everything above the cutoff by `gain`.
+    pub fn high_shelf(&mut self, input: Sample, coefficient: f32, gain: f32) -> Sample {
+        let low = self.lowpass(input, coefficient);
+        low + (input - low) * gain as Sample
+    }
+}
+
+/// The coefficients of a [`Biquad`], normalized so `a0` is one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiquadCoefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl BiquadCoefficients {
+    /// A peaking bell that boosts or cuts `gain_db` around `frequency`, with a bandwidth set by
+    /// `q`. These are the bell filter coefficients from the Audio EQ Cookbook.
+    pub fn peaking(frequency: f32, gain_db: f32, q: f32, sample_rate: f32) -> Self {
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let omega = TAU * frequency / sample_rate;
+        let alpha = omega.sin() / (2.0 * q);
+        let cos_omega = omega.cos();
+
+        let a0 = 1.0 + alpha / a;
+        Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * cos_omega / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * cos_omega / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+}
+
+/// A biquad filter in transposed direct form II, which behaves well when its coefficients change
+/// every sample.
+#[derive(Default, Clone, Copy)]
+pub struct Biquad {
+    s1: Sample,
+    s2: Sample,
+}
+
+impl Biquad {
+    pub fn process(&mut self, input: Sample, coefficients: &BiquadCoefficients) -> Sample {
+        let BiquadCoefficients { b0, b1, b2, a1, a2 } = *coefficients;
+        let output = input * b0 as Sample + self.s1;
+        self.s1 = flush_denormal(input * b1 as Sample - output * a1 as Sample + self.s2);
+        self.s2 = flush_denormal(input * b2 as Sample - output * a2 as Sample);
+
+        output
+    }
+}
+
+/// The Karplus-Strong loop filter, which averages every sample with the previous one. This damps
+/// the string's higher harmonics a little more on every pass.
+#[derive(Default)]
+pub struct LoopFilter {
+    x1: Sample,
+}
+
+impl LoopFilter {
+    /// The filter's delay in samples, which shortens the loop that tunes the string.
+    pub const DELAY: f32 = 0.5;
+
+    pub fn process(&mut self, input: Sample) -> Sample {
+        let output = (input + self.x1) * 0.5;
+        self.x1 = input;
+        output
+    }
+}
+
+/// A DC blocking high-pass filter with a very low cutoff. Summing the repeats back into the
+/// buffer and the asymmetric waveshapers can both build up a DC offset over time.
+#[derive(Default)]
+pub struct DcBlocker {
+    x1: Sample,
+    y1: Sample,
+}
+
+impl DcBlocker {
+    /// The cutoff frequency in Hertz, well below anything audible.
+    const CUTOFF: f32 = 5.0;
+
+    /// Compute the filter's pole for a sample rate.
+    pub fn coefficient(sample_rate: f32) -> f32 {
+        1.0 - TAU * Self::CUTOFF / sample_rate
+    }
+
+    pub fn process(&mut self, input: Sample, coefficient: f32) -> Sample {
+        self.y1 = flush_denormal(input - self.x1 + self.y1 * coefficient as Sample);
+        self.x1 = input;
+        self.y1
+    }
+}
+
+/// A bit crusher and sample rate reducer. The held sample only changes when its phase wraps
+/// around, and it's quantized to a lower bit depth.
+#[derive(Default)]
+pub struct Crusher {
+    held: Sample,
+    /// The position within the current held sample, in `[0, 1)`.
+    phase: f32,
+}
+
+impl Crusher {
+    /// The bit depth at which quantizing is skipped.
+    pub const MAX_BITS: f32 = 24.0;
+    /// The sample rate in Hertz at which the sample rate reduction is skipped, even when running
+    /// at a higher sample rate.
+    pub const MAX_RATE: f32 = 48000.0;
+
+    /// Process a sample. `bits` can be fractional for smooth transitions, and `rate` is the
+    /// reduced sample rate in Hertz.
+    pub fn process(&mut self, input: Sample, bits: f32, rate: f32, sample_rate: f32) -> Sample {
+        let ratio = rate / sample_rate;
+        if ratio >= 1.0 || rate >= Self::MAX_RATE {
+            self.phase = 0.0;
+            self.held = input;
+        } else {
+            self.phase += ratio;
+            if self.phase >= 1.0 {
+                self.phase -= 1.0;
+                self.held = input;
+            }
+        }
+
+        if bits >= Self::MAX_BITS {
+            self.held
+        } else {
+            let steps = 2.0f32.powf(bits - 1.0) as Sample;
+            (self.held * steps).round() / steps
+        }
+    }
+}
+
+/// A soft limiter without lookahead. Samples below half of `ceiling` pass through untouched,
+/// and everything above that is smoothly compressed so it never exceeds `ceiling`.
+pub fn soft_limit(sample: Sample, ceiling: Sample) -> Sample {
+    let knee = ceiling * 0.5;
+    let magnitude = sample.abs();
+    if magnitude <= knee {
+        sample
+    } else {
+        let limited = knee + (ceiling - knee) * ((magnitude - knee) / (ceiling - knee)).tanh();
+        limited.copysign(sample)
+    }
+}
+
+/// Scales the side signal of a pair of channels, and optionally folds the low end to mono.
+#[derive(Default)]
+pub struct StereoWidth {
+    side_low: OnePole,
+}
+
+impl StereoWidth {
+    /// A `width` of zero sums the pair to mono, one leaves it untouched, and two doubles the side
+    /// signal. `mono_below` is the coefficient of the high-pass filter applied to the side signal
+    /// when the low end is folded to mono.
+    pub fn process(
+        &mut self,
+        left: f32,
+        right: f32,
+        width: f32,
+        mono_below: Option<f32>,
+    ) -> (f32, f32) {
+        let mid = (left + right) * 0.5;
+        let mut side = (left - right) * 0.5;
+        if let Some(coefficient) = mono_below {
+            side = to_f32(self.side_low.highpass(side as Sample, coefficient));
+        }
+        side *= width;
+
+        (mid + side, mid - side)
+    }
+}
+
+/// The channel that receives `channel`'s repeats in ping-pong mode. Channels are paired up as
+/// left/right pairs, and a channel without a partner feeds itself.
+pub fn ping_pong_partner(channel: usize, num_channels: usize) -> usize {
+    let partner = channel ^ 1;
+    if partner < num_channels {
+        partner
+    } else {
+        channel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_holds_before_releasing() {
+        let mut follower = EnvelopeFollower::default();
+        assert_eq!(follower.process(1.0, 1.0, 0.5, 2), 1.0);
+        assert_eq!(follower.process(0.0, 1.0, 0.5, 2), 1.0);
+        assert_eq!(follower.process(0.0, 1.0, 0.5, 2), 1.0);
+        assert_eq!(follower.process(0.0, 1.0, 0.5, 2), 0.5);
+    }
+
+    #[test]
+    fn peaking_bell_only_changes_its_band() {
+        let sample_rate = 48000.0;
+        let coefficients = BiquadCoefficients::peaking(1000.0, -12.0, 2.0, sample_rate);
+        let peak_level = |frequency: f32| {
+            let mut filter = Biquad::default();
+            (0..48000)
+                .map(|i| {
+                    let input = (TAU * frequency * i as f32 / sample_rate).sin();
+                    filter.process(input as Sample, &coefficients)
+                })
+                .skip(24000)
+                .fold(0.0, |peak: f32, sample| peak.max(to_f32(sample).abs()))
+        };
+
+        // 12 dB down is a gain of about 0.25 at the center frequency
+        assert!((peak_level(1000.0) - 0.251).abs() < 0.01);
+        assert!((peak_level(100.0) - 1.0).abs() < 0.02);
+        assert!((peak_level(10000.0) - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn cached_values_follow_their_inputs() {
+        let mut computed = 0;
+        let mut cached = Cached::default();
+        let mut get = |cutoff: f32| {
+            cached.get((cutoff, 48000.0), |(cutoff, sample_rate)| {
+                computed += 1;
+                OnePole::coefficient(cutoff, sample_rate)
+            })
+        };
+
+        let coefficient = get(1000.0);
+        assert_eq!(get(1000.0), coefficient);
+        assert_eq!(get(2000.0), OnePole::coefficient(2000.0, 48000.0));
+        assert_eq!(get(1000.0), coefficient);
+        assert_eq!(computed, 3);
+    }
+
+    #[test]
+    fn high_shelf_leaves_the_low_end_alone() {
+        let coefficient = OnePole::coefficient(100.0, 48000.0);
+        let mut shelf = OnePole::default();
+        for _ in 0..48000 {
+            shelf.high_shelf(1.0, coefficient, 0.5);
+        }
+        assert!((to_f32(shelf.high_shelf(1.0, coefficient, 0.5)) - 1.0).abs() < 1e-4);
+
+        // A step is mostly made of high frequencies at first
+        let mut shelf = OnePole::default();
+        assert!((to_f32(shelf.high_shelf(1.0, coefficient, 0.5)) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn loop_filter_removes_nyquist() {
+        let mut filter = LoopFilter::default();
+        filter.process(1.0);
+        for i in 1..10 {
+            let input = if i % 2 == 0 { 1.0 } else { -1.0 };
+            assert_eq!(filter.process(input), 0.0);
+        }
+    }
+
+    #[test]
+    fn crusher_holds_and_quantizes() {
+        assert_eq!(
+            Crusher::default().process(0.3, Crusher::MAX_BITS, Crusher::MAX_RATE, 96000.0),
+            0.3
+        );
+
+        // Two bits leave steps of a half, and a quarter of the sample rate holds every fourth
+        // sample
+        let mut crusher = Crusher::default();
+        let output: Vec<Sample> = [-0.1, -0.2, -0.3, -0.4, 0.1, 0.2, 0.3, 0.0]
+            .into_iter()
+            .map(|input| crusher.process(input, 2.0, 12000.0, 48000.0))
+            .collect();
+        assert_eq!(output, [0.0, 0.0, 0.0, -0.5, -0.5, -0.5, -0.5, 0.0]);
+    }
+
+    #[test]
+    fn stereo_width_scales_the_sides() {
+        let mut width = StereoWidth::default();
+        assert_eq!(width.process(1.0, 0.5, 1.0, None), (1.0, 0.5));
+        assert_eq!(width.process(1.0, 0.5, 0.0, None), (0.75, 0.75));
+        assert_eq!(width.process(1.0, 0.5, 2.0, None), (1.25, 0.25));
+
+        // A constant offset between the channels is as low as it gets
+        let coefficient = OnePole::coefficient(100.0, 48000.0);
+        let (mut left, mut right) = (0.0, 0.0);
+        for _ in 0..48000 {
+            (left, right) = width.process(1.0, 0.5, 1.0, Some(coefficient));
+        }
+        assert!((left - right).abs() < 1e-4);
+    }
+
+    #[test]
+    fn soft_limit_stays_below_the_ceiling() {
+        assert_eq!(soft_limit(0.25, 1.0), 0.25);
+        assert_eq!(soft_limit(-0.5, 1.0), -0.5);
+
+        for sample in [0.75, 1.0, 4.0, 1000.0] {
+            let limited = soft_limit(sample, 0.5);
+            assert!(
+                limited > 0.25 && limited <= 0.5,
+                "{sample} became {limited}"
+            );
+            assert_eq!(soft_limit(-sample, 0.5), -limited);
+        }
+    }
+
+    #[test]
+    fn lfo_shapes_stay_in_range() {
+        for shape in [
+            LfoShape::Sine,
+            LfoShape::Triangle,
+            LfoShape::Random,
+            LfoShape::SampleAndHold,
+        ] {
+            let mut lfo = Lfo::new(1);
+            for _ in 0..1000 {
+                let value = lfo.next(shape, 0.013, 1.0);
+                assert!((-1.0..=1.0).contains(&value), "{shape:?} produced {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn sample_and_hold_is_reproducible() {
+        let run = |seed| {
+            let mut lfo = Lfo::new(seed);
+            (0..100)
+                .map(|_| lfo.next(LfoShape::SampleAndHold, 0.1, 0.5))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+}
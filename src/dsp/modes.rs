@@ -0,0 +1,257 @@
+use super::euclidean::TapPattern;
+use super::{to_f32, DelayLine, Sample};
+use crate::params::DelayMode;
+
+/// The signals a mode can combine for a single sample.
+#[derive(Debug, Clone, Copy)]
+pub struct Taps {
+    /// The input after the gain stage.
+    pub dry: f32,
+    /// The primary tap, read back at the delay time.
+    pub primary: f32,
+    /// The second tap, read back at the second tap's delay time.
+    pub second: f32,
+}
+
+/// A mode's dry and wet signals. Modes that replace the input set `dry` to zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModeOutput {
+    pub dry: f32,
+    pub wet: f32,
+}
+
+/// Run `mode` for a single sample. This is called before the delay line's write and read
+/// positions advance, and the glitchy modes move the read position on top of that. `ratio` is the
+/// shuffle ratio used by the shuffle and chaos modes, and `jitter` scales the distance of those
+/// modes' jumps. Ring Feed is oversampled by `oversampling`, which delays its output, and the
+/// Euclidean and multi-tap modes read the taps in `pattern`.
+pub fn process(
+    mode: DelayMode,
+    line: &mut DelayLine,
+    taps: Taps,
+    ratio: usize,
+    jitter: f32,
+    oversampling: usize,
+    pattern: &TapPattern,
+) -> ModeOutput {
+    match mode {
+        DelayMode::Echo
+        | DelayMode::PingPong
+        | DelayMode::Karplus
+        | DelayMode::Comb
+        | DelayMode::Doubler => echo(taps),
+        DelayMode::Glitch => glitch(line, taps, jitter),
+        DelayMode::Replace => replace(taps),
+        DelayMode::RingFeed => ring_feed(line, taps, oversampling),
+        DelayMode::DualTap => dual_tap(taps),
+        DelayMode::Shuffle => shuffle(line, taps, ratio, jitter),
+        DelayMode::Chaos => chaos(line, taps, ratio, jitter),
+        DelayMode::Euclidean | DelayMode::MultiTap => euclidean(line, taps, pattern),
+    }
+}
+
+/// A plain delay. The ping-pong, Karplus-Strong, comb and doubler modes also use this, their
+/// routing, loop filters and delay times are handled around the delay line.
+pub fn echo(taps: Taps) -> ModeOutput {
+    ModeOutput {
+        dry: taps.dry,
+        wet: taps.primary,
+    }
+}
+
+pub fn glitch(line: &mut DelayLine, taps: Taps, jitter: f32) -> ModeOutput {
+    glitch_step(line, jitter);
+    echo(taps)
+}
+
+pub fn replace(taps: Taps) -> ModeOutput {
+    ModeOutput {
+        dry: 0.0,
+        wet: taps.primary,
+    }
+}
+
+pub fn ring_feed(line: &mut DelayLine, taps: Taps, oversampling: usize) -> ModeOutput {
+    let wet = line.ring.process(
+        [taps.dry as Sample, taps.primary as Sample],
+        oversampling,
+        |[dry, primary]| dry * primary,
+    );
+
+    ModeOutput {
+        dry: 0.0,
+        wet: to_f32(wet),
+    }
+}
+
+pub fn dual_tap(taps: Taps) -> ModeOutput {
+    ModeOutput {
+        dry: taps.dry,
+        wet: taps.primary + taps.second,
+    }
+}
+
+pub fn shuffle(line: &mut DelayLine, taps: Taps, ratio: usize, jitter: f32) -> ModeOutput {
+    shuffle_step(line, ratio, jitter);
+    echo(taps)
+}
+
+pub fn chaos(line: &mut DelayLine, taps: Taps, ratio: usize, jitter: f32) -> ModeOutput {
+    shuffle_step(line, ratio, jitter);
+    glitch_step(line, jitter);
+    dual_tap(taps)
+}
+
+pub fn euclidean(line: &DelayLine, taps: Taps, pattern: &TapPattern) -> ModeOutput {
+    let wet: Sample = pattern
+        .offsets()
+        .iter()
+        .zip(pattern.levels())
+        .map(|(&offset, &level)| line.read(offset) * level as Sample)
+        .sum();
+
+    ModeOutput {
+        dry: taps.dry,
+        wet: to_f32(wet),
+    }
+}
+
+/// During the first half of the buffer the read position moves at double speed, which pitches
+/// the repeats up. After that it occasionally steps back or skips ahead.
+fn glitch_step(line: &mut DelayLine, jitter: f32) {
+    let len = line.buffer.len();
+    if line.iterdelay > len / 2 {
+        if line.iterdelay.is_multiple_of(5) {
+            line.shift_read(jump(-1, jitter));
+        } else if line.iterdelay.is_multiple_of(7) {
+            line.shift_read(jump(2, jitter));
+        };
+    } else {
+        line.shift_read(jump(1, jitter));
+    };
+}
+
+/// Every `ratio`-th sample the read position jumps `ratio` samples back or forward.
+fn shuffle_step(line: &mut DelayLine, ratio: usize, jitter: f32) {
+    if line.iterdelay.is_multiple_of(ratio) {
+        if line.iterdelay.is_multiple_of(2) {
+            line.shift_read(jump(-(ratio as isize), jitter));
+        } else {
+            line.shift_read(jump(ratio as isize, jitter));
+        };
+    };
+}
+
+/// Scale a jump of `distance` samples by the jitter amount, rounded to whole samples.
+fn jump(distance: isize, jitter: f32) -> isize {
+    (distance as f32 * jitter).round() as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAPS: Taps = Taps {
+        dry: 0.5,
+        primary: 0.25,
+        second: 0.125,
+    };
+
+    fn line_at(len: usize, iterdelay: usize, iterrepeats: usize) -> DelayLine {
+        let mut line = DelayLine::new(len, 16);
+        line.iterdelay = iterdelay;
+        line.iterrepeats = iterrepeats;
+        line
+    }
+
+    #[test]
+    fn replace_and_ring_feed_silence_the_dry_signal() {
+        assert_eq!(
+            replace(TAPS),
+            ModeOutput {
+                dry: 0.0,
+                wet: 0.25
+            }
+        );
+        assert_eq!(
+            ring_feed(&mut line_at(128, 0, 0), TAPS, 1),
+            ModeOutput {
+                dry: 0.0,
+                wet: 0.125
+            }
+        );
+    }
+
+    #[test]
+    fn dual_tap_sums_both_taps() {
+        assert_eq!(
+            dual_tap(TAPS),
+            ModeOutput {
+                dry: 0.5,
+                wet: 0.375
+            }
+        );
+    }
+
+    #[test]
+    fn euclidean_sums_the_pattern_taps() {
+        let mut line = line_at(16, 0, 0);
+        for i in 0..16 {
+            line.write_behind(0, i as Sample);
+            line.tick();
+        }
+
+        // A tap every two samples over an eight sample bar, at half the gain since there are four
+        // pulses
+        let pattern = TapPattern::new(4, 4, 0, 8);
+        let output = euclidean(&line, TAPS, &pattern);
+        assert_eq!(output.dry, 0.5);
+        assert_eq!(output.wet, (8.0 + 10.0 + 12.0 + 14.0) * 0.5);
+    }
+
+    #[test]
+    fn glitch_moves_at_double_speed_in_the_first_half() {
+        let mut line = line_at(128, 10, 20);
+        glitch(&mut line, TAPS, 1.0);
+        assert_eq!(line.iterrepeats, 21);
+
+        let mut line = line_at(128, 70, 20);
+        glitch(&mut line, TAPS, 1.0);
+        assert_eq!(line.iterrepeats, 19);
+
+        let mut line = line_at(128, 77, 20);
+        glitch(&mut line, TAPS, 1.0);
+        assert_eq!(line.iterrepeats, 22);
+    }
+
+    #[test]
+    fn shuffle_jumps_every_ratio_samples() {
+        let mut line = line_at(128, 12, 50);
+        shuffle(&mut line, TAPS, 4, 1.0);
+        assert_eq!(line.iterrepeats, 46);
+
+        let mut line = line_at(128, 9, 50);
+        shuffle(&mut line, TAPS, 3, 1.0);
+        assert_eq!(line.iterrepeats, 53);
+
+        let mut line = line_at(128, 13, 50);
+        shuffle(&mut line, TAPS, 3, 1.0);
+        assert_eq!(line.iterrepeats, 50);
+    }
+
+    #[test]
+    fn jitter_scales_the_jumps() {
+        let mut line = line_at(128, 12, 50);
+        shuffle(&mut line, TAPS, 4, 0.0);
+        assert_eq!(line.iterrepeats, 50);
+
+        let mut line = line_at(128, 12, 50);
+        shuffle(&mut line, TAPS, 4, 2.0);
+        assert_eq!(line.iterrepeats, 42);
+
+        // Jumping back past the start of the buffer wraps around to the end
+        let mut line = line_at(128, 70, 0);
+        glitch(&mut line, TAPS, 2.0);
+        assert_eq!(line.iterrepeats, 126);
+    }
+}
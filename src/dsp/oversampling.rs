@@ -0,0 +1,284 @@
+use super::Sample;
+use std::f64::consts::PI;
+
+/// The highest supported oversampling factor.
+pub const MAX_FACTOR: usize = 4;
+
+/// The number of taps in each of the two polyphase branches of the halfband filters. One branch
+/// is a pure delay, so only this many multiplications are needed per pair of samples.
+const BRANCH_LEN: usize = 12;
+/// The delay of the halfband filters' delay branch in samples at the lower rate.
+const BRANCH_DELAY: usize = BRANCH_LEN / 2 - 1;
+
+/// The longest latency [`latency()`] can return.
+pub const MAX_LATENCY: usize = (BRANCH_LEN - 1) + BRANCH_LEN / 2;
+
+/// The longest delay a [`LatencyCompensation`] can add. Besides the oversampling latency this
+/// also covers the ducker's lookahead.
+pub const MAX_COMPENSATION: usize = 4095;
+
+/// The latency in samples at the original sample rate when oversampling by `factor`. Every
+/// halfband filter delays the signal by `BRANCH_LEN - 1` samples at its higher rate, and at 4x an
+/// extra sample at 2x is added so this is always a whole number of samples.
+pub fn latency(factor: usize) -> usize {
+    match factor {
+        2 => BRANCH_LEN - 1,
+        4 => MAX_LATENCY,
+        _ => 0,
+    }
+}
+
+/// The coefficients of the halfband filters' non-trivial branch, which are the even taps of a
+/// Blackman windowed sinc filter with its cutoff at a quarter of the higher sample rate.
+fn branch_coefficients() -> [Sample; BRANCH_LEN] {
+    let len = 2 * BRANCH_LEN - 1;
+    let center = (len / 2) as f64;
+    let mut coefficients = [0.0; BRANCH_LEN];
+    for (i, coefficient) in coefficients.iter_mut().enumerate() {
+        let x = (2 * i) as f64 - center;
+        let sinc = (PI * x / 2.0).sin() / (PI * x);
+        let t = (2 * i + 1) as f64 / (len + 1) as f64;
+        let window = 0.42 - 0.5 * (2.0 * PI * t).cos() + 0.08 * (4.0 * PI * t).cos();
+        *coefficient = (sinc * window) as Sample;
+    }
+
+    // Together with the delay branch's 0.5 this gives the filter unity gain at DC
+    let sum: Sample = coefficients.iter().sum();
+    for coefficient in &mut coefficients {
+        *coefficient *= 0.5 / sum;
+    }
+
+    coefficients
+}
+
+/// Doubles the sample rate of a signal with a polyphase halfband filter.
+#[derive(Default)]
+struct HalfbandUp {
+    /// The most recent input first.
+    history: [Sample; BRANCH_LEN],
+}
+
+impl HalfbandUp {
+    fn process(&mut self, input: Sample, coefficients: &[Sample; BRANCH_LEN]) -> [Sample; 2] {
+        self.history.copy_within(..BRANCH_LEN - 1, 1);
+        self.history[0] = input;
+
+        // The zero stuffed samples are skipped, so both outputs are scaled back up by two
+        let filtered: Sample = coefficients
+            .iter()
+            .zip(&self.history)
+            .map(|(coefficient, sample)| coefficient * sample)
+            .sum();
+
+        [filtered * 2.0, self.history[BRANCH_DELAY]]
+    }
+}
+
+/// Halves the sample rate of a signal with a polyphase halfband filter.
+#[derive(Default)]
+struct HalfbandDown {
+    /// The even and odd input samples, the most recent ones first.
+    even: [Sample; BRANCH_LEN],
+    odd: [Sample; BRANCH_LEN],
+}
+
+impl HalfbandDown {
+    fn process(&mut self, input: [Sample; 2], coefficients: &[Sample; BRANCH_LEN]) -> Sample {
+        self.even.copy_within(..BRANCH_LEN - 1, 1);
+        self.even[0] = input[0];
+        self.odd.copy_within(..BRANCH_LEN - 1, 1);
+        self.odd[0] = input[1];
+
+        let filtered: Sample = coefficients
+            .iter()
+            .zip(&self.even)
+            .map(|(coefficient, sample)| coefficient * sample)
+            .sum();
+
+        filtered + self.odd[BRANCH_DELAY + 1] * 0.5
+    }
+}
+
+/// Raises the sample rate of a signal by a factor of two or four using cascaded halfband
+/// filters. A factor of one passes the signal through as is.
+pub struct Upsampler {
+    coefficients: [Sample; BRANCH_LEN],
+    /// The first stage goes from 1x to 2x, and the second stage from 2x to 4x.
+    stages: [HalfbandUp; 2],
+}
+
+impl Default for Upsampler {
+    fn default() -> Self {
+        Self {
+            coefficients: branch_coefficients(),
+            stages: Default::default(),
+        }
+    }
+}
+
+impl Upsampler {
+    /// Produce `factor` samples at the higher rate for a single input sample. Only the first
+    /// `factor` samples of the output are used.
+    pub fn process(&mut self, input: Sample, factor: usize) -> [Sample; MAX_FACTOR] {
+        let mut output = [0.0; MAX_FACTOR];
+        match factor {
+            2 => {
+                let [a, b] = self.stages[0].process(input, &self.coefficients);
+                output[..2].copy_from_slice(&[a, b]);
+            }
+            4 => {
+                let [a, b] = self.stages[0].process(input, &self.coefficients);
+                let [a0, a1] = self.stages[1].process(a, &self.coefficients);
+                let [b0, b1] = self.stages[1].process(b, &self.coefficients);
+                output = [a0, a1, b0, b1];
+            }
+            _ => output[0] = input,
+        }
+
+        output
+    }
+}
+
+/// The counterpart to [`Upsampler`], which filters and decimates the oversampled signal back to
+/// the original sample rate.
+pub struct Downsampler {
+    coefficients: [Sample; BRANCH_LEN],
+    stages: [HalfbandDown; 2],
+    /// The 2x sample that was delayed by one sample at 4x to keep the latency whole.
+    pad: Sample,
+}
+
+impl Default for Downsampler {
+    fn default() -> Self {
+        Self {
+            coefficients: branch_coefficients(),
+            stages: Default::default(),
+            pad: 0.0,
+        }
+    }
+}
+
+impl Downsampler {
+    pub fn process(&mut self, input: [Sample; MAX_FACTOR], factor: usize) -> Sample {
+        match factor {
+            2 => self.stages[0].process([input[0], input[1]], &self.coefficients),
+            4 => {
+                let a = self.stages[1].process([input[0], input[1]], &self.coefficients);
+                let b = self.stages[1].process([input[2], input[3]], &self.coefficients);
+                let pad = std::mem::replace(&mut self.pad, b);
+                self.stages[0].process([pad, a], &self.coefficients)
+            }
+            _ => input[0],
+        }
+    }
+}
+
+/// A nonlinear function of `N` signals that's computed at a higher sample rate so it doesn't
+/// alias. The output is delayed by [`latency()`] samples.
+pub struct Oversampled<const N: usize> {
+    upsamplers: [Upsampler; N],
+    downsampler: Downsampler,
+}
+
+impl<const N: usize> Default for Oversampled<N> {
+    fn default() -> Self {
+        Self {
+            upsamplers: std::array::from_fn(|_| Upsampler::default()),
+            downsampler: Downsampler::default(),
+        }
+    }
+}
+
+impl<const N: usize> Oversampled<N> {
+    pub fn process(
+        &mut self,
+        inputs: [Sample; N],
+        factor: usize,
+        f: impl Fn([Sample; N]) -> Sample,
+    ) -> Sample {
+        let upsampled: [[Sample; MAX_FACTOR]; N] =
+            std::array::from_fn(|i| self.upsamplers[i].process(inputs[i], factor));
+
+        let mut output = [0.0; MAX_FACTOR];
+        for (i, output) in output.iter_mut().enumerate().take(factor.max(1)) {
+            *output = f(std::array::from_fn(|input| upsampled[input][i]));
+        }
+
+        self.downsampler.process(output, factor)
+    }
+}
+
+/// A short delay used to line signals up with the oversampled ones.
+pub struct LatencyCompensation<T> {
+    buffer: [T; MAX_COMPENSATION + 1],
+    pos: usize,
+}
+
+impl<T: Copy + Default> Default for LatencyCompensation<T> {
+    fn default() -> Self {
+        Self {
+            buffer: [T::default(); MAX_COMPENSATION + 1],
+            pos: 0,
+        }
+    }
+}
+
+impl<T: Copy + Default> LatencyCompensation<T> {
+    /// Delay `input` by `latency` samples.
+    pub fn process(&mut self, input: T, latency: usize) -> T {
+        let len = self.buffer.len();
+        self.buffer[self.pos] = input;
+        let output = self.buffer[(self.pos + len - latency.min(MAX_COMPENSATION)) % len];
+        self.pos = (self.pos + 1) % len;
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a unit impulse through an identity function and return the position of the peak.
+    fn impulse_peak(factor: usize) -> usize {
+        let mut oversampled = Oversampled::<1>::default();
+        let output: Vec<Sample> = (0..64)
+            .map(|i| {
+                let input = if i == 0 { 1.0 } else { 0.0 };
+                oversampled.process([input], factor, |[sample]| sample)
+            })
+            .collect();
+
+        (0..output.len())
+            .max_by(|&a, &b| output[a].abs().total_cmp(&output[b].abs()))
+            .unwrap()
+    }
+
+    #[test]
+    fn reported_latency_matches_the_impulse_response() {
+        assert_eq!(impulse_peak(1), latency(1));
+        assert_eq!(impulse_peak(2), latency(2));
+        assert_eq!(impulse_peak(4), latency(4));
+    }
+
+    #[test]
+    fn dc_passes_at_unity_gain() {
+        for factor in [2, 4] {
+            let mut oversampled = Oversampled::<1>::default();
+            let mut output = 0.0;
+            for _ in 0..64 {
+                output = oversampled.process([1.0], factor, |[sample]| sample);
+            }
+
+            assert!((output - 1.0).abs() < 1e-4, "{output} at {factor}x");
+        }
+    }
+
+    #[test]
+    fn compensation_delays_by_the_latency() {
+        let mut compensation = LatencyCompensation::default();
+        let output: Vec<f32> = (0..8).map(|i| compensation.process(i as f32, 3)).collect();
+
+        assert_eq!(output, [0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0]);
+    }
+}
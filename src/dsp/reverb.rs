@@ -0,0 +1,117 @@
+use super::OnePole;
+
+/// The feedback delay network's delay times in seconds at the largest size. These are spread out
+/// and don't share any common factors, so the echoes build up into a dense tail.
+const LINE_SECONDS: [f32; 4] = [0.0531, 0.0673, 0.0797, 0.0937];
+/// The smallest room scales the delay times by this much.
+const MIN_SIZE_SCALE: f32 = 0.2;
+/// The cutoff of the damping filters inside the network, so the tail gets darker as it decays.
+const DAMPING_HZ: f32 = 6000.0;
+/// -60 dB in nepers, used to turn the decay time into per line feedback gains.
+const RT60_NEPERS: f32 = 6.907_755;
+
+/// A small four line feedback delay network reverb for a single channel.
+pub struct Reverb {
+    lines: [Vec<f32>; 4],
+    write_pos: [usize; 4],
+    /// The states of the one-pole low-pass filters in the feedback paths.
+    damping: [f32; 4],
+    damping_coefficient: f32,
+    /// The delay times at the largest size, in samples.
+    max_delays: [f32; 4],
+    sample_rate: f32,
+}
+
+impl Reverb {
+    /// Create a reverb for a single channel. Every channel gets slightly different delay times
+    /// so the tail is wide.
+    pub fn new(channel: usize, sample_rate: f32) -> Self {
+        let spread = 1.0 + 0.05 * channel as f32;
+        let max_delays = LINE_SECONDS.map(|seconds| seconds * spread * sample_rate);
+
+        Self {
+            lines: max_delays.map(|delay| vec![0.0; delay.ceil() as usize + 2]),
+            write_pos: [0; 4],
+            damping: [0.0; 4],
+            damping_coefficient: OnePole::coefficient(DAMPING_HZ, sample_rate),
+            max_delays,
+            sample_rate,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.fill(0.0);
+        }
+        self.write_pos = [0; 4];
+        self.damping = [0.0; 4];
+    }
+
+    /// Process a sample and return only the reverb. `size` in `[0, 1]` scales the delay times,
+    /// and `decay` is the time in seconds it takes the tail to fall by 60 dB.
+    pub fn process(&mut self, input: f32, size: f32, decay: f32) -> f32 {
+        let scale = MIN_SIZE_SCALE + (1.0 - MIN_SIZE_SCALE) * size.clamp(0.0, 1.0);
+        let decay_samples = decay * self.sample_rate;
+
+        let mut outputs = [0.0; 4];
+        for (i, output) in outputs.iter_mut().enumerate() {
+            let line = &self.lines[i];
+            let len = line.len();
+            let delay = self.max_delays[i] * scale;
+            let read_pos = (self.write_pos[i] as f32 - delay).rem_euclid(len as f32);
+            let index = read_pos as usize % len;
+            let fraction = read_pos.fract();
+            let delayed = line[index] + (line[(index + 1) % len] - line[index]) * fraction;
+
+            // Every line loses `RT60_NEPERS` over the decay time, no matter how long it is
+            *output = delayed * (-RT60_NEPERS * delay / decay_samples).exp();
+        }
+
+        // The lines are mixed with a normalized Hadamard matrix, which keeps the energy
+        let [a, b, c, d] = outputs;
+        let mixed = [
+            (a + b + c + d) * 0.5,
+            (a - b + c - d) * 0.5,
+            (a + b - c - d) * 0.5,
+            (a - b - c + d) * 0.5,
+        ];
+        for (i, feedback) in mixed.into_iter().enumerate() {
+            let damping = &mut self.damping[i];
+            *damping += (input + feedback - *damping) * self.damping_coefficient;
+            // The tail is flushed to zero like in `flush_denormal()`, which works on `Sample`s
+            if damping.abs() < 1e-20 {
+                *damping = 0.0;
+            }
+
+            let line = &mut self.lines[i];
+            line[self.write_pos[i]] = *damping;
+            self.write_pos[i] = (self.write_pos[i] + 1) % line.len();
+        }
+
+        (a + b + c + d) * 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_decays() {
+        let mut reverb = Reverb::new(0, 48000.0);
+        let energy = |reverb: &mut Reverb, first: usize| {
+            (first..first + 4800)
+                .map(|i| {
+                    let input = if i == 0 { 1.0 } else { 0.0 };
+                    reverb.process(input, 0.5, 1.0).powi(2)
+                })
+                .sum::<f32>()
+        };
+
+        let early = energy(&mut reverb, 0);
+        let _ = energy(&mut reverb, 4800);
+        let late = energy(&mut reverb, 9600);
+        assert!(early > 0.0);
+        assert!(late < early * 0.1, "{early} {late}");
+    }
+}
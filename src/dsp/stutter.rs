@@ -0,0 +1,159 @@
+use super::Rng;
+
+/// Beat repeat on top of the delay buffer. The host's timeline is divided into slices, and at the
+/// start of every slice a random roll decides whether it plays the live signal or repeats of the
+/// slice that started `capture` beats earlier.
+pub struct Stutter {
+    rng: Rng,
+    /// The index of the current slice on the timeline, or `None` while the transport is stopped.
+    slice: Option<i64>,
+    /// Whether the current slice is being repeated.
+    repeating: bool,
+    /// The number of samples since the current slice started.
+    elapsed: f32,
+}
+
+/// The stutter parameters for a block, converted to beats and samples.
+#[derive(Debug, Clone, Copy)]
+pub struct StutterSettings {
+    /// The length of a slice in beats.
+    pub slice_beats: f64,
+    /// How far back the repeated slice starts, in beats.
+    pub capture_beats: f64,
+    /// The length of a beat in samples at the current tempo.
+    pub beat_samples: f32,
+    /// The probability of a slice being repeated, in `[0, 1]`.
+    pub chance: f32,
+    /// The playback speed of the repeats.
+    pub ratio: f32,
+    /// The length of the fades at both ends of every repeat, in samples.
+    pub fade_len: f32,
+}
+
+/// Where a sample of the repeats should be read from, and how much of the output it replaces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StutterTap {
+    /// The distance behind the write position in samples.
+    pub distance: f32,
+    pub gain: f32,
+}
+
+impl Stutter {
+    pub fn new() -> Self {
+        Self {
+            // The same seed is used every time so the repeats are reproducible
+            rng: Rng::new(0xbea7),
+            slice: None,
+            repeating: false,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Advance by one sample. `pos_beats` is the transport's position at this sample, or `None`
+    /// when the transport isn't running, in which case nothing is repeated.
+    pub fn next(&mut self, pos_beats: Option<f64>, settings: &StutterSettings) -> StutterTap {
+        let Some(pos_beats) = pos_beats else {
+            self.slice = None;
+            self.repeating = false;
+            return StutterTap {
+                distance: 0.0,
+                gain: 0.0,
+            };
+        };
+
+        let slice = (pos_beats / settings.slice_beats).floor() as i64;
+        if self.slice != Some(slice) {
+            self.slice = Some(slice);
+            self.repeating = (self.rng.next_bipolar() + 1.0) / 2.0 < settings.chance;
+            self.elapsed = 0.0;
+        }
+
+        // The repeated slice has to have been written completely before it can be played back
+        let slice_len = (settings.slice_beats as f32 * settings.beat_samples).max(1.0);
+        let capture_len = (settings.capture_beats as f32 * settings.beat_samples).max(slice_len);
+        let repeat_pos = (self.elapsed * settings.ratio) % slice_len;
+        let distance = capture_len + self.elapsed - repeat_pos;
+
+        // Every repeat fades in and out, and so does the slice as a whole since a pitched repeat
+        // may get cut off by the next slice
+        let fade_len = settings.fade_len.min(slice_len / 4.0).max(1.0);
+        let gain = if self.repeating {
+            (repeat_pos
+                .min(slice_len - repeat_pos)
+                .min(slice_len - self.elapsed)
+                / fade_len)
+                .clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self.elapsed += 1.0;
+
+        StutterTap { distance, gain }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: StutterSettings = StutterSettings {
+        slice_beats: 0.25,
+        capture_beats: 1.0,
+        beat_samples: 400.0,
+        chance: 1.0,
+        ratio: 1.0,
+        fade_len: 4.0,
+    };
+
+    #[test]
+    fn repeats_the_slice_from_the_capture_length_ago() {
+        let mut stutter = Stutter::new();
+        let taps: Vec<StutterTap> = (0..200)
+            .map(|i| stutter.next(Some(1.0 + i as f64 / 400.0), &SETTINGS))
+            .collect();
+
+        // At normal speed every 100 sample slice is a copy of the slice a beat earlier
+        assert_eq!(taps[0].distance, 400.0);
+        assert_eq!(taps[50].distance, 400.0);
+        assert_eq!(taps[150].distance, 400.0);
+        assert_eq!(taps[0].gain, 0.0);
+        assert_eq!(taps[50].gain, 1.0);
+        assert_eq!(taps[99].gain, 0.25);
+    }
+
+    #[test]
+    fn pitched_repeats_loop_within_the_slice() {
+        let mut stutter = Stutter::new();
+        let settings = StutterSettings {
+            ratio: 2.0,
+            ..SETTINGS
+        };
+        let taps: Vec<StutterTap> = (0..100)
+            .map(|i| stutter.next(Some(1.0 + i as f64 / 400.0), &settings))
+            .collect();
+
+        // At double speed the slice plays twice, catching up with the write position until it
+        // starts over
+        assert_eq!(taps[25].distance, 375.0);
+        assert_eq!(taps[50].distance, 450.0);
+        assert_eq!(taps[50].gain, 0.0);
+    }
+
+    #[test]
+    fn does_nothing_without_a_transport_or_a_chance() {
+        let mut stutter = Stutter::new();
+        assert_eq!(stutter.next(None, &SETTINGS).gain, 0.0);
+
+        let settings = StutterSettings {
+            chance: 0.0,
+            ..SETTINGS
+        };
+        for i in 0..1000 {
+            assert_eq!(stutter.next(Some(i as f64 / 100.0), &settings).gain, 0.0);
+        }
+    }
+}
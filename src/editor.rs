@@ -0,0 +1,1940 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use nih_plug_egui::resizable_window::ResizableWindow;
+use nih_plug_egui::{create_egui_editor, egui, widgets};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::dsp::fft::{fft, hann_window};
+use crate::midi::{MidiCcs, RemoteSysEx, SYSEX_MAX_VALUE};
+use crate::params::{MyplugParams, NoteDivision};
+use crate::presets::{factory_presets, Preset};
+use crate::taps::{quantize, NUM_TAP_SLOTS};
+use crate::Myplug;
+
+/// The smallest size the editor window can be resized to, in logical pixels.
+const MIN_WINDOW_SIZE: [f32; 2] = [300.0, 200.0];
+
+/// The zoom levels that can be picked on top of the display's scale factor.
+const UI_SCALES: [f32; 6] = [0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+
+/// The XY pad's width and height in logical pixels.
+const XY_PAD_SIZE: f32 = 160.0;
+
+/// How much slower the XY pad moves while dragging with Shift held.
+const XY_PAD_FINE_SCALE: f32 = 0.1;
+
+/// The tap timeline's width and height in logical pixels.
+const TAP_TIMELINE_SIZE: [f32; 2] = [320.0, 80.0];
+
+/// How close the pointer needs to be to a tap on the timeline to grab it, in logical pixels.
+const TAP_GRAB_DISTANCE: f32 = 6.0;
+
+/// The spectrum analyzer's FFT size, which is also the number of samples between updates.
+pub const SPECTRUM_FFT_SIZE: usize = 2048;
+
+/// The number of logarithmically spaced frequencies the spectrum is drawn with, and their range.
+const SPECTRUM_POINTS: usize = 160;
+const SPECTRUM_MIN_HZ: f32 = 20.0;
+const SPECTRUM_MAX_HZ: f32 = 20000.0;
+
+/// The spectrum analyzer's range in decibels.
+const SPECTRUM_MIN_DB: f32 = -90.0;
+const SPECTRUM_MAX_DB: f32 = 0.0;
+
+/// How fast the spectrum falls back after a peak, in decibels per second.
+const SPECTRUM_FALL_DB_PER_SECOND: f32 = 40.0;
+
+/// The number of points the delay buffer is decimated to for the editor's waveform view.
+const WAVEFORM_POINTS: usize = 300;
+
+/// The number of points the scope's sweep is decimated to.
+const SCOPE_POINTS: usize = 300;
+
+/// How long the scope takes for a sweep when it can't follow the host's tempo, in seconds.
+const SCOPE_FREE_SECONDS: f32 = 2.0;
+
+/// The number of the most recent output samples the goniometer draws.
+const GONIOMETER_POINTS: usize = 1024;
+
+/// The goniometer's width and height in logical pixels.
+const GONIOMETER_SIZE: f32 = 160.0;
+
+/// The integration time of the correlation meter, in seconds.
+const CORRELATION_SECONDS: f32 = 0.3;
+
+/// How long it takes for the peak meters to fall by 12 dB, in seconds.
+const METER_PEAK_DECAY_SECONDS: f32 = 0.5;
+
+/// The integration time of the RMS meters, in seconds.
+const METER_RMS_SECONDS: f32 = 0.3;
+
+/// The width of the level meters' peak hold readouts in logical pixels.
+const METER_HOLD_WIDTH: f32 = 80.0;
+
+/// The input, wet, and output levels shown in the editor.
+#[derive(Default)]
+pub struct Meters {
+    pub input: LevelMeter,
+    pub wet: LevelMeter,
+    pub output: LevelMeter,
+    /// The output's short-term and integrated loudness in LUFS.
+    pub short_term_lufs: AtomicF32,
+    pub integrated_lufs: AtomicF32,
+    /// Set by the editor to start measuring the integrated loudness over.
+    pub reset_integrated: AtomicBool,
+}
+
+/// The editor's ends of the displays that are fed by the audio thread.
+pub struct Displays {
+    pub waveform: Arc<Mutex<triple_buffer::Output<Waveform>>>,
+    pub scope: Arc<Mutex<triple_buffer::Output<Scope>>>,
+    pub goniometer: Arc<Mutex<triple_buffer::Output<Goniometer>>>,
+    pub spectrum: Arc<Mutex<Spectrum>>,
+}
+
+/// Peak and RMS levels stored as linear gain.
+#[derive(Default)]
+pub struct LevelMeter {
+    peak: AtomicF32,
+    rms: AtomicF32,
+    /// The highest peak since the editor last cleared it. Anything at or above 0 dBFS latches
+    /// the meter's clip indicator.
+    held_peak: AtomicF32,
+}
+
+/// Collects a block's samples and applies peak decay and RMS integration before publishing the
+/// results to a [`LevelMeter`].
+#[derive(Default)]
+pub struct MeterBallistics {
+    block_peak: f32,
+    block_sum_squares: f32,
+    block_len: usize,
+    peak: f32,
+    mean_square: f32,
+}
+
+impl MeterBallistics {
+    pub fn add(&mut self, sample: f32) {
+        self.block_peak = self.block_peak.max(sample.abs());
+        self.block_sum_squares += sample * sample;
+        self.block_len += 1;
+    }
+
+    /// Update the meter with the samples added since the last call. `num_samples` is the
+    /// block's length in samples, regardless of the channel count.
+    pub fn finish_block(&mut self, num_samples: usize, sample_rate: f32, meter: &LevelMeter) {
+        if self.block_len == 0 {
+            return;
+        }
+
+        let elapsed = num_samples as f32 / sample_rate;
+        // A decay of 12 dB over `METER_PEAK_DECAY_SECONDS`
+        let peak_decay = 0.25f32.powf(elapsed / METER_PEAK_DECAY_SECONDS);
+        let rms_coefficient = 1.0 - (-elapsed / METER_RMS_SECONDS).exp();
+
+        self.peak = self.block_peak.max(self.peak * peak_decay);
+        let block_mean_square = self.block_sum_squares / self.block_len as f32;
+        self.mean_square += (block_mean_square - self.mean_square) * rms_coefficient;
+
+        meter.peak.store(self.peak, Ordering::Relaxed);
+        meter.rms.store(self.mean_square.sqrt(), Ordering::Relaxed);
+        meter
+            .held_peak
+            .fetch_max(self.block_peak, Ordering::Relaxed);
+
+        self.block_peak = 0.0;
+        self.block_sum_squares = 0.0;
+        self.block_len = 0;
+    }
+}
+
+/// A decimated snapshot of the delay buffer, sent from the audio thread to the editor.
+#[derive(Clone)]
+pub struct Waveform {
+    /// The absolute peak value of every bucket of the delay buffer, across all channels.
+    peaks: [f32; WAVEFORM_POINTS],
+    /// The write and primary read positions relative to the buffer's length, in `[0, 1)`.
+    write_pos: f32,
+    read_pos: f32,
+}
+
+impl Default for Waveform {
+    fn default() -> Self {
+        Self {
+            peaks: [0.0; WAVEFORM_POINTS],
+            write_pos: 0.0,
+            read_pos: 0.0,
+        }
+    }
+}
+
+/// Builds the [`Waveform`] incrementally as samples are written to the delay buffer, so the
+/// buffer never needs to be scanned as a whole.
+pub struct WaveformCapture {
+    waveform: Waveform,
+    /// The bucket the last sample was written to. Entering a new bucket clears its old peak.
+    bucket: usize,
+    input: triple_buffer::Input<Waveform>,
+}
+
+impl WaveformCapture {
+    pub fn new(input: triple_buffer::Input<Waveform>) -> Self {
+        Self {
+            waveform: Waveform::default(),
+            bucket: 0,
+            input,
+        }
+    }
+
+    /// Record a sample written to `pos` in a delay buffer of length `len`.
+    pub fn record(&mut self, pos: usize, len: usize, sample: f32) {
+        let bucket = pos * WAVEFORM_POINTS / len;
+        if bucket != self.bucket {
+            self.bucket = bucket;
+            self.waveform.peaks[bucket] = 0.0;
+        }
+
+        self.waveform.peaks[bucket] = self.waveform.peaks[bucket].max(sample.abs());
+    }
+
+    /// Send the current waveform to the editor.
+    pub fn publish(&mut self, write_pos: f32, read_pos: f32) {
+        self.waveform.write_pos = write_pos;
+        self.waveform.read_pos = read_pos;
+        self.input.write(self.waveform.clone());
+    }
+}
+
+/// The dry and the wet output decimated over one sweep of the scope, sent from the audio thread
+/// to the editor.
+#[derive(Clone)]
+pub struct Scope {
+    /// The absolute peak value of every bucket of the sweep, across all channels.
+    dry: [f32; SCOPE_POINTS],
+    wet: [f32; SCOPE_POINTS],
+    /// The sweep's current position in `[0, 1)`.
+    pos: f32,
+    /// The length of a sweep in beats if it follows the host's tempo.
+    bar_beats: Option<f32>,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self {
+            dry: [0.0; SCOPE_POINTS],
+            wet: [0.0; SCOPE_POINTS],
+            pos: 0.0,
+            bar_beats: None,
+        }
+    }
+}
+
+/// Builds the [`Scope`] from the output. While the host is playing a sweep lasts one bar and
+/// starts on the downbeat, so the repeats of a synced delay line up with the beats. Otherwise the
+/// sweep runs freely over [`SCOPE_FREE_SECONDS`].
+pub struct ScopeCapture {
+    scope: Scope,
+    /// The bucket the last sample was written to. Entering a new bucket clears its old peaks.
+    bucket: usize,
+    input: triple_buffer::Input<Scope>,
+}
+
+impl ScopeCapture {
+    pub fn new(input: triple_buffer::Input<Scope>) -> Self {
+        Self {
+            scope: Scope::default(),
+            bucket: 0,
+            input,
+        }
+    }
+
+    /// Record a sample of the dry and the wet output. `timeline` is the host's position and the
+    /// length of a bar, both in beats, if it's playing.
+    pub fn record(&mut self, dry: f32, wet: f32, timeline: Option<(f64, f64)>, sample_rate: f32) {
+        let scope = &mut self.scope;
+        match timeline {
+            Some((pos_beats, bar_beats)) => {
+                scope.pos = (pos_beats / bar_beats).rem_euclid(1.0) as f32;
+                scope.bar_beats = Some(bar_beats as f32);
+            }
+            None => {
+                scope.pos = (scope.pos + 1.0 / (SCOPE_FREE_SECONDS * sample_rate)).fract();
+                scope.bar_beats = None;
+            }
+        }
+
+        let bucket = ((scope.pos * SCOPE_POINTS as f32) as usize).min(SCOPE_POINTS - 1);
+        if bucket != self.bucket {
+            self.bucket = bucket;
+            scope.dry[bucket] = 0.0;
+            scope.wet[bucket] = 0.0;
+        }
+
+        scope.dry[bucket] = scope.dry[bucket].max(dry.abs());
+        scope.wet[bucket] = scope.wet[bucket].max(wet.abs());
+    }
+
+    /// Send the current sweep to the editor.
+    pub fn publish(&mut self) {
+        self.input.write(self.scope.clone());
+    }
+}
+
+/// The most recent stereo output samples and the correlation between the channels, sent from the
+/// audio thread to the editor.
+#[derive(Clone)]
+pub struct Goniometer {
+    /// A ring buffer of left and right output samples.
+    points: [(f32, f32); GONIOMETER_POINTS],
+    /// Ranges from -1 for channels that cancel out when summed to mono, through 0 for unrelated
+    /// channels, to 1 for mono.
+    correlation: f32,
+}
+
+impl Default for Goniometer {
+    fn default() -> Self {
+        Self {
+            points: [(0.0, 0.0); GONIOMETER_POINTS],
+            correlation: 0.0,
+        }
+    }
+}
+
+/// Builds the [`Goniometer`] from the output. The correlation is integrated once per block, like
+/// [`MeterBallistics`] does for the levels.
+pub struct GoniometerCapture {
+    goniometer: Goniometer,
+    pos: usize,
+    /// The sums of the products of the channels, `left * right`, `left * left` and
+    /// `right * right`, over the current block and integrated over the past blocks.
+    block_products: [f32; 3],
+    products: [f32; 3],
+    input: triple_buffer::Input<Goniometer>,
+}
+
+impl GoniometerCapture {
+    pub fn new(input: triple_buffer::Input<Goniometer>) -> Self {
+        Self {
+            goniometer: Goniometer::default(),
+            pos: 0,
+            block_products: [0.0; 3],
+            products: [0.0; 3],
+            input,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.goniometer = Goniometer::default();
+        self.block_products = [0.0; 3];
+        self.products = [0.0; 3];
+    }
+
+    pub fn add(&mut self, left: f32, right: f32) {
+        self.goniometer.points[self.pos] = (left, right);
+        self.pos = (self.pos + 1) % GONIOMETER_POINTS;
+        let [lr, ll, rr] = &mut self.block_products;
+        *lr += left * right;
+        *ll += left * left;
+        *rr += right * right;
+    }
+
+    /// Update the correlation with the samples added since the last call and send everything to
+    /// the editor.
+    pub fn finish_block(&mut self, num_samples: usize, sample_rate: f32) {
+        if num_samples == 0 {
+            return;
+        }
+
+        let coefficient = 1.0 - (-(num_samples as f32) / sample_rate / CORRELATION_SECONDS).exp();
+        for (products, block_products) in self.products.iter_mut().zip(&mut self.block_products) {
+            *products += (*block_products / num_samples as f32 - *products) * coefficient;
+            *block_products = 0.0;
+        }
+
+        // Silence counts as unrelated
+        let [lr, ll, rr] = self.products;
+        let energy = (ll * rr).sqrt();
+        self.goniometer.correlation = if energy > 1e-12 {
+            (lr / energy).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+
+        self.input.write(self.goniometer.clone());
+    }
+}
+
+/// A block of the wet signal for the spectrum analyzer.
+#[derive(Clone)]
+pub struct SpectrumBlock {
+    samples: [f32; SPECTRUM_FFT_SIZE],
+    sample_rate: f32,
+}
+
+impl Default for SpectrumBlock {
+    fn default() -> Self {
+        Self {
+            samples: [0.0; SPECTRUM_FFT_SIZE],
+            sample_rate: 44100.0,
+        }
+    }
+}
+
+/// Collects the wet signal into blocks on the audio thread. Full blocks are handed over to the
+/// analyzer through a triple buffer, so the audio thread never waits for it.
+pub struct SpectrumCapture {
+    pos: usize,
+    /// Set when a block has been published and the analyzer hasn't been asked to run yet.
+    ready: bool,
+    input: triple_buffer::Input<SpectrumBlock>,
+}
+
+impl SpectrumCapture {
+    pub fn new(input: triple_buffer::Input<SpectrumBlock>) -> Self {
+        Self {
+            pos: 0,
+            ready: false,
+            input,
+        }
+    }
+
+    pub fn add(&mut self, sample: f32, sample_rate: f32) {
+        let block = self.input.input_buffer();
+        block.samples[self.pos] = sample;
+        self.pos += 1;
+        if self.pos == SPECTRUM_FFT_SIZE {
+            block.sample_rate = sample_rate;
+            self.input.publish();
+            self.pos = 0;
+            self.ready = true;
+        }
+    }
+
+    /// Whether a new block is ready for the analyzer. This is only true once per block.
+    pub fn take_ready(&mut self) -> bool {
+        std::mem::take(&mut self.ready)
+    }
+}
+
+/// The level of the wet signal in decibels at logarithmically spaced frequencies.
+pub struct Spectrum {
+    db: [f32; SPECTRUM_POINTS],
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Self {
+            db: [SPECTRUM_MIN_DB; SPECTRUM_POINTS],
+        }
+    }
+}
+
+/// The frequency of one of the spectrum's points.
+fn spectrum_point_hz(point: f32) -> f32 {
+    SPECTRUM_MIN_HZ * (SPECTRUM_MAX_HZ / SPECTRUM_MIN_HZ).powf(point / SPECTRUM_POINTS as f32)
+}
+
+/// Runs the FFT on the blocks from [`SpectrumCapture`]. This runs on a background thread, and the
+/// result is shared with the editor.
+pub struct SpectrumAnalyzer {
+    output: triple_buffer::Output<SpectrumBlock>,
+    window: Vec<f32>,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    spectrum: Arc<Mutex<Spectrum>>,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(
+        output: triple_buffer::Output<SpectrumBlock>,
+        spectrum: Arc<Mutex<Spectrum>>,
+    ) -> Self {
+        Self {
+            output,
+            window: hann_window(SPECTRUM_FFT_SIZE),
+            re: vec![0.0; SPECTRUM_FFT_SIZE],
+            im: vec![0.0; SPECTRUM_FFT_SIZE],
+            spectrum,
+        }
+    }
+
+    /// Analyze the latest block. Every point shows the loudest bin between it and the next
+    /// point, and falls back slowly so short peaks stay visible.
+    pub fn analyze(&mut self) {
+        let block = self.output.read();
+        for ((re, im), (sample, window)) in self
+            .re
+            .iter_mut()
+            .zip(&mut self.im)
+            .zip(block.samples.iter().zip(&self.window))
+        {
+            *re = sample * window;
+            *im = 0.0;
+        }
+        fft(&mut self.re, &mut self.im);
+
+        // A full scale sine reads as 0 dB after compensating for the window
+        let scale = 2.0 / self.window.iter().sum::<f32>();
+        let bin_hz = block.sample_rate / SPECTRUM_FFT_SIZE as f32;
+        let max_bin = SPECTRUM_FFT_SIZE / 2;
+        let fall = SPECTRUM_FALL_DB_PER_SECOND / bin_hz;
+
+        let mut spectrum = self.spectrum.lock().unwrap();
+        for (point, db) in spectrum.db.iter_mut().enumerate() {
+            let first = (spectrum_point_hz(point as f32) / bin_hz).round() as usize;
+            let last = ((spectrum_point_hz(point as f32 + 1.0) / bin_hz).round() as usize)
+                .max(first + 1)
+                .min(max_bin);
+            let magnitude = (first.min(max_bin - 1)..last)
+                .map(|bin| self.re[bin].hypot(self.im[bin]) * scale)
+                .fold(0.0, f32::max);
+
+            let level = util::gain_to_db(magnitude).max(SPECTRUM_MIN_DB);
+            *db = level.max(*db - fall);
+        }
+    }
+}
+
+/// Draw the spectrum of the wet signal, with the feedback path's low and high cut frequencies
+/// marked so it's clear what they're doing to the repeats.
+fn draw_spectrum(ui: &mut egui::Ui, params: &MyplugParams, spectrum: &Spectrum) {
+    let (rect, _) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(24, 24, 24));
+
+    let hz_to_x = |hz: f32| {
+        let position = (hz / SPECTRUM_MIN_HZ).ln() / (SPECTRUM_MAX_HZ / SPECTRUM_MIN_HZ).ln();
+        rect.left() + position.clamp(0.0, 1.0) * rect.width()
+    };
+    for hz in [
+        params.feedback_path.low_cut.value(),
+        params.feedback_path.high_cut.value(),
+    ] {
+        let x = hz_to_x(hz);
+        painter.line_segment(
+            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+            egui::Stroke::new(1.0, egui::Color32::DARK_GRAY),
+        );
+    }
+
+    let points = spectrum
+        .db
+        .iter()
+        .enumerate()
+        .map(|(point, db)| {
+            let level = (db - SPECTRUM_MIN_DB) / (SPECTRUM_MAX_DB - SPECTRUM_MIN_DB);
+            egui::pos2(
+                hz_to_x(spectrum_point_hz(point as f32 + 0.5)),
+                rect.bottom() - level.clamp(0.0, 1.0) * rect.height(),
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.5, egui::Color32::LIGHT_BLUE),
+    ));
+}
+
+/// Draw the decimated delay buffer together with the write and read positions.
+fn draw_waveform(ui: &mut egui::Ui, waveform: &Waveform) {
+    let (rect, _) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(24, 24, 24));
+
+    let center = rect.center().y;
+    let half_height = rect.height() / 2.0;
+    let bucket_width = rect.width() / WAVEFORM_POINTS as f32;
+    let waveform_stroke = egui::Stroke::new(bucket_width.max(1.0), egui::Color32::LIGHT_GRAY);
+    for (bucket, peak) in waveform.peaks.iter().enumerate() {
+        let x = rect.left() + (bucket as f32 + 0.5) * bucket_width;
+        let height = peak.min(1.0) * half_height;
+        painter.line_segment(
+            [
+                egui::pos2(x, center - height),
+                egui::pos2(x, center + height),
+            ],
+            waveform_stroke,
+        );
+    }
+
+    for (pos, color) in [
+        (waveform.write_pos, egui::Color32::RED),
+        (waveform.read_pos, egui::Color32::LIGHT_BLUE),
+    ] {
+        let x = rect.left() + pos * rect.width();
+        painter.line_segment(
+            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+            egui::Stroke::new(1.0, color),
+        );
+    }
+}
+
+/// Draw the scope with the dry output as bars and the wet output's envelope on top of it. Synced
+/// sweeps get a line on every beat.
+fn draw_scope(ui: &mut egui::Ui, scope: &Scope) {
+    let (rect, _) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(24, 24, 24));
+
+    if let Some(bar_beats) = scope.bar_beats {
+        for beat in 1..bar_beats.ceil() as usize {
+            let x = rect.left() + beat as f32 / bar_beats * rect.width();
+            painter.line_segment(
+                [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                egui::Stroke::new(1.0, egui::Color32::from_rgb(48, 48, 48)),
+            );
+        }
+    }
+
+    let center = rect.center().y;
+    let half_height = rect.height() / 2.0;
+    let bucket_width = rect.width() / SCOPE_POINTS as f32;
+    let dry_stroke = egui::Stroke::new(bucket_width.max(1.0), egui::Color32::DARK_GRAY);
+    for (bucket, peak) in scope.dry.iter().enumerate() {
+        let x = rect.left() + (bucket as f32 + 0.5) * bucket_width;
+        let height = peak.min(1.0) * half_height;
+        painter.line_segment(
+            [
+                egui::pos2(x, center - height),
+                egui::pos2(x, center + height),
+            ],
+            dry_stroke,
+        );
+    }
+
+    for direction in [-1.0, 1.0] {
+        let points = scope
+            .wet
+            .iter()
+            .enumerate()
+            .map(|(bucket, peak)| {
+                egui::pos2(
+                    rect.left() + (bucket as f32 + 0.5) * bucket_width,
+                    center + direction * peak.min(1.0) * half_height,
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, egui::Color32::LIGHT_BLUE),
+        ));
+    }
+
+    let x = rect.left() + scope.pos * rect.width();
+    painter.line_segment(
+        [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+        egui::Stroke::new(1.0, egui::Color32::WHITE),
+    );
+}
+
+/// Draw the output as a goniometer, where mono is a vertical line and signals that cancel out in
+/// mono are horizontal, next to the correlation between the channels.
+fn draw_goniometer(ui: &mut egui::Ui, goniometer: &Goniometer) {
+    ui.horizontal(|ui| {
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(GONIOMETER_SIZE, GONIOMETER_SIZE),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(24, 24, 24));
+        let center = rect.center();
+        let radius = rect.width() / 2.0;
+        for [from, to] in [[(0.0, -1.0), (0.0, 1.0)], [(-1.0, 0.0), (1.0, 0.0)]] {
+            painter.line_segment(
+                [
+                    center + egui::vec2(from.0, from.1) * radius,
+                    center + egui::vec2(to.0, to.1) * radius,
+                ],
+                egui::Stroke::new(1.0, egui::Color32::from_rgb(48, 48, 48)),
+            );
+        }
+
+        // Rotated by 45 degrees so the mid signal is vertical and the side signal horizontal
+        for &(left, right) in &goniometer.points {
+            let side = ((right - left) * std::f32::consts::FRAC_1_SQRT_2).clamp(-1.0, 1.0);
+            let mid = ((left + right) * std::f32::consts::FRAC_1_SQRT_2).clamp(-1.0, 1.0);
+            painter.circle_filled(
+                center + egui::vec2(side, -mid) * radius,
+                1.0,
+                egui::Color32::LIGHT_BLUE,
+            );
+        }
+
+        ui.vertical(|ui| {
+            ui.label(format!("Correlation {:+.2}", goniometer.correlation));
+            let (rect, _) = ui
+                .allocate_exact_size(egui::vec2(ui.available_width(), 14.0), egui::Sense::hover());
+            let painter = ui.painter();
+            painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(24, 24, 24));
+            let x = rect.center().x + goniometer.correlation * rect.width() / 2.0;
+            let color = if goniometer.correlation < 0.0 {
+                egui::Color32::RED
+            } else {
+                egui::Color32::GREEN
+            };
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(x.min(rect.center().x), rect.top()),
+                    egui::pos2(x.max(rect.center().x), rect.bottom()),
+                ),
+                2.0,
+                color,
+            );
+        });
+    });
+}
+
+/// The XY pad's local state.
+#[derive(Default)]
+struct XyPad {
+    /// The delay time and feedback typed into the text entry, while it's open.
+    entry: Option<[String; 2]>,
+    /// Whether the pad is being dragged, so the gesture is ended exactly once.
+    dragging: bool,
+}
+
+/// A pad that sets the delay time horizontally and the feedback vertically, so both can be played
+/// at once. Dragging the pad is a single gesture for both parameters, so hosts record it as one
+/// automation pass. Like the sliders, double-clicking resets both parameters, Shift-dragging makes
+/// fine adjustments, and Ctrl-clicking the pad or clicking the values below it opens a text entry
+/// for both of them.
+fn draw_xy_pad(
+    ui: &mut egui::Ui,
+    params: &MyplugParams,
+    setter: &ParamSetter,
+    pad: &mut XyPad,
+    history: &mut History,
+) {
+    let time = &params.delay_a.time;
+    let feedback = &params.feedback_path.feedback;
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(XY_PAD_SIZE, XY_PAD_SIZE),
+        egui::Sense::click_and_drag(),
+    );
+    let modifiers = ui.input(|input| input.modifiers);
+    if response.is_pointer_button_down_on() && !modifiers.command {
+        history.touch(time.as_ptr());
+        history.touch(feedback.as_ptr());
+    }
+
+    let set_from_pointer = || {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let x = (pos.x - rect.left()) / rect.width();
+            let y = (rect.bottom() - pos.y) / rect.height();
+            setter.set_parameter_normalized(time, x.clamp(0.0, 1.0));
+            setter.set_parameter_normalized(feedback, y.clamp(0.0, 1.0));
+        }
+    };
+
+    // Resetting is checked on its own, since depending on the egui version a click can also start
+    // and end a drag
+    let reset = response.double_clicked();
+    let open_entry = response.clicked() && modifiers.command && !reset;
+    let jump = response.clicked() && !modifiers.command && !reset;
+    if reset || jump {
+        if !pad.dragging {
+            setter.begin_set_parameter(time);
+            setter.begin_set_parameter(feedback);
+        }
+        if reset {
+            setter.set_parameter(time, time.default_plain_value());
+            setter.set_parameter(feedback, feedback.default_plain_value());
+        } else {
+            set_from_pointer();
+        }
+        if !pad.dragging {
+            setter.end_set_parameter(time);
+            setter.end_set_parameter(feedback);
+        }
+    }
+
+    if response.drag_started() && !pad.dragging && !modifiers.command {
+        setter.begin_set_parameter(time);
+        setter.begin_set_parameter(feedback);
+        pad.dragging = true;
+    }
+    // Only moving the pointer moves the parameters, so pressing a button alone leaves them be
+    let delta = response.drag_delta();
+    if pad.dragging && response.dragged() && delta != egui::Vec2::ZERO && !modifiers.command {
+        if modifiers.shift {
+            // Fine adjustments move the handle from where it is instead of jumping to the pointer
+            let delta = delta * XY_PAD_FINE_SCALE;
+            let x = time.unmodulated_normalized_value() + delta.x / rect.width();
+            let y = feedback.unmodulated_normalized_value() - delta.y / rect.height();
+            setter.set_parameter_normalized(time, x.clamp(0.0, 1.0));
+            setter.set_parameter_normalized(feedback, y.clamp(0.0, 1.0));
+        } else {
+            set_from_pointer();
+        }
+    }
+    if pad.dragging && !response.dragged() {
+        setter.end_set_parameter(time);
+        setter.end_set_parameter(feedback);
+        pad.dragging = false;
+    }
+
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(24, 24, 24));
+    let handle = egui::pos2(
+        rect.left() + time.unmodulated_normalized_value() * rect.width(),
+        rect.bottom() - feedback.unmodulated_normalized_value() * rect.height(),
+    );
+    let stroke = egui::Stroke::new(1.0, egui::Color32::DARK_GRAY);
+    painter.line_segment(
+        [
+            egui::pos2(handle.x, rect.top()),
+            egui::pos2(handle.x, rect.bottom()),
+        ],
+        stroke,
+    );
+    painter.line_segment(
+        [
+            egui::pos2(rect.left(), handle.y),
+            egui::pos2(rect.right(), handle.y),
+        ],
+        stroke,
+    );
+    painter.circle_filled(handle, 5.0, egui::Color32::LIGHT_BLUE);
+
+    let values = ui
+        .add(
+            egui::Label::new(format!("Time {time}, Feedback {feedback}"))
+                .sense(egui::Sense::click()),
+        )
+        .on_hover_text("Click to type in the values");
+    if open_entry || values.clicked() {
+        pad.entry = Some([time, feedback].map(|param| {
+            param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+        }));
+    }
+
+    // Values that can't be parsed leave their parameter alone
+    let mut close_entry = false;
+    if let Some(texts) = pad.entry.as_mut() {
+        ui.horizontal(|ui| {
+            for (label, text) in ["Time", "Feedback"].into_iter().zip(texts.iter_mut()) {
+                ui.label(label);
+                ui.add(egui::TextEdit::singleline(text).desired_width(70.0));
+            }
+            if ui.button("Set").clicked() {
+                for (param, text) in [time, feedback].into_iter().zip(texts.iter()) {
+                    if let Some(normalized) = param.string_to_normalized_value(text) {
+                        setter.begin_set_parameter(param);
+                        setter.set_parameter_normalized(param, normalized);
+                        setter.end_set_parameter(param);
+                        history.touch(param.as_ptr());
+                    }
+                }
+                close_entry = true;
+            }
+            if ui.button("Cancel").clicked() {
+                close_entry = true;
+            }
+        });
+    }
+    if close_entry {
+        pad.entry = None;
+    }
+}
+
+/// Draw a horizontal level meter with the RMS level as a filled bar and the peak level as a
+/// line, followed by the held peak. The held peak turns red once the signal has clipped and is
+/// cleared by clicking it.
+fn draw_meter(ui: &mut egui::Ui, label: &str, meter: &LevelMeter) {
+    const MIN_DB: f32 = -60.0;
+    const MAX_DB: f32 = 6.0;
+    let db_to_x = |rect: egui::Rect, gain: f32| {
+        let db = util::gain_to_db(gain).clamp(MIN_DB, MAX_DB);
+        rect.left() + (db - MIN_DB) / (MAX_DB - MIN_DB) * rect.width()
+    };
+
+    ui.horizontal(|ui| {
+        ui.add_sized([30.0, 14.0], egui::Label::new(label));
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2((ui.available_width() - METER_HOLD_WIDTH).max(0.0), 14.0),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(24, 24, 24));
+
+        let peak = meter.peak.load(Ordering::Relaxed);
+        let rms = meter.rms.load(Ordering::Relaxed);
+        let color = if peak >= 1.0 {
+            egui::Color32::RED
+        } else {
+            egui::Color32::GREEN
+        };
+        painter.rect_filled(
+            egui::Rect::from_min_max(rect.min, egui::pos2(db_to_x(rect, rms), rect.bottom())),
+            2.0,
+            color,
+        );
+        let peak_x = db_to_x(rect, peak);
+        painter.line_segment(
+            [
+                egui::pos2(peak_x, rect.top()),
+                egui::pos2(peak_x, rect.bottom()),
+            ],
+            egui::Stroke::new(2.0, egui::Color32::WHITE),
+        );
+
+        let held_peak = meter.held_peak.load(Ordering::Relaxed);
+        let readout = if held_peak > 0.0 {
+            format!("{:.1} dBFS", util::gain_to_db(held_peak))
+        } else {
+            String::from("-inf dBFS")
+        };
+        let mut hold = egui::Button::new(readout);
+        if held_peak >= 1.0 {
+            hold = hold.fill(egui::Color32::from_rgb(160, 0, 0));
+        }
+        if ui
+            .add_sized([METER_HOLD_WIDTH, 14.0], hold)
+            .on_hover_text("Click to clear the held peak")
+            .clicked()
+        {
+            meter.held_peak.store(0.0, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Show the output's short-term and integrated loudness, with a button to restart the
+/// integrated measurement.
+fn draw_loudness(ui: &mut egui::Ui, meters: &Meters) {
+    let format_lufs = |lufs: f32| {
+        if lufs.is_finite() {
+            format!("{lufs:.1} LUFS")
+        } else {
+            String::from("-inf LUFS")
+        }
+    };
+
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Short-term {}",
+            format_lufs(meters.short_term_lufs.load(Ordering::Relaxed))
+        ));
+        ui.separator();
+        ui.label(format!(
+            "Integrated {}",
+            format_lufs(meters.integrated_lufs.load(Ordering::Relaxed))
+        ));
+        if ui.small_button("Reset").clicked() {
+            meters.reset_integrated.store(true, Ordering::Relaxed);
+        }
+    });
+}
+
+/// The editor's local state for the preset browser.
+#[derive(Default)]
+struct PresetBrowser {
+    /// The JSON text shown in the import/export box.
+    json: String,
+    /// The error from the last failed import, if any.
+    error: Option<String>,
+}
+
+fn draw_preset_browser(
+    ui: &mut egui::Ui,
+    params: &MyplugParams,
+    setter: &ParamSetter,
+    factory_presets: &[Preset],
+    browser: &mut PresetBrowser,
+    history: &mut History,
+) {
+    let mut preset_name = params.preset_name.read().unwrap().clone();
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Preset")
+            .selected_text(preset_name.as_str())
+            .show_ui(ui, |ui| {
+                for preset in factory_presets {
+                    if ui
+                        .selectable_label(preset_name == preset.name, preset.name.as_str())
+                        .clicked()
+                    {
+                        preset.apply(params, setter);
+                        history.touch_all();
+                        preset_name = preset.name.clone();
+                    }
+                }
+            });
+
+        if ui.button("Export").clicked() {
+            let name = if preset_name.is_empty() {
+                "User"
+            } else {
+                preset_name.as_str()
+            };
+            browser.json = Preset::capture(name, params).to_json();
+            browser.error = None;
+        }
+
+        if ui.button("Import").clicked() {
+            match Preset::from_json(&browser.json) {
+                Ok(preset) => {
+                    preset.apply(params, setter);
+                    history.touch_all();
+                    preset_name = preset.name;
+                    browser.error = None;
+                }
+                Err(err) => browser.error = Some(err.to_string()),
+            }
+        }
+    });
+
+    if *params.preset_name.read().unwrap() != preset_name {
+        *params.preset_name.write().unwrap() = preset_name;
+    }
+
+    ui.add(egui::TextEdit::multiline(&mut browser.json).desired_width(f32::INFINITY));
+    if let Some(error) = &browser.error {
+        ui.colored_label(egui::Color32::RED, format!("Invalid preset: {error}"));
+    }
+}
+
+fn draw_ab_compare(
+    ui: &mut egui::Ui,
+    params: &MyplugParams,
+    setter: &ParamSetter,
+    history: &mut History,
+) {
+    let mut compare = params.ab_compare.read().unwrap().clone();
+    ui.horizontal(|ui| {
+        ui.label("Compare");
+        if ui.selectable_label(!compare.b_active, "A").clicked() && compare.b_active {
+            compare.switch(params, setter);
+            history.touch_all();
+        }
+        if ui.selectable_label(compare.b_active, "B").clicked() && !compare.b_active {
+            compare.switch(params, setter);
+            history.touch_all();
+        }
+
+        let copy = if compare.b_active {
+            "Copy B > A"
+        } else {
+            "Copy A > B"
+        };
+        if ui.button(copy).clicked() {
+            compare.copy_to_inactive(params);
+        }
+    });
+
+    if *params.ab_compare.read().unwrap() != compare {
+        *params.ab_compare.write().unwrap() = compare;
+    }
+}
+
+/// The randomizer's buttons, its seed and the parameter locks.
+fn draw_randomizer(
+    ui: &mut egui::Ui,
+    params: &MyplugParams,
+    setter: &ParamSetter,
+    param_names: &[(String, String)],
+    history: &mut History,
+) {
+    let mut randomizer = params.randomizer.read().unwrap().clone();
+    ui.collapsing("Randomize", |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Seed");
+            ui.add(egui::DragValue::new(&mut randomizer.seed).clamp_range(1..=u32::MAX));
+            if ui.button("Randomize").clicked() {
+                randomizer.randomize(params, setter);
+                history.touch_all();
+            }
+            if ui.button("Mutate").clicked() {
+                randomizer.mutate(params, setter);
+                history.touch_all();
+            }
+            ui.add(
+                egui::Slider::new(&mut randomizer.mutate_amount, 0.01..=0.5)
+                    .text("Amount")
+                    .custom_formatter(|amount, _| format!("{:.0}%", amount * 100.0)),
+            );
+        });
+
+        let locked = match randomizer.locked.len() {
+            0 => String::from("None"),
+            1 => String::from("1 parameter"),
+            len => format!("{len} parameters"),
+        };
+        egui::ComboBox::from_label("Locked")
+            .selected_text(locked)
+            .show_ui(ui, |ui| {
+                for (id, name) in param_names {
+                    let mut is_locked = randomizer.locked.contains(id);
+                    if ui.checkbox(&mut is_locked, name.as_str()).changed() {
+                        if is_locked {
+                            randomizer.locked.insert(id.clone());
+                        } else {
+                            randomizer.locked.remove(id);
+                        }
+                    }
+                }
+            });
+    });
+
+    if *params.randomizer.read().unwrap() != randomizer {
+        *params.randomizer.write().unwrap() = randomizer;
+    }
+}
+
+/// Set a parameter through the host as a single gesture.
+pub fn set_normalized(setter: &ParamSetter, param: ParamPtr, normalized: f32) {
+    unsafe {
+        setter.raw_context.raw_begin_set_parameter(param);
+        setter
+            .raw_context
+            .raw_set_parameter_normalized(param, normalized.clamp(0.0, 1.0));
+        setter.raw_context.raw_end_set_parameter(param);
+    }
+}
+
+/// The text typed into a slider's text entry, kept in egui's memory under the slider's ID while
+/// the entry is open.
+#[derive(Clone)]
+struct SliderEntry {
+    text: String,
+    /// Whether the text field has been focused, so it's only focused when it opens.
+    focused: bool,
+}
+
+/// A parameter slider whose edits go into the undo history. Clicking, dragging or typing into
+/// the slider counts as touching its parameter. NIH-plug's sliders reset on Ctrl-click, but here
+/// Ctrl-clicking (Cmd-clicking on macOS) opens a text entry for the value instead, which applies
+/// on Enter and closes without changes otherwise. Double-clicking still resets the slider.
+fn add_param_slider<P: Param>(
+    ui: &mut egui::Ui,
+    param: &P,
+    setter: &ParamSetter,
+    history: &mut History,
+) -> egui::Response {
+    let id = egui::Id::new(param.as_ptr());
+    if let Some(mut entry) = ui.data_mut(|data| data.get_temp::<SliderEntry>(id)) {
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut entry.text).desired_width(ui.spacing().slider_width),
+        );
+        if !entry.focused {
+            response.request_focus();
+            entry.focused = true;
+        }
+
+        if response.lost_focus() {
+            // Values that can't be parsed leave the parameter alone
+            if ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                if let Some(normalized) = param.string_to_normalized_value(&entry.text) {
+                    setter.begin_set_parameter(param);
+                    setter.set_parameter_normalized(param, normalized);
+                    setter.end_set_parameter(param);
+                    history.touch(param.as_ptr());
+                }
+            }
+            ui.data_mut(|data| data.remove::<SliderEntry>(id));
+        } else {
+            ui.data_mut(|data| data.insert_temp(id, entry));
+        }
+
+        return response;
+    }
+
+    // The slider can't be stopped from resetting on a Ctrl-click, so the slider under the pointer
+    // is disabled while Ctrl is held and the click is handled here instead. Its position is only
+    // known once it's drawn, so this uses the position from the last frame.
+    let rect_id = id.with("rect");
+    let (command, pointer) = ui.input(|input| (input.modifiers.command, input.pointer.hover_pos()));
+    let last_rect = ui.data_mut(|data| data.get_temp::<egui::Rect>(rect_id));
+    let intercept = command
+        && matches!((last_rect, pointer), (Some(rect), Some(pointer)) if rect.contains(pointer));
+
+    let response = ui.add_enabled(!intercept, widgets::ParamSlider::for_param(param, setter));
+    ui.data_mut(|data| data.insert_temp(rect_id, response.rect));
+    if intercept {
+        if ui
+            .interact(response.rect, id.with("entry"), egui::Sense::click())
+            .clicked()
+        {
+            let text = param.normalized_value_to_string(param.unmodulated_normalized_value(), true);
+            ui.data_mut(|data| {
+                data.insert_temp(
+                    id,
+                    SliderEntry {
+                        text,
+                        focused: false,
+                    },
+                )
+            });
+        }
+    } else if response.is_pointer_button_down_on() || response.has_focus() || response.lost_focus()
+    {
+        history.touch(param.as_ptr());
+    }
+
+    response
+}
+
+/// Add a slider for a parameter with a context menu to reset it or to map a MIDI CC to it. The
+/// plugin formats let hosts add their own entries, like automation options, to a plugin's context
+/// menus, but NIH-plug doesn't expose that so this menu only has the plugin's own entries.
+fn draw_param_slider<P: Param>(
+    ui: &mut egui::Ui,
+    param: &P,
+    setter: &ParamSetter,
+    params: &MyplugParams,
+    learn: &mut MidiLearn,
+    history: &mut History,
+) {
+    add_param_slider(ui, param, setter, history).context_menu(|ui| {
+        if ui
+            .add_enabled(
+                param.unmodulated_plain_value() != param.default_plain_value(),
+                egui::Button::new("Reset to Default"),
+            )
+            .clicked()
+        {
+            setter.begin_set_parameter(param);
+            setter.set_parameter(param, param.default_plain_value());
+            setter.end_set_parameter(param);
+            history.touch(param.as_ptr());
+            ui.close_menu();
+        }
+        ui.separator();
+
+        let Some((id, _, _)) = params
+            .param_map()
+            .into_iter()
+            .find(|(_, ptr, _)| *ptr == param.as_ptr())
+        else {
+            return;
+        };
+        let mapped_cc = params
+            .cc_mappings
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, mapped_id)| **mapped_id == id)
+            .map(|(&cc, _)| cc);
+        if ui.button("MIDI Learn").clicked() {
+            learn.param_id = id;
+            learn.learning = true;
+            ui.close_menu();
+        }
+        if let Some(cc) = mapped_cc {
+            if ui.button(format!("Remove CC {cc} Mapping")).clicked() {
+                params.cc_mappings.write().unwrap().remove(&cc);
+                ui.close_menu();
+            }
+        }
+    });
+}
+
+/// The editor's local state for mapping MIDI CCs to parameters.
+#[derive(Default)]
+struct MidiLearn {
+    /// The ID of the parameter selected for learning.
+    param_id: String,
+    /// Whether the next incoming CC gets mapped to `param_id`.
+    learning: bool,
+    /// The mappings last passed on to the audio thread.
+    mapped: BTreeMap<u8, String>,
+}
+
+fn draw_midi_learn(
+    ui: &mut egui::Ui,
+    params: &MyplugParams,
+    setter: &ParamSetter,
+    midi_ccs: &MidiCcs,
+    param_names: &[(String, String)],
+    learn: &mut MidiLearn,
+) {
+    for cc in 0..128 {
+        let Some(value) = midi_ccs.take(cc) else {
+            continue;
+        };
+
+        if learn.learning {
+            params
+                .cc_mappings
+                .write()
+                .unwrap()
+                .insert(cc, learn.param_id.clone());
+            learn.learning = false;
+        }
+        if let Some(id) = params.cc_mappings.read().unwrap().get(&cc) {
+            if let Some((_, ptr, _)) = params.param_map().into_iter().find(|(i, _, _)| i == id) {
+                set_normalized(setter, ptr, value);
+            }
+        }
+    }
+
+    // Mappings are changed from this list, the sliders' menus and by learning
+    let mappings = params.cc_mappings.read().unwrap();
+    if *mappings != learn.mapped {
+        learn.mapped = mappings.clone();
+        midi_ccs.map(&mappings, params);
+    }
+    drop(mappings);
+
+    let name_of = |id: &str| {
+        param_names
+            .iter()
+            .find(|(param_id, _)| param_id == id)
+            .map_or(id.to_string(), |(_, name)| name.clone())
+    };
+    ui.collapsing("MIDI CC", |ui| {
+        let mappings = params.cc_mappings.read().unwrap().clone();
+        for (cc, id) in mappings {
+            ui.horizontal(|ui| {
+                ui.label(format!("CC {cc}: {}", name_of(&id)));
+                if ui.small_button("Remove").clicked() {
+                    params.cc_mappings.write().unwrap().remove(&cc);
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("midi-learn-param")
+                .selected_text(name_of(&learn.param_id))
+                .show_ui(ui, |ui| {
+                    for (id, name) in param_names {
+                        ui.selectable_value(&mut learn.param_id, id.clone(), name.as_str());
+                    }
+                });
+
+            if learn.learning {
+                ui.label("Move a controller...");
+                if ui.button("Cancel").clicked() {
+                    learn.learning = false;
+                }
+            } else if ui
+                .add_enabled(!learn.param_id.is_empty(), egui::Button::new("Learn"))
+                .clicked()
+            {
+                learn.learning = true;
+            }
+        });
+    });
+}
+
+/// The sequencer's parameters and its steps. The steps aren't parameters, so they're written to
+/// the persisted sequence directly.
+fn draw_sequencer(
+    ui: &mut egui::Ui,
+    params: &MyplugParams,
+    setter: &ParamSetter,
+    history: &mut History,
+) {
+    ui.collapsing("Sequencer", |ui| {
+        egui::Grid::new("sequencer-params")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Sequencer");
+                add_param_slider(ui, &params.performance.sequencer, setter, history);
+                ui.end_row();
+
+                ui.label("Rate");
+                add_param_slider(ui, &params.performance.sequencer_rate, setter, history);
+                ui.end_row();
+
+                ui.label("Length");
+                add_param_slider(ui, &params.performance.sequencer_length, setter, history);
+                ui.end_row();
+            });
+
+        let mut sequence = *params.sequence.read().unwrap();
+        let len = params.performance.sequencer_length.value() as usize;
+        egui::Grid::new("sequencer-steps").show(ui, |ui| {
+            for (i, step) in sequence.steps.iter_mut().enumerate() {
+                // Steps past the sequence's length are kept, but they're greyed out
+                ui.add_enabled_ui(i < len, |ui| {
+                    ui.vertical(|ui| {
+                        ui.checkbox(&mut step.enabled, format!("{}", i + 1));
+                        egui::ComboBox::from_id_source(("sequencer-step", i))
+                            .width(70.0)
+                            .selected_text(NoteDivision::variants()[step.note.to_index()])
+                            .show_ui(ui, |ui| {
+                                for (index, name) in NoteDivision::variants().iter().enumerate() {
+                                    ui.selectable_value(
+                                        &mut step.note,
+                                        NoteDivision::from_index(index),
+                                        *name,
+                                    );
+                                }
+                            });
+                    });
+                });
+                if i % 8 == 7 {
+                    ui.end_row();
+                }
+            }
+        });
+
+        if *params.sequence.read().unwrap() != sequence {
+            *params.sequence.write().unwrap() = sequence;
+        }
+    });
+}
+
+/// The tap timeline's local state.
+#[derive(Default)]
+struct TapTimeline {
+    /// The tap that's being dragged, so its gesture is ended exactly once.
+    dragged: Option<usize>,
+}
+
+/// The multi-tap mode's taps on a timeline spanning a bar, with the time from left to right and
+/// the level from bottom to top. Dragging a tap moves it, clicking an empty spot enables the next
+/// disabled tap there, and right-clicking a tap disables it. With quantize enabled, moved and
+/// added taps snap to the grid, which is spaced in note values of the host's bar. The current
+/// taps can be stored in and recalled from the pattern slots that are saved with the project.
+fn draw_tap_timeline(
+    ui: &mut egui::Ui,
+    params: &MyplugParams,
+    setter: &ParamSetter,
+    timeline: &mut TapTimeline,
+    bar_beats: f32,
+    history: &mut History,
+) {
+    let taps = &params.delay_a.taps;
+    let mut patterns = params.tap_patterns.read().unwrap().clone();
+    ui.collapsing("Multi-Tap", |ui| {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut patterns.quantize, "Quantize");
+            egui::ComboBox::from_id_source("tap-grid")
+                .width(70.0)
+                .selected_text(NoteDivision::variants()[patterns.grid.to_index()])
+                .show_ui(ui, |ui| {
+                    for (index, name) in NoteDivision::variants().iter().enumerate() {
+                        ui.selectable_value(
+                            &mut patterns.grid,
+                            NoteDivision::from_index(index),
+                            *name,
+                        );
+                    }
+                });
+        });
+
+        let (rect, response) = ui.allocate_exact_size(
+            egui::Vec2::from(TAP_TIMELINE_SIZE),
+            egui::Sense::click_and_drag(),
+        );
+        let tap_x = |index: usize| rect.left() + taps[index].time.value() * rect.width();
+        let tap_at = |pos: egui::Pos2| {
+            (0..taps.len())
+                .filter(|&index| taps[index].enabled.value())
+                .map(|index| (index, (tap_x(index) - pos.x).abs()))
+                .filter(|(_, distance)| *distance <= TAP_GRAB_DISTANCE)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(index, _)| index)
+        };
+        let time_and_level = |pos: egui::Pos2| {
+            let time = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+            let time = if patterns.quantize {
+                quantize(time, patterns.grid, bar_beats)
+            } else {
+                time
+            };
+            let level = ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0);
+            (time, level)
+        };
+        let pointer = response.interact_pointer_pos();
+
+        if response.drag_started() && timeline.dragged.is_none() {
+            timeline.dragged = pointer.and_then(tap_at);
+            if let Some(index) = timeline.dragged {
+                let tap = &taps[index];
+                history.touch(tap.time.as_ptr());
+                history.touch(tap.level.as_ptr());
+                setter.begin_set_parameter(&tap.time);
+                setter.begin_set_parameter(&tap.level);
+            }
+        }
+        if let (Some(index), Some(pos)) = (timeline.dragged, pointer) {
+            if response.dragged() && response.drag_delta() != egui::Vec2::ZERO {
+                let (time, level) = time_and_level(pos);
+                setter.set_parameter(&taps[index].time, time);
+                setter.set_parameter(&taps[index].level, level);
+            }
+        }
+        if let Some(index) = timeline.dragged.filter(|_| !response.dragged()) {
+            setter.end_set_parameter(&taps[index].time);
+            setter.end_set_parameter(&taps[index].level);
+            timeline.dragged = None;
+        }
+
+        if let Some(pos) = pointer.filter(|_| response.clicked()) {
+            let free = (0..taps.len()).find(|&index| !taps[index].enabled.value());
+            if let Some(tap) = free
+                .filter(|_| tap_at(pos).is_none())
+                .map(|index| &taps[index])
+            {
+                let (time, level) = time_and_level(pos);
+                set_normalized(setter, tap.time.as_ptr(), tap.time.preview_normalized(time));
+                set_normalized(
+                    setter,
+                    tap.level.as_ptr(),
+                    tap.level.preview_normalized(level),
+                );
+                set_normalized(setter, tap.enabled.as_ptr(), 1.0);
+                history.touch(tap.time.as_ptr());
+                history.touch(tap.level.as_ptr());
+                history.touch(tap.enabled.as_ptr());
+            }
+        }
+        if let Some(index) = pointer
+            .filter(|_| response.secondary_clicked())
+            .and_then(tap_at)
+        {
+            set_normalized(setter, taps[index].enabled.as_ptr(), 0.0);
+            history.touch(taps[index].enabled.as_ptr());
+        }
+
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(24, 24, 24));
+        let grid_step = patterns.grid.beats() / bar_beats;
+        let grid_stroke = egui::Stroke::new(1.0, egui::Color32::DARK_GRAY);
+        for line in 1..(1.0 / grid_step).ceil() as usize {
+            let x = rect.left() + line as f32 * grid_step * rect.width();
+            painter.line_segment(
+                [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                grid_stroke,
+            );
+        }
+        for index in (0..taps.len()).filter(|&index| taps[index].enabled.value()) {
+            let x = tap_x(index);
+            let y = rect.bottom() - taps[index].level.value() * rect.height();
+            let color = if timeline.dragged == Some(index) {
+                egui::Color32::YELLOW
+            } else {
+                egui::Color32::LIGHT_BLUE
+            };
+            painter.line_segment(
+                [egui::pos2(x, rect.bottom()), egui::pos2(x, y)],
+                egui::Stroke::new(2.0, color),
+            );
+            painter.circle_filled(egui::pos2(x, y), 4.0, color);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Patterns");
+            for slot in 0..NUM_TAP_SLOTS {
+                ui.label(format!("{}", slot + 1));
+                if ui.small_button("Store").clicked() {
+                    patterns.store(slot, params);
+                }
+                if ui
+                    .add_enabled(patterns.slots[slot].is_some(), egui::Button::new("Recall"))
+                    .clicked()
+                {
+                    patterns.recall(slot, params, setter);
+                    history.touch_all();
+                }
+            }
+        });
+    });
+
+    if *params.tap_patterns.read().unwrap() != patterns {
+        *params.tap_patterns.write().unwrap() = patterns;
+    }
+}
+
+/// The number of edits the undo history keeps.
+const MAX_UNDO_STEPS: usize = 100;
+
+/// The number of frames an edit waits after the controls have been let go for its changes to
+/// reach the parameters. Hosts apply the editor's changes asynchronously.
+const EDIT_SETTLE_FRAMES: usize = 10;
+
+/// An undo or redo step: normalized values by the parameter's index in `param_map()`.
+type Edit = Vec<(usize, f32)>;
+
+/// The editor's undo history, which is separate from the host's. The editor's controls mark the
+/// parameters they change, and once the controls are let go the changes to those parameters are
+/// recorded as one step, so dragging a slider is undone as a single step. Automation and MIDI CCs
+/// changing other parameters in the meantime aren't recorded.
+#[derive(Default)]
+struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// Every parameter and its normalized value as of the last frame. The parameters that are
+    /// being edited keep their values from before the edit.
+    params: Vec<(ParamPtr, f32)>,
+    /// Which parameters the controls have touched since the last step was recorded.
+    editing: Vec<bool>,
+    /// The number of frames since the controls were let go, while an edit is waiting.
+    settle_frames: usize,
+}
+
+impl History {
+    fn new(params: &MyplugParams) -> Self {
+        let params: Vec<_> = params
+            .param_map()
+            .into_iter()
+            .map(|(_, ptr, _)| (ptr, unsafe { ptr.unmodulated_normalized_value() }))
+            .collect();
+        Self {
+            editing: vec![false; params.len()],
+            params,
+            ..Self::default()
+        }
+    }
+
+    /// Mark a parameter as changed by one of the editor's controls.
+    fn touch(&mut self, param: ParamPtr) {
+        if let Some(index) = self.params.iter().position(|(ptr, _)| *ptr == param) {
+            self.editing[index] = true;
+            self.settle_frames = 0;
+        }
+    }
+
+    /// Mark every parameter as changed, for loading presets and other edits of the whole state.
+    fn touch_all(&mut self) {
+        self.editing.fill(true);
+        self.settle_frames = 0;
+    }
+
+    /// Keep track of the parameters after the editor has been drawn, and record the current edit
+    /// once it's done.
+    fn update(&mut self, ctx: &egui::Context) {
+        let held = ctx.input(|input| input.pointer.any_down()) || ctx.wants_keyboard_input();
+        let editing = self.editing.contains(&true);
+        if editing && held {
+            self.settle_frames = 0;
+        } else if editing {
+            self.settle_frames += 1;
+            let changed = self
+                .params
+                .iter()
+                .zip(&self.editing)
+                .any(|((ptr, value), &editing)| {
+                    editing && unsafe { ptr.unmodulated_normalized_value() } != *value
+                });
+            if changed || self.settle_frames >= EDIT_SETTLE_FRAMES {
+                self.finish_edit();
+            }
+        }
+
+        for ((ptr, value), &editing) in self.params.iter_mut().zip(&self.editing) {
+            if !editing {
+                *value = unsafe { ptr.unmodulated_normalized_value() };
+            }
+        }
+    }
+
+    /// Record the parameters touched by the current edit that have changed since it started.
+    fn finish_edit(&mut self) {
+        let mut edit = Edit::new();
+        for (index, ((ptr, value), editing)) in
+            self.params.iter_mut().zip(&mut self.editing).enumerate()
+        {
+            let current = unsafe { ptr.unmodulated_normalized_value() };
+            if *editing && current != *value {
+                edit.push((index, *value));
+            }
+            *value = current;
+            *editing = false;
+        }
+
+        if !edit.is_empty() {
+            if self.undo.len() == MAX_UNDO_STEPS {
+                self.undo.remove(0);
+            }
+            self.undo.push(edit);
+            self.redo.clear();
+        }
+    }
+
+    /// Set the parameters in `edit`, and return their current values to go back to.
+    fn restore(&self, edit: Edit, setter: &ParamSetter) -> Edit {
+        edit.into_iter()
+            .map(|(index, value)| {
+                let ptr = self.params[index].0;
+                let current = unsafe { ptr.unmodulated_normalized_value() };
+                set_normalized(setter, ptr, value);
+                (index, current)
+            })
+            .collect()
+    }
+
+    fn undo(&mut self, setter: &ParamSetter) {
+        self.finish_edit();
+        if let Some(previous) = self.undo.pop() {
+            let next = self.restore(previous, setter);
+            self.redo.push(next);
+        }
+    }
+
+    fn redo(&mut self, setter: &ParamSetter) {
+        self.finish_edit();
+        if let Some(next) = self.redo.pop() {
+            let previous = self.restore(next, setter);
+            self.undo.push(previous);
+        }
+    }
+
+    /// Undo with Ctrl+Z, and redo with Ctrl+Shift+Z or Ctrl+Y. These are left to text fields
+    /// while they have the keyboard focus.
+    fn handle_shortcuts(&mut self, setter: &ParamSetter, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        let (undo, redo) = ctx.input_mut(|input| {
+            let redo = input.consume_key(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::Z,
+            ) || input.consume_key(egui::Modifiers::COMMAND, egui::Key::Y);
+            let undo = input.consume_key(egui::Modifiers::COMMAND, egui::Key::Z);
+            (undo, redo)
+        });
+        if undo {
+            self.undo(setter);
+        } else if redo {
+            self.redo(setter);
+        }
+    }
+}
+
+/// The editor's local state.
+#[derive(Default)]
+struct EditorState {
+    preset_browser: PresetBrowser,
+    midi_learn: MidiLearn,
+    history: History,
+    xy_pad: XyPad,
+    tap_timeline: TapTimeline,
+    /// The display's scale factor when the editor was opened.
+    system_scale: f32,
+    /// The zoom that's been applied on top of `system_scale`, if any.
+    applied_scale: Option<f32>,
+}
+
+/// Apply the persisted zoom. The window is resized along with it, so the editor keeps showing
+/// the same part of its contents.
+fn apply_ui_scale(egui_ctx: &egui::Context, params: &MyplugParams, state: &mut EditorState) {
+    let scale = *params.ui_scale.read().unwrap();
+    if state.applied_scale == Some(scale) {
+        return;
+    }
+
+    egui_ctx.set_pixels_per_point(state.system_scale * scale);
+    if let Some(previous) = state.applied_scale {
+        let (width, height) = params.editor_state.size();
+        let ratio = scale / previous;
+        params.editor_state.set_requested_size((
+            (width as f32 * ratio).round() as u32,
+            (height as f32 * ratio).round() as u32,
+        ));
+    }
+    state.applied_scale = Some(scale);
+}
+
+fn draw_ui_scale(ui: &mut egui::Ui, params: &MyplugParams) {
+    let mut scale = *params.ui_scale.read().unwrap();
+    egui::ComboBox::from_id_source("ui-scale")
+        .selected_text(format!("{:.0}%", scale * 100.0))
+        .show_ui(ui, |ui| {
+            for option in UI_SCALES {
+                ui.selectable_value(&mut scale, option, format!("{:.0}%", option * 100.0));
+            }
+        });
+
+    if *params.ui_scale.read().unwrap() != scale {
+        *params.ui_scale.write().unwrap() = scale;
+    }
+}
+
+/// Create the editor. Parameters changed through MIDI CC mappings and SysEx snapshots are applied
+/// here, since they can only be set from the GUI thread.
+pub fn create(
+    params: Arc<MyplugParams>,
+    displays: Displays,
+    meters: Arc<Meters>,
+    midi_ccs: Arc<MidiCcs>,
+    sysex_snapshot: Arc<Mutex<Option<RemoteSysEx>>>,
+) -> Option<Box<dyn Editor>> {
+    let factory_presets = factory_presets(&params);
+    let param_names: Vec<(String, String)> = params
+        .param_map()
+        .into_iter()
+        .map(|(id, ptr, _)| (id, unsafe { ptr.name() }.to_string()))
+        .collect();
+    create_egui_editor(
+        params.editor_state.clone(),
+        EditorState {
+            history: History::new(&params),
+            ..EditorState::default()
+        },
+        |egui_ctx, state| {
+            state.system_scale = egui_ctx.pixels_per_point();
+            state.applied_scale = None;
+        },
+        move |egui_ctx, setter, state| {
+            // The audio thread has already checked that the snapshot matches the parameters
+            if let Some(RemoteSysEx::Snapshot { values, len, .. }) =
+                sysex_snapshot.lock().unwrap().take()
+            {
+                for ((_, ptr, _), value) in params.param_map().into_iter().zip(&values[..len]) {
+                    set_normalized(setter, ptr, *value as f32 / SYSEX_MAX_VALUE);
+                }
+            }
+
+            state.history.handle_shortcuts(setter, egui_ctx);
+            apply_ui_scale(egui_ctx, &params, state);
+
+            let window = ResizableWindow::new("myplug").min_size(MIN_WINDOW_SIZE);
+            window.show(egui_ctx, &params.editor_state, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.heading(<Myplug>::NAME);
+                        draw_ui_scale(ui, &params);
+                        // Not every host passes keyboard input on to plugin windows
+                        let history = &mut state.history;
+                        if ui
+                            .add_enabled(!history.undo.is_empty(), egui::Button::new("Undo"))
+                            .clicked()
+                        {
+                            history.undo(setter);
+                        }
+                        if ui
+                            .add_enabled(!history.redo.is_empty(), egui::Button::new("Redo"))
+                            .clicked()
+                        {
+                            history.redo(setter);
+                        }
+                    });
+                    draw_preset_browser(
+                        ui,
+                        &params,
+                        setter,
+                        &factory_presets,
+                        &mut state.preset_browser,
+                        &mut state.history,
+                    );
+                    draw_ab_compare(ui, &params, setter, &mut state.history);
+                    draw_randomizer(ui, &params, setter, &param_names, &mut state.history);
+                    draw_midi_learn(
+                        ui,
+                        &params,
+                        setter,
+                        &midi_ccs,
+                        &param_names,
+                        &mut state.midi_learn,
+                    );
+                    draw_sequencer(ui, &params, setter, &mut state.history);
+                    // The scope only knows the length of a bar while the host is playing
+                    let bar_beats = displays.scope.lock().unwrap().read().bar_beats;
+                    draw_tap_timeline(
+                        ui,
+                        &params,
+                        setter,
+                        &mut state.tap_timeline,
+                        bar_beats.unwrap_or(4.0),
+                        &mut state.history,
+                    );
+                    ui.separator();
+
+                    egui::Grid::new("params").num_columns(2).show(ui, |ui| {
+                        ui.label("Input Gain");
+                        draw_param_slider(
+                            ui,
+                            &params.input_gain,
+                            setter,
+                            &params,
+                            &mut state.midi_learn,
+                            &mut state.history,
+                        );
+                        ui.end_row();
+
+                        ui.label("Output Gain");
+                        draw_param_slider(
+                            ui,
+                            &params.output.output_gain,
+                            setter,
+                            &params,
+                            &mut state.midi_learn,
+                            &mut state.history,
+                        );
+                        ui.end_row();
+
+                        ui.label("Mode");
+                        draw_param_slider(
+                            ui,
+                            &params.delay_a.mode,
+                            setter,
+                            &params,
+                            &mut state.midi_learn,
+                            &mut state.history,
+                        );
+                        ui.end_row();
+
+                        ui.label("Time");
+                        draw_param_slider(
+                            ui,
+                            &params.delay_a.time,
+                            setter,
+                            &params,
+                            &mut state.midi_learn,
+                            &mut state.history,
+                        );
+                        ui.end_row();
+
+                        ui.label("Delay");
+                        draw_param_slider(
+                            ui,
+                            &params.delay_a.delay,
+                            setter,
+                            &params,
+                            &mut state.midi_learn,
+                            &mut state.history,
+                        );
+                        ui.end_row();
+
+                        ui.label("Feedback");
+                        draw_param_slider(
+                            ui,
+                            &params.feedback_path.feedback,
+                            setter,
+                            &params,
+                            &mut state.midi_learn,
+                            &mut state.history,
+                        );
+                        ui.end_row();
+
+                        ui.label("Mix");
+                        draw_param_slider(
+                            ui,
+                            &params.output.mix,
+                            setter,
+                            &params,
+                            &mut state.midi_learn,
+                            &mut state.history,
+                        );
+                        ui.end_row();
+                    });
+
+                    ui.separator();
+                    draw_xy_pad(ui, &params, setter, &mut state.xy_pad, &mut state.history);
+
+                    ui.separator();
+                    draw_waveform(ui, displays.waveform.lock().unwrap().read());
+                    draw_scope(ui, displays.scope.lock().unwrap().read());
+                    draw_spectrum(ui, &params, &displays.spectrum.lock().unwrap());
+
+                    ui.separator();
+                    draw_meter(ui, "In", &meters.input);
+                    draw_meter(ui, "Wet", &meters.wet);
+                    draw_meter(ui, "Out", &meters.output);
+                    draw_loudness(ui, &meters);
+                    draw_goniometer(ui, displays.goniometer.lock().unwrap().read());
+                });
+            });
+
+            state.history.update(egui_ctx);
+        },
+    )
+}
@@ -0,0 +1,177 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use nih_plug::nih_log;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+use crate::dsp::{to_f32, DelayLine, Sample};
+
+/// The number of samples per encoded chunk of a saved frozen loop.
+const FROZEN_STATE_CHUNK_SAMPLES: usize = 16384;
+
+/// A copy of the frozen loop that is stored in the plugin state, so a frozen texture survives
+/// reloading the project. The audio thread captures into preallocated storage and the encoding
+/// only happens when the host saves the state.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(into = "EncodedFrozenBuffer", from = "EncodedFrozenBuffer")]
+pub struct FrozenBuffer {
+    pub sample_rate: f32,
+    /// The loop for every channel, oldest sample first.
+    channels: Vec<Vec<f32>>,
+}
+
+impl FrozenBuffer {
+    pub fn is_empty(&self) -> bool {
+        self.channels.iter().all(Vec::is_empty)
+    }
+
+    /// Make sure a loop of up to `capacity` samples can be captured for `num_channels` channels
+    /// without allocating.
+    pub fn allocate(&mut self, num_channels: usize, capacity: usize) {
+        self.channels.resize_with(num_channels, Vec::new);
+        for channel in &mut self.channels {
+            channel.reserve(capacity.saturating_sub(channel.len()));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for channel in &mut self.channels {
+            channel.clear();
+        }
+    }
+
+    /// Copy the last `len` samples written to each delay line. Loops that don't fit in the
+    /// preallocated storage are not saved at all.
+    pub fn capture(&mut self, delay_lines: &[DelayLine], len: usize, sample_rate: f32) {
+        self.clear();
+        if self.channels.len() < delay_lines.len()
+            || self.channels.iter().any(|channel| channel.capacity() < len)
+        {
+            return;
+        }
+
+        self.sample_rate = sample_rate;
+        for (channel, line) in self.channels.iter_mut().zip(delay_lines) {
+            channel.extend((0..len).rev().map(|offset| to_f32(line.read(offset + 1))));
+        }
+    }
+
+    /// Write the loop back so it ends right before each delay line's write position.
+    pub fn restore(&self, delay_lines: &mut [DelayLine]) {
+        for (channel, line) in self.channels.iter().zip(delay_lines) {
+            if channel.len() >= line.buffer.len() {
+                continue;
+            }
+
+            for (i, &sample) in channel.iter().enumerate() {
+                let pos = line.position(channel.len() - i);
+                line.buffer[pos] = sample as Sample;
+            }
+        }
+    }
+}
+
+/// The serialized form of a [`FrozenBuffer`]. Every channel is split into chunks that are
+/// quantized to 16-bit relative to the chunk's peak, deflated, and then base64 encoded.
+#[derive(Serialize, Deserialize)]
+struct EncodedFrozenBuffer {
+    sample_rate: f32,
+    channels: Vec<Vec<EncodedChunk>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncodedChunk {
+    peak: f32,
+    data: String,
+}
+
+impl EncodedChunk {
+    fn encode(samples: &[f32]) -> Self {
+        let peak = samples
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let scale = if peak > 0.0 {
+            i16::MAX as f32 / peak
+        } else {
+            0.0
+        };
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| ((sample * scale).round() as i16).to_le_bytes())
+            .collect();
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        let compressed = encoder
+            .write_all(&bytes)
+            .and_then(|()| encoder.finish())
+            .unwrap_or_default();
+
+        Self {
+            peak,
+            data: BASE64.encode(compressed),
+        }
+    }
+
+    fn decode(&self) -> Option<Vec<f32>> {
+        let compressed = BASE64.decode(&self.data).ok()?;
+        let mut bytes = Vec::new();
+        DeflateDecoder::new(compressed.as_slice())
+            .read_to_end(&mut bytes)
+            .ok()?;
+
+        let scale = self.peak / i16::MAX as f32;
+        Some(
+            bytes
+                .chunks_exact(2)
+                .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 * scale)
+                .collect(),
+        )
+    }
+}
+
+impl From<FrozenBuffer> for EncodedFrozenBuffer {
+    fn from(frozen: FrozenBuffer) -> Self {
+        Self {
+            sample_rate: frozen.sample_rate,
+            channels: frozen
+                .channels
+                .iter()
+                .map(|channel| {
+                    channel
+                        .chunks(FROZEN_STATE_CHUNK_SAMPLES)
+                        .map(EncodedChunk::encode)
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<EncodedFrozenBuffer> for FrozenBuffer {
+    fn from(encoded: EncodedFrozenBuffer) -> Self {
+        let channels: Option<Vec<Vec<f32>>> = encoded
+            .channels
+            .iter()
+            .map(|chunks| {
+                chunks.iter().try_fold(Vec::new(), |mut channel, chunk| {
+                    channel.extend(chunk.decode()?);
+                    Some(channel)
+                })
+            })
+            .collect();
+
+        match channels {
+            Some(channels) => Self {
+                sample_rate: encoded.sample_rate,
+                channels,
+            },
+            None => {
+                nih_log!("The saved frozen buffer could not be decoded, ignoring it");
+                Self::default()
+            }
+        }
+    }
+}
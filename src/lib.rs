@@ -1,88 +1,1792 @@
-use nih_plug::{params, prelude::*};
-use std::{sync::Arc, usize};
+use nih_plug::prelude::*;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+mod dsp;
+mod editor;
+mod frozen;
+mod looper;
+mod midi;
+mod modulation;
+mod params;
+mod presets;
+mod sequencer;
+mod taps;
+
+use dsp::block;
+use dsp::character::CharacterSettings;
+use dsp::diffusion::Diffuser;
+use dsp::euclidean::TapPattern;
+use dsp::loudness::LoudnessMeter;
+use dsp::modes::{self, Taps};
+use dsp::oversampling;
+use dsp::reverb::Reverb;
+use dsp::stutter::{Stutter, StutterSettings};
+use dsp::{
+    ping_pong_partner, soft_limit, time_constant_coefficient, to_f32, Biquad, BiquadCoefficients,
+    Cached, DcBlocker, DelayLine, EnvelopeFollower, Lfo, LoopFilter, OnePole, Sample, StereoWidth,
+    WowFlutter,
+};
+use editor::{
+    Displays, Goniometer, GoniometerCapture, MeterBallistics, Meters, Scope, ScopeCapture,
+    Spectrum, SpectrumAnalyzer, SpectrumBlock, SpectrumCapture, Waveform, WaveformCapture,
+};
+use looper::Looper;
+use midi::{MidiCcs, RemoteParams, TapTempo};
+use modulation::{Modulation, Sources, CUTOFF_OCTAVES, NUM_MOD_SLOTS};
+use params::{DEFAULT_MAX_DELAY_SECONDS, ENGINE_B_MAX_SECONDS};
+use sequencer::StepSequence;
+
+pub use midi::RemoteSysEx;
+pub use params::{
+    Character, CombPolarity, DelayMode, DuckSource, EngineRouting, EnvelopeTarget, FeedbackShift,
+    Interpolation, LfoShape, LooperState, MixLaw, ModDestination, ModSlotParams, ModSource,
+    MyplugParams, NoteDivision, Oversampling, ParamOverrides, Saturation, ShimmerShift,
+    TimeChangeMode, TransportMode,
+};
 
 // This is a shortened version of the gain example with most comments removed, check out
 // https://github.com/robbert-vdh/nih-plug/blob/master/plugins/examples/gain/src/lib.rs to get
 // started
 
-struct Myplug {
+/// The number of samples processed at a time. Gains and the dry and wet signals are computed for
+/// a whole block at once in buffers on the stack.
+const BLOCK_SIZE: usize = 64;
+
+/// The most channels any of the audio IO layouts have.
+const MAX_CHANNELS: usize = 8;
+
+/// How long the read heads crossfade for when the delay time changes, in seconds.
+const CROSSFADE_SECONDS: f32 = 0.03;
+
+/// The time constant for the read position slewing towards a new delay time in glide mode, in
+/// seconds.
+const GLIDE_SECONDS: f32 = 0.25;
+
+/// The level in decibels below which the repeats are considered to have died out when computing
+/// the tail length.
+const TAIL_THRESHOLD_DB: f32 = -80.0;
+
+/// The range the cutoff modulation keeps the high cut filter in. The upper limit is relative to
+/// the sample rate, and it also keeps the bell filter's frequency below Nyquist.
+const MIN_CUTOFF_HZ: f32 = 20.0;
+const MAX_CUTOFF_RATIO: f32 = 0.45;
+
+/// The cutoff of the comb mode's damping filter at no and at full damping.
+const COMB_MAX_DAMPING_HZ: f32 = 20000.0;
+const COMB_MIN_DAMPING_HZ: f32 = 200.0;
+
+/// The Haas doubler delays every other channel by this fraction of its delay time, so the
+/// channels are always offset from each other.
+const HAAS_SHORT_RATIO: f32 = 0.3;
+/// How far the Haas doubler detunes the channels against each other in cents, on top of the
+/// detune parameter.
+const HAAS_DETUNE_CENTS: f32 = 7.0;
+
+/// The length of the window the detuned read heads drift through before they jump back, in
+/// seconds. The detuned repeats are delayed by half of this on average.
+const DETUNE_WINDOW_SECONDS: f32 = 0.015;
+
+/// The autopan LFO's seed for its random shapes. Unlike the modulation LFO's seed this can't be
+/// changed.
+const AUTOPAN_SEED: u32 = 0xa070;
+
+/// The corner frequency of the decay tilt's high shelf.
+const DECAY_TILT_CORNER_HZ: f32 = 1500.0;
+
+/// The range the input envelope is measured over, in decibels. Anything this far below 0 dBFS
+/// counts as silence.
+const ENVELOPE_RANGE_DB: f32 = 60.0;
+
+/// How far the key signal needs to rise above the ducking threshold for the full ducking amount
+/// to apply, in decibels.
+const DUCK_RANGE_DB: f32 = 12.0;
+
+/// How long it takes to fade into and out of freeze mode, in seconds.
+const FREEZE_FADE_SECONDS: f32 = 0.02;
+
+/// How long it takes the feedback to glide to unity and back when infinite is toggled, in
+/// seconds.
+const INFINITE_FADE_SECONDS: f32 = 0.05;
+
+/// How long the clear trigger fades out the repeats before the delay lines are emptied, in
+/// seconds.
+const CLEAR_FADE_SECONDS: f32 = 0.01;
+
+/// How long bypassing crossfades between the processed and the unprocessed signal, in seconds.
+const BYPASS_FADE_SECONDS: f32 = 0.01;
+
+/// How fast the auto gain follows changes to the mix and the feedback, in seconds.
+const AUTO_GAIN_SECONDS: f32 = 0.2;
+/// Auto gain treats the feedback as if it never went above this, so infinite feedback doesn't
+/// mute the output.
+const AUTO_GAIN_MAX_FEEDBACK: f32 = 0.95;
+/// The range of the auto gain's compensation.
+const AUTO_GAIN_MIN: f32 = 0.1;
+const AUTO_GAIN_MAX: f32 = 2.0;
+
+/// The grain length of the shimmer pitch shifter, in seconds.
+const SHIMMER_WINDOW_SECONDS: f32 = 0.05;
+
+/// How long the stutter repeats fade in and out for, in seconds.
+const STUTTER_FADE_SECONDS: f32 = 0.002;
+
+/// How long the sequencer fades the input in and out for on steps that are switched off, in
+/// seconds.
+const SEQUENCER_FADE_SECONDS: f32 = 0.005;
+
+/// The longest frozen loop that can be saved in the plugin state, in seconds.
+const FROZEN_STATE_MAX_SECONDS: f32 = 4.0;
+
+/// How far the freeze fade needs to have progressed before the loop is captured for the plugin
+/// state.
+const FROZEN_CAPTURE_THRESHOLD: f32 = 0.999;
+
+/// The delay plugin. `I` sets the IDs it's registered with, see [`LegacyMyplug`].
+pub struct Myplug<I: PluginIds = CurrentIds> {
     params: Arc<MyplugParams>,
-    prevsample: Vec<f32>,
-    iterdelay: usize,
-    iterrepeats: usize,
-    prev: usize,
-}
+    sample_rate: f32,
+    /// The length of the delay buffers in samples, derived from the sample rate.
+    buffer_len: usize,
+    /// The length of a read head crossfade in samples, derived from the sample rate.
+    crossfade_len: usize,
+    /// The number of main input channels in the current audio IO layout. This may be lower than
+    /// the number of output channels.
+    num_input_channels: usize,
+    /// One delay line per channel. These are allocated in `initialize()` once the channel count
+    /// is known.
+    delay_lines: Vec<DelayLine>,
+    /// Diffuse every channel's wet signal, allocated alongside the delay lines.
+    diffusers: Vec<Diffuser>,
+    /// The reverb after the diffusion, one per channel.
+    reverbs: Vec<Reverb>,
+    /// Sets the wet signal's width for every pair of channels.
+    stereo_width: [StereoWidth; MAX_CHANNELS / 2],
+    /// The second delay engine's delay lines, one per channel. Only their buffers, filters and
+    /// latency compensation are used.
+    engine_b: Vec<DelayLine>,
+    /// Delay B's delay time in samples, which glides to the delay time parameter.
+    engine_b_delay: f32,
+    /// Whether delay B was processed during the last block.
+    engine_b_active: bool,
+    /// Whether the reverb was processed during the last block. It's cleared when it gets
+    /// enabled again so it doesn't pick up where it left off.
+    reverb_active: bool,
+    /// New delay buffers allocated by the background task after the maximum delay time changed.
+    pending_buffers: Arc<Mutex<Option<Vec<Vec<Sample>>>>>,
+    /// The buffer length that has been requested from the background task, if any.
+    requested_buffer_len: Option<usize>,
 
-#[derive(Params)]
-struct MyplugParams {
-    /// The parameter's ID is used to identify the parameter in the wrappred plugin API. As long as
-    /// these IDs remain constant, you can rename and reorder these fields as you wish. The
-    /// parameters are exposed to the host in the same order they were defined. In this case, this
-    /// gain parameter is stored as linear gain while the values are displayed in decibels.
-    #[id = "gain"]
-    pub gain: FloatParam,
-    #[id = "delay"]
-    pub delay: IntParam,
-    #[id = "mode"]
-    pub mode: IntParam,
-    #[id = "time"]
-    pub time: IntParam,
-    #[id = "mode6-ratio"]
-    pub mode6_ratio: IntParam,
+    /// Decimates the delay buffer for the editor's waveform view.
+    waveform_capture: WaveformCapture,
+    /// The editor's end of the waveform triple buffer.
+    waveform_output: Arc<Mutex<triple_buffer::Output<Waveform>>>,
+    /// Decimates the dry and the wet output for the editor's scope.
+    scope_capture: ScopeCapture,
+    scope_output: Arc<Mutex<triple_buffer::Output<Scope>>>,
+    /// Collects the stereo output and its correlation for the editor's goniometer.
+    goniometer_capture: GoniometerCapture,
+    goniometer_output: Arc<Mutex<triple_buffer::Output<Goniometer>>>,
+    /// Collects the wet signal for the spectrum analyzer, which runs as a background task.
+    spectrum_capture: SpectrumCapture,
+    spectrum_analyzer: Arc<Mutex<SpectrumAnalyzer>>,
+    /// The analyzed spectrum shown by the editor.
+    spectrum: Arc<Mutex<Spectrum>>,
+
+    /// Slews between zero and one when freeze is toggled, so the feedback path can be faded
+    /// over to looping the buffer's contents without clicks.
+    freeze_amount: f32,
+    /// Slews between zero and one when infinite is toggled, so the feedback glides to unity.
+    infinite_amount: f32,
+    /// Ramps from zero to one when the plugin gets bypassed.
+    bypass_amount: f32,
+    /// The auto gain's current compensation, which slews towards the estimate for the current
+    /// settings.
+    auto_gain: f32,
+    /// Whether the clear trigger was held during the last block, so it only fires once per press.
+    clear_held: bool,
+    /// How many samples are left of the fade out before the delay lines are cleared.
+    clear_fade_remaining: usize,
+    /// Whether the current frozen loop has been copied to `MyplugParams::frozen_buffer`.
+    frozen_captured: bool,
+    /// Modulates the read positions in tape wow and flutter emulation.
+    wow_flutter: WowFlutter,
+    /// Tracks the looper's transport.
+    looper: Looper,
+    /// The modulation LFO, which also moves the read positions.
+    lfo: Lfo,
+    /// Pans the wet signal.
+    autopan: Lfo,
+    /// Decides which slices get repeated in stutter mode.
+    stutter: Stutter,
+    /// The audio thread's copy of `MyplugParams::sequence`.
+    sequence: StepSequence,
+    /// Slews between zero and one as the sequencer switches the input to the delay on and off.
+    sequencer_send: f32,
+    /// Whether the host's transport was playing during the last buffer. The LFO and the stutter
+    /// restart when playback starts so the modulation and the repeats are the same on every take.
+    was_playing: bool,
+    /// The timeline position in samples the next buffer should start at if the transport keeps
+    /// playing, used to tell when the host jumps or loops.
+    next_pos_samples: Option<i64>,
+    /// The host's tempo during the last block, and whether the delay lines are still catching up
+    /// with a tempo change. Tempo changes always crossfade to the new delay time, so a tempo ramp
+    /// doesn't bend the pitch of the repeats in glide mode.
+    last_tempo: Option<f64>,
+    tempo_resync: bool,
+    /// Follows the level of the ducker's key signal.
+    duck_envelope: EnvelopeFollower,
+    /// Follows the input's level for the envelope modulation.
+    input_envelope: EnvelopeFollower,
+    /// Sets the delay time from incoming MIDI notes when MIDI tap is enabled.
+    tap_tempo: TapTempo,
+    /// The period of the last MIDI note in samples, used when note tuning is enabled.
+    tuned_delay: Option<f32>,
+    /// The number of samples processed since the plugin was last reset, used to time taps.
+    sample_clock: u64,
+    /// The latency last reported to the host, which depends on the oversampling amount.
+    latency_samples: u32,
+
+    /// Incoming MIDI CCs and the parameters they're mapped to, shared with the editor for MIDI
+    /// learn.
+    midi_ccs: Arc<MidiCcs>,
+    /// Parameter values set over MIDI, and the parameters' order in SysEx snapshots.
+    remote: RemoteParams,
+    /// The last snapshot received over SysEx while the editor is open. Those values already apply
+    /// through `remote`, the editor also sets the parameters so the host sees the change.
+    sysex_snapshot: Arc<Mutex<Option<RemoteSysEx>>>,
+
+    /// Levels shared with the editor, and the ballistics used to compute them.
+    meters: Arc<Meters>,
+    input_meter: MeterBallistics,
+    wet_meter: MeterBallistics,
+    output_meter: MeterBallistics,
+    loudness_meter: LoudnessMeter,
+
+    /// The feedback paths' filter coefficients, keyed by the cutoff and the sample rate so
+    /// they're only recomputed while the cutoffs are smoothed or modulated.
+    low_cut_coefficient: Cached<(f32, f32), f32>,
+    high_cut_coefficient: Cached<(f32, f32), f32>,
+    bell_coefficients: Cached<(f32, f32, f32, f32), BiquadCoefficients>,
+    b_low_cut_coefficient: Cached<(f32, f32), f32>,
+    b_high_cut_coefficient: Cached<(f32, f32), f32>,
+
+    ids: PhantomData<I>,
 }
 
-impl Default for Myplug {
+impl<I: PluginIds> Default for Myplug<I> {
     fn default() -> Self {
-        Self {
-            params: Arc::new(MyplugParams::default()),
-            prevsample: vec![0.0; 400000],
-            iterdelay: 0,
-            iterrepeats: 399999,
-            prev: 399999,
-        }
+        Self::new(MyplugParams::default())
     }
 }
 
-impl Default for MyplugParams {
-    fn default() -> Self {
+impl<I: PluginIds> Myplug<I> {
+    pub fn new(params: MyplugParams) -> Self {
+        let (waveform_input, waveform_output) =
+            triple_buffer::TripleBuffer::<Waveform>::default().split();
+        let (scope_input, scope_output) = triple_buffer::TripleBuffer::<Scope>::default().split();
+        let (goniometer_input, goniometer_output) =
+            triple_buffer::TripleBuffer::<Goniometer>::default().split();
+        let (spectrum_input, spectrum_output) =
+            triple_buffer::TripleBuffer::<SpectrumBlock>::default().split();
+        let spectrum = Arc::new(Mutex::new(Spectrum::default()));
+        // The remote parameters point into the shared parameters, so they're created once the
+        // parameters are in their final place
+        let params = Arc::new(params);
+        let remote = RemoteParams::new(params.as_ref());
+
         Self {
-            // This gain is stored as linear gain. NIH-plug comes with useful conversion functions
-            // to treat these kinds of parameters as if we were dealing with decibels. Storing this
-            // as decibels is easier to work with, but requires a conversion for every sample.
-            gain: FloatParam::new(
-                "Gain",
-                util::db_to_gain(0.0),
-                FloatRange::Skewed {
-                    min: util::db_to_gain(-30.0),
-                    max: util::db_to_gain(30.0),
-                    // This makes the range appear as if it was linear when displaying the values as
-                    // decibels
-                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
-                },
+            params,
+            sample_rate: 44100.0,
+            buffer_len: buffer_len(44100.0, DEFAULT_MAX_DELAY_SECONDS),
+            crossfade_len: (44100.0 * CROSSFADE_SECONDS) as usize,
+            num_input_channels: 2,
+            delay_lines: Vec::new(),
+            diffusers: Vec::new(),
+            reverbs: Vec::new(),
+            reverb_active: false,
+            engine_b: Vec::new(),
+            engine_b_delay: 0.0,
+            engine_b_active: false,
+            stereo_width: Default::default(),
+            pending_buffers: Arc::new(Mutex::new(None)),
+            requested_buffer_len: None,
+
+            waveform_capture: WaveformCapture::new(waveform_input),
+            waveform_output: Arc::new(Mutex::new(waveform_output)),
+            scope_capture: ScopeCapture::new(scope_input),
+            scope_output: Arc::new(Mutex::new(scope_output)),
+            goniometer_capture: GoniometerCapture::new(goniometer_input),
+            goniometer_output: Arc::new(Mutex::new(goniometer_output)),
+            spectrum_capture: SpectrumCapture::new(spectrum_input),
+            spectrum_analyzer: Arc::new(Mutex::new(SpectrumAnalyzer::new(
+                spectrum_output,
+                spectrum.clone(),
+            ))),
+            spectrum,
+
+            freeze_amount: 0.0,
+            infinite_amount: 0.0,
+            bypass_amount: 0.0,
+            auto_gain: 1.0,
+            clear_held: false,
+            clear_fade_remaining: 0,
+            frozen_captured: false,
+            wow_flutter: WowFlutter::new(),
+            looper: Looper::default(),
+            lfo: Lfo::new(1),
+            autopan: Lfo::new(AUTOPAN_SEED),
+            stutter: Stutter::new(),
+            sequence: StepSequence::default(),
+            sequencer_send: 1.0,
+            was_playing: false,
+            next_pos_samples: None,
+            last_tempo: None,
+            tempo_resync: false,
+            duck_envelope: EnvelopeFollower::default(),
+            input_envelope: EnvelopeFollower::default(),
+            tap_tempo: TapTempo::default(),
+            tuned_delay: None,
+            sample_clock: 0,
+            latency_samples: 0,
+
+            midi_ccs: Arc::new(MidiCcs::default()),
+            remote,
+            sysex_snapshot: Arc::new(Mutex::new(None)),
+
+            meters: Arc::new(Meters::default()),
+            input_meter: MeterBallistics::default(),
+            wet_meter: MeterBallistics::default(),
+            output_meter: MeterBallistics::default(),
+            loudness_meter: LoudnessMeter::new(2, 44100.0),
+
+            low_cut_coefficient: Cached::default(),
+            high_cut_coefficient: Cached::default(),
+            bell_coefficients: Cached::default(),
+            b_low_cut_coefficient: Cached::default(),
+            b_high_cut_coefficient: Cached::default(),
+
+            ids: PhantomData,
+        }
+    }
+
+    /// The current delay length in samples. In sync mode this is derived from the host's tempo,
+    /// falling back to the free running `time` parameter when the host doesn't provide one.
+    fn delay_samples(&self, tempo: Option<f64>) -> usize {
+        let tapped = self
+            .tap_tempo
+            .delay_samples
+            .filter(|_| self.remote.value(&self.params.delay_a.midi_tap));
+        // The doubler's delays are too short to be tapped or synced
+        if self.remote.value(&self.params.delay_a.mode) == DelayMode::Doubler {
+            return self
+                .ms_to_samples(self.remote.value(&self.params.delay_a.haas_time))
+                .clamp(1, self.buffer_len - 1);
+        }
+        let samples = match (self.tuned_delay(), tapped, tempo) {
+            (Some(tuned), _, _) => tuned as usize,
+            (None, Some(tapped), _) => tapped,
+            (None, None, Some(tempo)) if self.remote.value(&self.params.delay_a.sync) => {
+                let seconds =
+                    self.remote.value(&self.params.delay_a.note).beats() * 60.0 / tempo as f32;
+                (seconds * self.sample_rate) as usize
+            }
+            _ => self.ms_to_samples(self.remote.value(&self.params.delay_a.time)),
+        };
+
+        samples.clamp(1, self.buffer_len - 1)
+    }
+
+    /// Whether `delay_samples()` currently follows the host's tempo.
+    fn synced(&self, tempo: Option<f64>) -> bool {
+        let tapped = self.tap_tempo.delay_samples.is_some()
+            && self.remote.value(&self.params.delay_a.midi_tap);
+        self.remote.value(&self.params.delay_a.sync)
+            && tempo.is_some()
+            && self.tuned_delay().is_none()
+            && self.remote.value(&self.params.delay_a.mode) != DelayMode::Doubler
+            && !tapped
+    }
+
+    /// The fractional delay time in samples set by the last MIDI note, if note tuning is enabled.
+    /// The Karplus-Strong and comb modes are always tuned, to the pitch parameter when there's no
+    /// note.
+    fn tuned_delay(&self) -> Option<f32> {
+        let note = self
+            .tuned_delay
+            .filter(|_| self.remote.value(&self.params.delay_a.note_tuning));
+        let period = || {
+            note.unwrap_or_else(|| self.sample_rate / self.remote.value(&self.params.delay_a.pitch))
+        };
+        match self.remote.value(&self.params.delay_a.mode) {
+            // The loop filter makes up part of the period
+            DelayMode::Karplus => Some((period() - LoopFilter::DELAY).max(1.0)),
+            DelayMode::Comb => Some(period().max(1.0)),
+            _ => note,
+        }
+    }
+
+    /// The length of the clear trigger's fade out in samples.
+    fn clear_fade_len(&self) -> usize {
+        (CLEAR_FADE_SECONDS * self.sample_rate).max(1.0) as usize
+    }
+
+    /// Fade out the repeats and then empty the delay lines, unless that's already happening.
+    fn start_clear(&mut self) {
+        if self.clear_fade_remaining == 0 {
+            self.clear_fade_remaining = self.clear_fade_len();
+        }
+    }
+
+    /// Convert a time in milliseconds to a whole number of samples at the current sample rate.
+    fn ms_to_samples(&self, ms: f32) -> usize {
+        (ms / 1000.0 * self.sample_rate) as usize
+    }
+
+    /// The latency in samples caused by the current oversampling amount and the ducker's
+    /// lookahead.
+    pub fn latency(&self) -> u32 {
+        (oversampling::latency(
+            self.remote
+                .value(&self.params.feedback_path.oversampling)
+                .factor(),
+        ) + self.lookahead()) as u32
+    }
+
+    /// The ducker's lookahead in samples.
+    fn lookahead(&self) -> usize {
+        self.ms_to_samples(self.remote.value(&self.params.output.duck_lookahead))
+            .min(oversampling::MAX_COMPENSATION - oversampling::MAX_LATENCY)
+    }
+
+    /// Tell the host how long the repeats will keep ringing out for based on the current delay
+    /// time and feedback amount. With feedback at or above unity or while frozen the repeats
+    /// never die out.
+    fn tail_status(&self) -> ProcessStatus {
+        let delay_samples = match self.delay_lines.first() {
+            Some(line) => line.prev as f32,
+            None => return ProcessStatus::Normal,
+        };
+
+        let feedback = if self.remote.value(&self.params.delay_a.mode) == DelayMode::Doubler {
+            0.0
+        } else if self.remote.value(&self.params.performance.infinite) {
+            1.0
+        } else {
+            self.remote.value(&self.params.feedback_path.feedback)
+        };
+        if feedback >= 1.0
+            || self.remote.value(&self.params.performance.freeze)
+            || self.remote.value(&self.params.performance.looper) == LooperState::Play
+        {
+            return ProcessStatus::KeepAlive;
+        }
+
+        // Every repeat is `feedback` times as loud as the previous one
+        let num_repeats = if feedback > 0.0 {
+            (util::db_to_gain(TAIL_THRESHOLD_DB).ln() / feedback.ln()).ceil()
+        } else {
+            0.0
+        };
+        let mut tail_samples = delay_samples * (num_repeats + 1.0)
+            + self.remote.value(&self.params.delay_a.pre_delay) / 1000.0 * self.sample_rate;
+        // Delay B rings out on its own, or after the main delay in serial routing
+        if self.remote.value(&self.params.engine_b.enabled) {
+            let feedback = self.remote.value(&self.params.engine_b.feedback);
+            if feedback >= 1.0 {
+                return ProcessStatus::KeepAlive;
+            }
+            let num_repeats = if feedback > 0.0 {
+                (util::db_to_gain(TAIL_THRESHOLD_DB).ln() / feedback.ln()).ceil()
+            } else {
+                0.0
+            };
+            tail_samples += self.engine_b_delay * (num_repeats + 1.0);
+        }
+        // The reverb's decay time is how long it takes to fall by 60 dB
+        if self.remote.value(&self.params.output.reverb) {
+            tail_samples += self.remote.value(&self.params.output.reverb_decay)
+                * self.sample_rate
+                * TAIL_THRESHOLD_DB
+                / -60.0;
+        }
+
+        ProcessStatus::Tail(tail_samples.min(u32::MAX as f32) as u32)
+    }
+
+    /// Reallocate the delay buffers on a background thread when the maximum delay time changes,
+    /// and swap them in once they're ready.
+    fn update_buffer_len(&mut self, context: &mut impl ProcessContext<Self>) {
+        if self.requested_buffer_len.is_some() {
+            if let Ok(mut pending) = self.pending_buffers.try_lock() {
+                if let Some(mut buffers) = pending.take() {
+                    // Buffers from a request made before the last `initialize()` are discarded
+                    let len = self.requested_buffer_len;
+                    if buffers.len() != self.delay_lines.len()
+                        || buffers.iter().any(|buffer| Some(buffer.len()) != len)
+                    {
+                        context.execute_background(BufferTask::Deallocate(buffers));
+                        return;
+                    }
+
+                    for (line, buffer) in self.delay_lines.iter_mut().zip(&mut buffers) {
+                        line.swap_buffer(buffer);
+                    }
+                    self.buffer_len = self.requested_buffer_len.take().unwrap_or(self.buffer_len);
+                    context.execute_background(BufferTask::Deallocate(buffers));
+                }
+            }
+        }
+
+        let len = buffer_len(
+            self.sample_rate,
+            self.remote.value(&self.params.delay_a.max_delay),
+        );
+        if len != self.buffer_len && self.requested_buffer_len.is_none() {
+            self.requested_buffer_len = Some(len);
+            context.execute_background(BufferTask::Allocate {
+                num_channels: self.delay_lines.len(),
+                len,
+            });
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        event: PluginNoteEvent<Self>,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        match event {
+            NoteEvent::NoteOn { note, .. } => {
+                if self.remote.value(&self.params.delay_a.note_tuning) {
+                    self.tuned_delay = Some(self.sample_rate / util::midi_note_to_freq(note));
+                }
+                if self.remote.value(&self.params.delay_a.midi_tap) {
+                    let max_interval = self.buffer_len as u64;
+                    self.tap_tempo.tap(self.sample_clock, max_interval);
+                }
+            }
+            NoteEvent::MidiCC { cc, value, .. } => {
+                self.midi_ccs.set(cc, value);
+                if let Some(index) = self.midi_ccs.target(cc) {
+                    self.remote.set(index, value, self.sample_rate);
+                }
+            }
+            NoteEvent::MidiSysEx {
+                timing,
+                message: RemoteSysEx::RequestSnapshot,
+            } => context.send_event(NoteEvent::MidiSysEx {
+                timing,
+                message: self.remote.snapshot(),
+            }),
+            NoteEvent::MidiSysEx { message, .. } => {
+                let applied = self.remote.apply_snapshot(&message, self.sample_rate);
+                if applied && self.params.editor_state.is_open() {
+                    if let Ok(mut snapshot) = self.sysex_snapshot.try_lock() {
+                        *snapshot = Some(message);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Process the samples in `range` of the host's buffers.
+    fn process_segment(
+        &mut self,
+        channels: &mut [&mut [f32]],
+        sidechain: Option<&[&mut [f32]]>,
+        range: Range<usize>,
+        playhead: Playhead,
+    ) -> ProcessStatus {
+        self.remote.prune();
+
+        let num_channels = channels.len().min(MAX_CHANNELS);
+        let mut segment: [&mut [f32]; MAX_CHANNELS] = Default::default();
+        for (segment, channel) in segment.iter_mut().zip(channels.iter_mut()) {
+            *segment = &mut channel[range.clone()];
+        }
+
+        let mut sidechain_segment: [&[f32]; MAX_CHANNELS] = Default::default();
+        let mut num_sidechain_channels = 0;
+        if let Some(sidechain) = sidechain {
+            num_sidechain_channels = sidechain.len().min(MAX_CHANNELS);
+            for (segment, channel) in sidechain_segment.iter_mut().zip(sidechain) {
+                *segment = &channel[range.clone()];
+            }
+        }
+        let sidechain = sidechain.map(|_| &sidechain_segment[..num_sidechain_channels]);
+
+        let playhead = playhead.advance(range.start, self.sample_rate);
+        self.process_channels(&mut segment[..num_channels], sidechain, playhead)
+    }
+
+    /// Process a block of audio in place. This contains all of `process()` that doesn't need the
+    /// host, so it can also be used to render audio offline. `playhead` is the transport's state at
+    /// the first sample.
+    pub fn process_channels(
+        &mut self,
+        channels: &mut [&mut [f32]],
+        sidechain: Option<&[&[f32]]>,
+        playhead: Playhead,
+    ) -> ProcessStatus {
+        let tempo = playhead.tempo;
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        let duck_attack = time_constant_coefficient(
+            self.remote.value(&self.params.output.duck_attack) / 1000.0,
+            self.sample_rate,
+        );
+        let duck_release = time_constant_coefficient(
+            self.remote.value(&self.params.output.duck_release) / 1000.0,
+            self.sample_rate,
+        );
+        let duck_hold = self.ms_to_samples(self.remote.value(&self.params.output.duck_hold));
+        let env_target = self.remote.value(&self.params.modulation.env_target);
+        let env_attack = time_constant_coefficient(
+            self.remote.value(&self.params.modulation.env_attack) / 1000.0,
+            self.sample_rate,
+        );
+        let env_release = time_constant_coefficient(
+            self.remote.value(&self.params.modulation.env_release) / 1000.0,
+            self.sample_rate,
+        );
+        let max_feedback = self.params.feedback_path.feedback.preview_plain(1.0) as Sample;
+        let feedback_shift = self.remote.value(&self.params.feedback_path.feedback_shift);
+        let comb_sign = match self.remote.value(&self.params.delay_a.comb_polarity) {
+            CombPolarity::Positive => 1.0,
+            CombPolarity::Negative => -1.0,
+        };
+        // Without damping the loop filter is almost fully open, and full damping only lets the
+        // fundamentals of low pitches through
+        let comb_damping = OnePole::coefficient(
+            COMB_MAX_DAMPING_HZ
+                * (COMB_MIN_DAMPING_HZ / COMB_MAX_DAMPING_HZ)
+                    .powf(self.remote.value(&self.params.delay_a.comb_damping)),
+            self.sample_rate,
+        );
+        let mod_routes: [(ModSource, ModDestination); NUM_MOD_SLOTS] =
+            std::array::from_fn(|slot| {
+                let slot = &self.params.modulation.mod_slots[slot];
+                (
+                    self.remote.value(&slot.source),
+                    self.remote.value(&slot.destination),
+                )
+            });
+        let duck_threshold = self.remote.value(&self.params.output.duck_threshold);
+        let looper = self.remote.value(&self.params.performance.looper);
+        let punch_in = self.remote.value(&self.params.performance.loop_punch_in);
+        let clear_loop = self.looper.update(
+            looper,
+            punch_in,
+            self.remote.value(&self.params.performance.loop_clear),
+            self.sample_clock,
+            self.buffer_len - 1,
+        );
+        if clear_loop {
+            for line in &mut self.delay_lines {
+                line.reset();
+            }
+        }
+        let clear_fade_len = self.clear_fade_len();
+        let clear = self.remote.value(&self.params.performance.clear);
+        if clear && !self.clear_held {
+            self.start_clear();
+        }
+        self.clear_held = clear;
+        // Playing a loop works just like freezing the buffer
+        let freeze_target =
+            if self.remote.value(&self.params.performance.freeze) || looper == LooperState::Play {
+                1.0
+            } else {
+                0.0
+            };
+        let freeze_coefficient = time_constant_coefficient(FREEZE_FADE_SECONDS, self.sample_rate);
+        let infinite_target = if self.remote.value(&self.params.performance.infinite) {
+            1.0
+        } else {
+            0.0
+        };
+        let infinite_coefficient =
+            time_constant_coefficient(INFINITE_FADE_SECONDS, self.sample_rate);
+        let auto_gain = self.remote.value(&self.params.output.auto_gain);
+        let auto_gain_feedback = if self.remote.value(&self.params.performance.infinite) {
+            AUTO_GAIN_MAX_FEEDBACK
+        } else {
+            self.remote
+                .value(&self.params.feedback_path.feedback)
+                .min(AUTO_GAIN_MAX_FEEDBACK)
+        };
+        let auto_gain_coefficient = time_constant_coefficient(AUTO_GAIN_SECONDS, self.sample_rate);
+        let bypass_target = if self.remote.value(&self.params.bypass) {
+            1.0
+        } else {
+            0.0
+        };
+        let bypass_step = 1.0 / (BYPASS_FADE_SECONDS * self.sample_rate);
+        let dc_coefficient = DcBlocker::coefficient(self.sample_rate);
+        let reverb_enabled = self.remote.value(&self.params.output.reverb);
+        let reverb_decay = self.remote.value(&self.params.output.reverb_decay);
+        let mono_below = self.remote.value(&self.params.output.mono_low).then(|| {
+            OnePole::coefficient(
+                self.remote.value(&self.params.output.mono_below),
+                self.sample_rate,
             )
-            // Because the gain parameter is stored as linear gain instead of storing the value as
-            // decibels, we need logarithmic smoothing
-            .with_smoother(SmoothingStyle::Logarithmic(50.0))
-            .with_unit(" dB")
-            // There are many predefined formatters we can use here. If the gain was stored as
-            // decibels instead of as a linear gain value, we could have also used the
-            // `.with_step_size(0.1)` function to get internal rounding.
-            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
-            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
-            delay: IntParam::new("Delay", 0, IntRange::Linear { min: 1, max: 1000 })
-                .with_smoother(SmoothingStyle::None),
-            mode: IntParam::new("Mode", 1, IntRange::Linear { min: 1, max: 7 })
-                .with_smoother(SmoothingStyle::None),
-            time: IntParam::new("Time", 1, IntRange::Linear { min: 1, max: 1000 })
-                .with_smoother(SmoothingStyle::None),
-            mode6_ratio: IntParam::new("Mode6_ratio", 3, IntRange::Linear { min: 3, max: 21 })
-                .with_smoother(SmoothingStyle::None),
+        });
+        let decay_tilt_coefficient = OnePole::coefficient(DECAY_TILT_CORNER_HZ, self.sample_rate);
+        // Parameters without smoothing are only read once per block. With sample accurate
+        // automation the host's buffer is split at every parameter change, so these are still
+        // up to date for every sample.
+        let saturation = self.remote.value(&self.params.feedback_path.saturation);
+        let character = CharacterSettings::new(
+            self.remote.value(&self.params.feedback_path.character),
+            self.remote.value(&self.params.feedback_path.age),
+            self.sample_rate,
+        );
+        let shimmer_ratio = self
+            .remote
+            .value(&self.params.feedback_path.shimmer_shift)
+            .ratio();
+        let mode = self.remote.value(&self.params.delay_a.mode);
+        let shuffle_ratio = self.remote.value(&self.params.delay_a.mode6_ratio) as usize;
+        let jitter = self.remote.value(&self.params.delay_a.jitter);
+        let interpolation = self.remote.value(&self.params.delay_a.interpolation);
+        let glide_coefficient = time_constant_coefficient(GLIDE_SECONDS, self.sample_rate);
+        let detune_window = DETUNE_WINDOW_SECONDS * self.sample_rate;
+        let engine_b =
+            self.remote.value(&self.params.engine_b.enabled) && !self.engine_b.is_empty();
+        let engine_routing = self.remote.value(&self.params.engine_routing);
+        let ping = engine_b && engine_routing == EngineRouting::Ping;
+        let engine_b_target = match tempo.filter(|_| self.remote.value(&self.params.engine_b.sync))
+        {
+            Some(tempo) => {
+                self.remote.value(&self.params.engine_b.note).beats() * 60.0 / tempo as f32
+                    * self.sample_rate
+            }
+            None => self.remote.value(&self.params.engine_b.time) / 1000.0 * self.sample_rate,
+        };
+        let engine_b_len = self.engine_b.first().map_or(0, |line| line.buffer.len());
+        let engine_b_target = engine_b_target.clamp(1.0, engine_b_len.saturating_sub(2) as f32);
+        if engine_b && !self.engine_b_active {
+            for line in &mut self.engine_b {
+                line.reset();
+            }
+            self.engine_b_delay = engine_b_target;
+        }
+        self.engine_b_active = engine_b;
+        let pre_delay_synced = tempo
+            .filter(|_| self.remote.value(&self.params.delay_a.pre_delay_sync))
+            .map(|tempo| {
+                self.remote
+                    .value(&self.params.delay_a.pre_delay_note)
+                    .beats()
+                    * 60.0
+                    / tempo as f32
+                    * self.sample_rate
+            });
+        // The Euclidean and multi-tap patterns span a bar, and feeding back the bar repeats the
+        // pattern
+        let tap_mode = matches!(mode, DelayMode::Euclidean | DelayMode::MultiTap);
+        let pattern_bar = tempo.filter(|_| tap_mode).map(|tempo| {
+            let seconds = playhead.bar_beats.unwrap_or(4.0) * 60.0 / tempo;
+            ((seconds * self.sample_rate as f64) as usize).clamp(1, self.buffer_len - 1)
+        });
+        let delay_samples = match (self.looper.punched_len(), pattern_bar) {
+            (Some(len), _) if looper != LooperState::Off && punch_in => len,
+            (_, Some(bar_samples)) => bar_samples,
+            _ => self.delay_samples(tempo),
+        };
+        let tap_pattern = if mode == DelayMode::MultiTap {
+            TapPattern::from_taps(
+                self.params
+                    .delay_a
+                    .taps
+                    .iter()
+                    .filter(|tap| self.remote.value(&tap.enabled))
+                    .map(|tap| (self.remote.value(&tap.time), self.remote.value(&tap.level))),
+                delay_samples,
+            )
+        } else {
+            TapPattern::new(
+                self.remote.value(&self.params.delay_a.euclid_steps) as usize,
+                self.remote.value(&self.params.delay_a.euclid_pulses) as usize,
+                self.remote.value(&self.params.delay_a.euclid_rotation) as usize,
+                delay_samples,
+            )
+        };
+        // Tuned delay times need the fractional part to stay in tune at higher pitches
+        let tuned_fraction = self.tuned_delay().map_or(0.0, f32::fract);
+        // The stereo offset lengthens either the left or the right channels' delay time. Loops
+        // are the same length on every channel.
+        let looping = looper != LooperState::Off;
+        let stereo_offset = if self.remote.value(&self.params.delay_a.stereo_link)
+            || self.delay_lines.len() < 2
+            || looping
+        {
+            0.0
+        } else {
+            self.remote.value(&self.params.delay_a.stereo_offset)
+        };
+        let offset_samples = self.ms_to_samples(stereo_offset.abs());
+        let offset_channel = if stereo_offset > 0.0 { 1 } else { 0 };
+        let time_spread = if looping {
+            0.0
+        } else {
+            self.remote.value(&self.params.delay_a.time_spread)
+        };
+        let second_tap_samples = self
+            .ms_to_samples(self.remote.value(&self.params.delay_a.delay))
+            .clamp(1, self.buffer_len - 1);
+        let duck_source = self.remote.value(&self.params.output.duck_source);
+        let mix_law = self.remote.value(&self.params.output.mix_law);
+        let kill_dry = self.remote.value(&self.params.output.kill_dry);
+        let lfo_shape = self.remote.value(&self.params.modulation.lfo_shape);
+        let lfo_synced_rate = tempo
+            .filter(|_| self.remote.value(&self.params.modulation.lfo_sync))
+            .map(|tempo| {
+                tempo as f32 / 60.0 / self.remote.value(&self.params.modulation.lfo_note).beats()
+            });
+        let lfo_slew = time_constant_coefficient(
+            self.remote.value(&self.params.modulation.lfo_slew) / 1000.0,
+            self.sample_rate,
+        );
+        let autopan_shape = self.remote.value(&self.params.modulation.autopan_shape);
+        let autopan_synced_rate = tempo
+            .filter(|_| self.remote.value(&self.params.modulation.autopan_sync))
+            .map(|tempo| {
+                tempo as f32
+                    / 60.0
+                    / self
+                        .remote
+                        .value(&self.params.modulation.autopan_note)
+                        .beats()
+            });
+        let oversampling = self
+            .remote
+            .value(&self.params.feedback_path.oversampling)
+            .factor();
+        let latency = oversampling::latency(oversampling);
+        // The ducker looks ahead by delaying everything but its key signal
+        let lookahead = self.lookahead();
+        let compensation = latency + lookahead;
+        // The stutter and the sequencer follow the host's timeline, so they only work while it's
+        // playing. This is the position at the first sample and the length of a beat in samples.
+        let timeline = playhead
+            .pos_beats
+            .zip(playhead.tempo)
+            .filter(|_| playhead.playing)
+            .map(|(pos, tempo)| (pos, (60.0 / tempo) as f32 * self.sample_rate));
+        let stutter_settings = timeline
+            .filter(|_| self.remote.value(&self.params.performance.stutter))
+            .map(|(_, beat_samples)| StutterSettings {
+                slice_beats: self
+                    .remote
+                    .value(&self.params.performance.stutter_slice)
+                    .beats() as f64,
+                capture_beats: self.remote.value(&self.params.performance.stutter_capture) as f64,
+                beat_samples,
+                chance: self.remote.value(&self.params.performance.stutter_chance),
+                ratio: 2.0f32
+                    .powf(self.remote.value(&self.params.performance.stutter_pitch) / 12.0),
+                fade_len: STUTTER_FADE_SECONDS * self.sample_rate,
+            });
+        // The sequence is edited on the GUI thread, so the last copy is used while it's locked
+        if let Ok(sequence) = self.params.sequence.try_read() {
+            self.sequence = *sequence;
+        }
+        let sequencer = self.remote.value(&self.params.performance.sequencer) && !looping;
+        let sequencer_rate = self
+            .remote
+            .value(&self.params.performance.sequencer_rate)
+            .beats() as f64;
+        let sequencer_length =
+            self.remote.value(&self.params.performance.sequencer_length) as usize;
+        let sequencer_fade_step = 1.0 / (SEQUENCER_FADE_SECONDS * self.sample_rate);
+        // Swing plays every other synced repeat late. The delay time is doubled so the feedback
+        // path produces the even repeats, and the odd ones are read from in between with the
+        // same decay.
+        let swing = self.remote.value(&self.params.delay_a.swing);
+        let swing_ratio = (swing > 0.5
+            && self.synced(tempo)
+            && pattern_bar.is_none()
+            && !looping
+            && !(sequencer && timeline.is_some()))
+        .then_some(swing);
+        // Synced delay times, the tap patterns' bar and the sequencer's steps all follow the tempo
+        let follows_tempo =
+            self.synced(tempo) || pattern_bar.is_some() || (sequencer && timeline.is_some());
+        let tempo_changed = follows_tempo && tempo != self.last_tempo;
+        let tempo_resync = tempo_changed || self.tempo_resync;
+        let time_change = if tempo_resync {
+            TimeChangeMode::Crossfade
+        } else {
+            self.remote.value(&self.params.delay_a.time_change)
+        };
+        let delay_samples = if swing_ratio.is_some() {
+            (delay_samples * 2).min(self.buffer_len - 1)
+        } else {
+            delay_samples
+        };
+        let num_channels = self.delay_lines.len().min(MAX_CHANNELS);
+        for block_start in (0..num_samples).step_by(BLOCK_SIZE) {
+            let block_end = (block_start + BLOCK_SIZE).min(num_samples);
+            let block_len = block_end - block_start;
+
+            // A mono input is copied to all output channels so every delay line gets fed
+            if self.num_input_channels == 1 {
+                if let Some((first, rest)) = channels.split_first_mut() {
+                    for channel in rest {
+                        channel[block_start..block_end]
+                            .copy_from_slice(&first[block_start..block_end]);
+                    }
+                }
+            }
+
+            // Everything that doesn't depend on the delay line's state is computed for the whole
+            // block at once. Smoothing is optionally built into the parameters themselves.
+            let mut gain = [0.0; BLOCK_SIZE];
+            let mut output_gain = [0.0; BLOCK_SIZE];
+            let mut mix = [0.0; BLOCK_SIZE];
+            self.params
+                .input_gain
+                .smoothed
+                .next_block(&mut gain, block_len);
+            self.params
+                .output
+                .output_gain
+                .smoothed
+                .next_block(&mut output_gain, block_len);
+            self.params
+                .output
+                .mix
+                .smoothed
+                .next_block(&mut mix, block_len);
+            // The input after the gain stage, which the modes then replace with their dry signal,
+            // and the wet signal after the mix and ducking gains
+            let mut dry = [[0.0; BLOCK_SIZE]; MAX_CHANNELS];
+            let mut wet = [[0.0; BLOCK_SIZE]; MAX_CHANNELS];
+            // The stutter repeats, which replace the output by `stutter_gain`
+            let mut stutter = [[0.0; BLOCK_SIZE]; MAX_CHANNELS];
+            let mut stutter_gain = [0.0; BLOCK_SIZE];
+            for (dry, channel) in dry.iter_mut().zip(channels.iter()) {
+                block::multiply(
+                    &mut dry[..block_len],
+                    &channel[block_start..block_end],
+                    &gain[..block_len],
+                );
+            }
+
+            // The input's envelope scaled by the envelope amount, measured in decibels so it
+            // also responds to quiet passages
+            let mut env_amount = [0.0; BLOCK_SIZE];
+            self.params
+                .modulation
+                .env_amount
+                .smoothed
+                .next_block(&mut env_amount, block_len);
+            let mut env_level = [0.0; BLOCK_SIZE];
+            let mut envelope = [0.0; BLOCK_SIZE];
+            for i in 0..block_len {
+                let peak = dry[..num_channels]
+                    .iter()
+                    .fold(0.0f32, |peak, dry| peak.max(dry[i].abs()));
+                let level = self
+                    .input_envelope
+                    .process(peak, env_attack, env_release, 0);
+                let level = (util::gain_to_db(level) / ENVELOPE_RANGE_DB + 1.0).clamp(0.0, 1.0);
+                env_level[i] = level;
+                envelope[i] = level * env_amount[i];
+            }
+
+            let mut dry_gain = [0.0; BLOCK_SIZE];
+            let mut wet_gain = [0.0; BLOCK_SIZE];
+            let mut bypass = [0.0; BLOCK_SIZE];
+            for i in 0..block_len {
+                let mix = match env_target {
+                    EnvelopeTarget::Mix => (mix[i] + envelope[i]).clamp(0.0, 1.0),
+                    _ => mix[i],
+                };
+                let (dry, wet) = mix_law.gains(mix);
+                let dry = if kill_dry { 0.0 } else { dry };
+                // Only the dry signal is heard while recording a loop
+                let wet = if looper == LooperState::Record {
+                    0.0
+                } else {
+                    wet
+                };
+                let target_auto_gain = if auto_gain {
+                    auto_gain_compensation(dry, wet, auto_gain_feedback)
+                } else {
+                    1.0
+                };
+                self.auto_gain += (target_auto_gain - self.auto_gain) * auto_gain_coefficient;
+                // The output gain is applied together with the mix gains
+                dry_gain[i] = dry * output_gain[i] * self.auto_gain;
+                wet_gain[i] = wet * output_gain[i] * self.auto_gain;
+
+                self.bypass_amount = if self.bypass_amount < bypass_target {
+                    (self.bypass_amount + bypass_step).min(bypass_target)
+                } else {
+                    (self.bypass_amount - bypass_step).max(bypass_target)
+                };
+                bypass[i] = self.bypass_amount;
+            }
+
+            // Only the delay lines themselves need to be processed sample by sample
+            let mut non_finite = false;
+            for i in 0..block_len {
+                let sample_idx = block_start + i;
+                // The modulation LFO pushes the read positions back by up to its depth, and it's
+                // also a source for the modulation matrix
+                let lfo_depth =
+                    self.params.modulation.lfo_depth.smoothed.next() / 1000.0 * self.sample_rate;
+                let lfo_rate = self.params.modulation.lfo_rate.smoothed.next();
+                let lfo_increment = lfo_synced_rate.unwrap_or(lfo_rate) / self.sample_rate;
+                let lfo = self.lfo.next(lfo_shape, lfo_increment, lfo_slew);
+                let lfo_offset = (lfo + 1.0) / 2.0 * lfo_depth;
+                let slots: [(ModSource, ModDestination, f32); NUM_MOD_SLOTS] =
+                    std::array::from_fn(|slot| {
+                        let (source, destination) = mod_routes[slot];
+                        let depth = self.params.modulation.mod_slots[slot].depth.smoothed.next();
+                        (source, destination, depth)
+                    });
+                let modulation = Modulation::sum(
+                    &slots,
+                    Sources {
+                        lfo,
+                        envelope: env_level[i],
+                    },
+                );
+
+                // Recording overwrites the loop, and while overdubbing the feedback amount sets
+                // how much of the loop is kept. The doubler never feeds back.
+                let feedback = self.params.feedback_path.feedback.smoothed.next() as Sample;
+                let feedback = if looper == LooperState::Record || mode == DelayMode::Doubler {
+                    0.0
+                } else {
+                    let envelope = match env_target {
+                        EnvelopeTarget::Feedback => envelope[i],
+                        _ => 0.0,
+                    };
+                    let feedback = (feedback + (envelope + modulation.feedback) as Sample)
+                        .clamp(0.0, max_feedback);
+                    // Infinite overrides the feedback amount and its modulation, unity gain
+                    // holds the repeats without them creeping up or dying out
+                    self.infinite_amount +=
+                        (infinite_target - self.infinite_amount) * infinite_coefficient;
+                    feedback + (1.0 - feedback) * self.infinite_amount as Sample
+                };
+                // With swing the feedback path covers two repeats at a time
+                let repeat_feedback = feedback;
+                let feedback = if swing_ratio.is_some() {
+                    feedback * feedback
+                } else {
+                    feedback
+                };
+                let feedback_ceiling =
+                    self.params.feedback_path.feedback_ceiling.smoothed.next() as Sample;
+                let ping_pong_width = self.params.delay_a.ping_pong_width.smoothed.next() as Sample;
+                let low_cut = self.low_cut_coefficient.get(
+                    (
+                        self.params.feedback_path.low_cut.smoothed.next(),
+                        self.sample_rate,
+                    ),
+                    |(cutoff, sample_rate)| OnePole::coefficient(cutoff, sample_rate),
+                );
+                let high_cut = self.params.feedback_path.high_cut.smoothed.next();
+                let high_cut = if modulation.cutoff == 0.0 {
+                    high_cut
+                } else {
+                    high_cut * 2.0f32.powf(modulation.cutoff * CUTOFF_OCTAVES)
+                }
+                .clamp(MIN_CUTOFF_HZ, self.sample_rate * MAX_CUTOFF_RATIO);
+                let high_cut = self
+                    .high_cut_coefficient
+                    .get((high_cut, self.sample_rate), |(cutoff, sample_rate)| {
+                        OnePole::coefficient(cutoff, sample_rate)
+                    });
+                let decay_tilt =
+                    util::db_to_gain(-self.params.feedback_path.decay_tilt.smoothed.next());
+                let crush_bits = self.params.feedback_path.crush_bits.smoothed.next();
+                let crush_rate = self.params.feedback_path.crush_rate.smoothed.next();
+                let bell_gain = self.params.feedback_path.bell_gain.smoothed.next();
+                let bell = self.bell_coefficients.get(
+                    (
+                        self.params
+                            .feedback_path
+                            .bell_freq
+                            .smoothed
+                            .next()
+                            .min(self.sample_rate * MAX_CUTOFF_RATIO),
+                        bell_gain,
+                        self.params.feedback_path.bell_q.smoothed.next(),
+                        self.sample_rate,
+                    ),
+                    |(frequency, gain, q, sample_rate)| {
+                        BiquadCoefficients::peaking(frequency, gain, q, sample_rate)
+                    },
+                );
+                let drive = self.params.feedback_path.drive.smoothed.next();
+                let shimmer = self.params.feedback_path.shimmer.smoothed.next() as Sample;
+                let shift_freq = self.params.feedback_path.shift_freq.smoothed.next();
+                let b_feedback = self.params.engine_b.feedback.smoothed.next() as Sample;
+                let b_low_cut = self.b_low_cut_coefficient.get(
+                    (
+                        self.params.engine_b.low_cut.smoothed.next(),
+                        self.sample_rate,
+                    ),
+                    |(cutoff, sample_rate)| OnePole::coefficient(cutoff, sample_rate),
+                );
+                let b_high_cut = self.b_high_cut_coefficient.get(
+                    (
+                        self.params
+                            .engine_b
+                            .high_cut
+                            .smoothed
+                            .next()
+                            .min(self.sample_rate * MAX_CUTOFF_RATIO),
+                        self.sample_rate,
+                    ),
+                    |(cutoff, sample_rate)| OnePole::coefficient(cutoff, sample_rate),
+                );
+                let b_pan = self.params.engine_b.pan.smoothed.next();
+                let b_level = self.params.engine_b.level.smoothed.next();
+                self.engine_b_delay += (engine_b_target - self.engine_b_delay) * glide_coefficient;
+                let pre_delay =
+                    self.params.delay_a.pre_delay.smoothed.next() / 1000.0 * self.sample_rate;
+                let pre_delay = pre_delay_synced.unwrap_or(pre_delay);
+                let detune = self.params.modulation.detune.smoothed.next()
+                    + if mode == DelayMode::Doubler {
+                        HAAS_DETUNE_CENTS
+                    } else {
+                        0.0
+                    };
+                let rotation = self.params.delay_a.rotation.smoothed.next() as Sample;
+                // Wow and flutter also pushes the read positions further back by up to the depth
+                let wow_depth =
+                    self.params.modulation.wow_depth.smoothed.next() / 1000.0 * self.sample_rate;
+                let wow_offset = self.wow_flutter.next(
+                    self.params.modulation.wow_rate.smoothed.next(),
+                    self.sample_rate,
+                ) * wow_depth;
+                // The delay time modulation in octaves
+                let time_modulation = match env_target {
+                    EnvelopeTarget::Time => envelope[i],
+                    _ => 0.0,
+                } + modulation.time;
+                // Negative values pan the repeats towards the first channel
+                let autopan_rate = self.params.modulation.autopan_rate.smoothed.next();
+                let autopan_increment =
+                    autopan_synced_rate.unwrap_or(autopan_rate) / self.sample_rate;
+                let autopan = self.autopan.next(autopan_shape, autopan_increment, 1.0)
+                    * self.params.modulation.autopan_depth.smoothed.next();
+                let pan = (modulation.pan + autopan).clamp(-1.0, 1.0);
+
+                // The ducker turns the repeats down while the key signal is above the threshold
+                let key = match (duck_source, &sidechain) {
+                    (DuckSource::Sidechain, Some(sidechain)) => sidechain
+                        .iter()
+                        .fold(0.0f32, |key, channel| key.max(channel[sample_idx].abs())),
+                    _ => dry[..num_channels]
+                        .iter()
+                        .fold(0.0f32, |key, dry| key.max(dry[i].abs())),
+                };
+                let duck_envelope =
+                    self.duck_envelope
+                        .process(key, duck_attack, duck_release, duck_hold);
+                let duck_over = util::gain_to_db(duck_envelope) - duck_threshold;
+                let duck_gain = 1.0
+                    - (duck_over / DUCK_RANGE_DB).clamp(0.0, 1.0)
+                        * self.params.output.duck_amount.smoothed.next();
+                let wet_gain = wet_gain[i] * duck_gain;
+                let pos_beats = timeline
+                    .map(|(pos, beat_samples)| pos + sample_idx as f64 / beat_samples as f64);
+                let stutter_tap =
+                    stutter_settings.map(|settings| self.stutter.next(pos_beats, &settings));
+
+                // Every step of the sequence sets its own delay time, and the steps that are
+                // switched off stop sending the input into the delay
+                let step = pos_beats
+                    .filter(|_| sequencer)
+                    .map(|pos| self.sequence.step_at(pos, sequencer_rate, sequencer_length));
+                let delay_samples = match step.zip(timeline) {
+                    Some((step, (_, beat_samples))) => {
+                        ((step.note.beats() * beat_samples) as usize).clamp(1, self.buffer_len - 1)
+                    }
+                    None => delay_samples,
+                };
+                let send_target = if step.is_none_or(|step| step.enabled) {
+                    1.0
+                } else {
+                    0.0
+                };
+                self.sequencer_send = if self.sequencer_send < send_target {
+                    (self.sequencer_send + sequencer_fade_step).min(send_target)
+                } else {
+                    (self.sequencer_send - sequencer_fade_step).max(send_target)
+                };
+                let send = self.sequencer_send;
+                stutter_gain[i] = stutter_tap.map_or(0.0, |tap| tap.gain * (1.0 - bypass[i]));
+
+                for channel in 0..num_channels {
+                    let line = &mut self.delay_lines[channel];
+                    let len = line.buffer.len();
+                    let spread_samples = (delay_samples as f32 * time_spread * channel as f32
+                        / num_channels as f32) as usize;
+                    // The oversampled feedback path can't write closer to the read position than
+                    // its latency
+                    let delay_samples = if mode == DelayMode::Doubler && channel % 2 == 0 {
+                        (delay_samples as f32 * HAAS_SHORT_RATIO) as usize
+                    } else {
+                        delay_samples
+                    };
+                    let delay_samples = if channel % 2 == offset_channel {
+                        delay_samples + spread_samples + offset_samples
+                    } else {
+                        delay_samples + spread_samples
+                    }
+                    .clamp(latency, len - 1);
+                    // Whenever the delay length changes the read position is moved to match it.
+                    // To avoid clicks the old read position is either faded out while the new one
+                    // fades in, or the read position glides to the new position. Changes that
+                    // happen during a crossfade are picked up once that fade has finished.
+                    match time_change {
+                        TimeChangeMode::Crossfade
+                            if line.prev != delay_samples && line.fade_remaining == 0 =>
+                        {
+                            line.fade_head = line.iterrepeats;
+                            line.fade_remaining = self.crossfade_len;
+                            line.glide_offset = 0.0;
+                            line.iterrepeats = line.position(delay_samples);
+                            line.prev = delay_samples;
+                        }
+                        TimeChangeMode::Glide if line.prev != delay_samples => {
+                            // The very first delay time is jumped to directly
+                            if line.prev != 0 {
+                                line.glide_offset += delay_samples as f32 - line.prev as f32;
+                            }
+                            line.iterrepeats = line.position(delay_samples);
+                            line.prev = delay_samples;
+                        }
+                        _ => (),
+                    }
+
+                    // The modulation scales the delay time, but it can't read closer to the write
+                    // position than the feedback path's latency
+                    let time_offset = if time_modulation != 0.0 {
+                        (delay_samples as f32 * (2.0f32.powf(time_modulation) - 1.0)).clamp(
+                            (latency + 1) as f32 - delay_samples as f32,
+                            (len - 1 - delay_samples) as f32,
+                        )
+                    } else {
+                        0.0
+                    };
+                    let read_offset =
+                        line.glide_offset - wow_offset - lfo_offset - time_offset - tuned_fraction;
+                    let mut prevsample = if read_offset != 0.0 {
+                        line.read_fractional(line.iterrepeats as f32 + read_offset, interpolation)
+                    } else {
+                        line.buffer[line.iterrepeats]
+                    };
+                    if line.glide_offset != 0.0 {
+                        line.glide_offset -= line.glide_offset * glide_coefficient;
+                        if line.glide_offset.abs() < 1e-3 {
+                            line.glide_offset = 0.0;
+                        }
+                    }
+                    if line.fade_remaining > 0 {
+                        let t = line.fade_remaining as Sample / self.crossfade_len as Sample;
+                        prevsample += (line.buffer[line.fade_head] - prevsample) * t;
+                    }
+                    // The pre-delay reads the wet signal further back than the feedback path, so
+                    // the first repeat starts later without changing the spacing of the others
+                    let pre_delay = pre_delay
+                        .min((len - 1) as f32 - delay_samples as f32 + read_offset.min(0.0))
+                        .max(0.0);
+                    let wet_head = if pre_delay > 0.0 {
+                        let mut tap = line.read_fractional(
+                            line.iterrepeats as f32 + read_offset - pre_delay,
+                            interpolation,
+                        );
+                        if line.fade_remaining > 0 {
+                            let t = line.fade_remaining as Sample / self.crossfade_len as Sample;
+                            let fade_tap = line
+                                .read_fractional(line.fade_head as f32 - pre_delay, interpolation);
+                            tap += (fade_tap - tap) * t;
+                        }
+                        tap
+                    } else {
+                        prevsample
+                    };
+                    let prevsample2 =
+                        line.read((second_tap_samples + pre_delay as usize).min(len - 1));
+                    // Only the wet signal is detuned, the feedback path gets the regular tap so
+                    // the detuning doesn't build up over the repeats
+                    let wet_tap = if detune != 0.0 {
+                        let cents = if channel % 2 == 0 { detune } else { -detune };
+                        line.read_detuned(
+                            line.iterrepeats as f32 + read_offset - pre_delay,
+                            2.0f32.powf(cents / 1200.0),
+                            detune_window,
+                            interpolation,
+                        )
+                    } else {
+                        wet_head
+                    };
+                    let primary = match swing_ratio {
+                        Some(ratio) => {
+                            line.read((delay_samples as f32 * ratio + pre_delay) as usize)
+                                + wet_tap * repeat_feedback
+                        }
+                        None => wet_tap,
+                    };
+                    if let Some(tap) = stutter_tap.filter(|tap| tap.gain > 0.0) {
+                        // The feedback path is written `latency` samples behind the write
+                        // position, and the output is delayed by `compensation` samples
+                        let distance = tap.distance + (latency + compensation) as f32;
+                        let pos = line.iterdelay as f32 - distance.min((len - 1) as f32);
+                        stutter[channel][i] =
+                            to_f32(line.read_fractional(pos, interpolation)) * output_gain[i];
+                    }
+                    let input = dry[channel][i];
+                    // Every mode produces a wet signal. Some modes replace the input entirely, in
+                    // which case the dry signal is silenced before mixing.
+                    let taps = Taps {
+                        dry: input,
+                        primary: to_f32(primary),
+                        second: to_f32(prevsample2),
+                    };
+                    let output = modes::process(
+                        mode,
+                        line,
+                        taps,
+                        shuffle_ratio,
+                        jitter,
+                        oversampling,
+                        &tap_pattern,
+                    );
+                    // While bypassed no new input enters the delay line, and the existing repeats
+                    // ring out on top of the unprocessed input
+                    line.frame_input = (input * (1.0 - bypass[i]) * send) as Sample;
+                    line.frame_tap = prevsample;
+                    // Ring Feed's output is delayed by the oversampling, so everything else is
+                    // delayed to match
+                    let wet_sample = if mode == DelayMode::RingFeed {
+                        line.wet_compensation.process(output.wet, lookahead)
+                    } else {
+                        line.wet_compensation.process(output.wet, compensation)
+                    };
+                    dry[channel][i] = line.dry_compensation.process(output.dry, compensation);
+                    wet[channel][i] = wet_sample * wet_gain * pan_gain(channel, num_channels, pan);
+                    if engine_b {
+                        let line_b = &mut self.engine_b[channel];
+                        line_b.frame_tap = line_b.read_fractional(
+                            line_b.iterdelay as f32 - self.engine_b_delay,
+                            interpolation,
+                        );
+                        let wet_b = line_b
+                            .wet_compensation
+                            .process(to_f32(line_b.frame_tap), compensation);
+                        wet[channel][i] +=
+                            wet_b * b_level * wet_gain * pan_gain(channel, num_channels, b_pan);
+                    }
+                    channels[channel][sample_idx] = line
+                        .input_compensation
+                        .process(channels[channel][sample_idx], compensation);
+                    self.input_meter.add(input);
+                    self.wet_meter.add(wet[channel][i]);
+                }
+                let wet_sum = wet[..num_channels].iter().map(|wet| wet[i]).sum::<f32>();
+                self.spectrum_capture
+                    .add(wet_sum / num_channels as f32, self.sample_rate);
+
+                // The primary tap is fed back into the delay line. In ping-pong mode the input is
+                // panned towards the first channel and every channel is fed by its neighbour's
+                // tap.
+                let width = if mode == DelayMode::PingPong {
+                    ping_pong_width
+                } else {
+                    0.0
+                };
+                self.freeze_amount += (freeze_target - self.freeze_amount) * freeze_coefficient;
+                let mono_input = self.delay_lines[..num_channels]
+                    .iter()
+                    .map(|line| line.frame_input)
+                    .sum::<Sample>()
+                    / num_channels as Sample;
+                for channel in 0..num_channels {
+                    let partner_tap =
+                        self.delay_lines[ping_pong_partner(channel, num_channels)].frame_tap;
+                    let previous_tap =
+                        self.delay_lines[(channel + num_channels - 1) % num_channels].frame_tap;
+                    let line = &mut self.delay_lines[channel];
+                    let input = if channel == 0 {
+                        line.frame_input + (mono_input - line.frame_input) * width
+                    } else {
+                        line.frame_input * (1.0 - width)
+                    };
+                    let tap = line.frame_tap + (partner_tap - line.frame_tap) * width;
+                    let tap = tap + (previous_tap - tap) * rotation;
+                    let tap = match mode {
+                        DelayMode::Karplus => line.loop_filter.process(tap),
+                        DelayMode::Comb => line.comb_damping.lowpass(tap, comb_damping) * comb_sign,
+                        _ => tap,
+                    };
+                    let shifted_tap = line.shimmer.process(tap, shimmer_ratio, interpolation);
+                    let tap = tap + (shifted_tap - tap) * shimmer;
+                    let tap =
+                        line.shifter
+                            .process(tap, feedback_shift, shift_freq, self.sample_rate);
+
+                    // Past unity gain the repeats would grow without bound, so the feedback path
+                    // is soft limited to the ceiling. With the engines pinging the repeats
+                    // alternate between them, so delay B's repeats are fed back here instead.
+                    let repeats = tap * feedback;
+                    let mut feedback_sample = if ping {
+                        input + self.engine_b[channel].frame_tap * b_feedback
+                    } else {
+                        input + repeats
+                    };
+                    feedback_sample = line.high_cut.lowpass(feedback_sample, high_cut);
+                    feedback_sample = line.low_cut.highpass(feedback_sample, low_cut);
+                    feedback_sample =
+                        line.tilt
+                            .high_shelf(feedback_sample, decay_tilt_coefficient, decay_tilt);
+                    // At 0 dB the bell doesn't change anything
+                    if bell_gain == 0.0 {
+                        line.bell = Biquad::default();
+                    } else {
+                        feedback_sample = line.bell.process(feedback_sample, &bell);
+                    }
+                    // The waveshaping is oversampled, and to keep the delay time exact its output
+                    // is written as far behind the write position as the oversampling delayed it
+                    feedback_sample =
+                        line.saturation
+                            .process([feedback_sample], oversampling, |[sample]| {
+                                soft_limit(saturation.process(sample, drive), feedback_ceiling)
+                            });
+                    feedback_sample = line.character.process(feedback_sample, &character);
+                    feedback_sample = line.crusher.process(
+                        feedback_sample,
+                        crush_bits,
+                        crush_rate,
+                        self.sample_rate,
+                    );
+                    feedback_sample = line.dc_blocker.process(feedback_sample, dc_coefficient);
+                    // While frozen the primary tap is written back as is, bypassing the rest of
+                    // the feedback path, so the last delay period loops forever
+                    let frame_tap = line.tap_compensation.process(line.frame_tap, latency);
+                    feedback_sample += (frame_tap - feedback_sample) * self.freeze_amount as Sample;
+                    line.write_behind(latency, feedback_sample);
+                    non_finite |= !feedback_sample.is_finite();
+                    self.waveform_capture.record(
+                        line.position(latency),
+                        line.buffer.len(),
+                        to_f32(feedback_sample),
+                    );
+                    line.tick();
+
+                    if engine_b {
+                        let line_b = &mut self.engine_b[channel];
+                        let mut sample = match engine_routing {
+                            EngineRouting::Serial => line.frame_tap + line_b.frame_tap * b_feedback,
+                            EngineRouting::Parallel => {
+                                line.frame_input + line_b.frame_tap * b_feedback
+                            }
+                            EngineRouting::Ping => repeats,
+                        };
+                        sample = line_b.high_cut.lowpass(sample, b_high_cut);
+                        sample = line_b.low_cut.highpass(sample, b_low_cut);
+                        sample = soft_limit(sample, 1.0);
+                        sample = line_b.dc_blocker.process(sample, dc_coefficient);
+                        line_b.write_behind(0, sample);
+                        line_b.tick();
+                        non_finite |= !sample.is_finite();
+                    }
+                }
+            }
+
+            // The diffusion smears the wet signal after it has been read from the delay lines
+            let mut diffusion = [0.0; BLOCK_SIZE];
+            self.params
+                .output
+                .diffusion
+                .smoothed
+                .next_block(&mut diffusion, block_len);
+            for (wet, diffuser) in wet.iter_mut().zip(&mut self.diffusers) {
+                for (sample, &amount) in wet[..block_len].iter_mut().zip(&diffusion) {
+                    *sample = diffuser.process(*sample, amount);
+                }
+            }
+
+            if reverb_enabled {
+                if !self.reverb_active {
+                    for reverb in &mut self.reverbs {
+                        reverb.reset();
+                    }
+                }
+
+                let mut reverb_size = [0.0; BLOCK_SIZE];
+                let mut reverb_mix = [0.0; BLOCK_SIZE];
+                self.params
+                    .output
+                    .reverb_size
+                    .smoothed
+                    .next_block(&mut reverb_size, block_len);
+                self.params
+                    .output
+                    .reverb_mix
+                    .smoothed
+                    .next_block(&mut reverb_mix, block_len);
+                for (wet, reverb) in wet.iter_mut().zip(&mut self.reverbs) {
+                    for i in 0..block_len {
+                        let reverb = reverb.process(wet[i], reverb_size[i], reverb_decay);
+                        wet[i] += (reverb - wet[i]) * reverb_mix[i];
+                    }
+                }
+            }
+            self.reverb_active = reverb_enabled;
+
+            // The width is applied to every pair of channels, like the ping-pong mode's routing
+            let mut width = [0.0; BLOCK_SIZE];
+            self.params
+                .output
+                .width
+                .smoothed
+                .next_block(&mut width, block_len);
+            for (pair, stereo_width) in self
+                .stereo_width
+                .iter_mut()
+                .take(num_channels / 2)
+                .enumerate()
+            {
+                if let [left, right] = &mut wet[pair * 2..pair * 2 + 2] {
+                    for i in 0..block_len {
+                        (left[i], right[i]) =
+                            stereo_width.process(left[i], right[i], width[i], mono_below);
+                    }
+                }
+            }
+
+            // The clear trigger fades out everything that comes from the delay lines, and then
+            // empties them along with the diffusion and reverb tails
+            if self.clear_fade_remaining > 0 {
+                for i in 0..block_len {
+                    let gain =
+                        self.clear_fade_remaining.saturating_sub(i) as f32 / clear_fade_len as f32;
+                    for channel in 0..num_channels {
+                        wet[channel][i] *= gain;
+                        stutter[channel][i] *= gain;
+                    }
+                }
+
+                self.clear_fade_remaining = self.clear_fade_remaining.saturating_sub(block_len);
+                if self.clear_fade_remaining == 0 {
+                    for line in self.delay_lines.iter_mut().chain(&mut self.engine_b) {
+                        line.reset();
+                    }
+                    for diffuser in &mut self.diffusers {
+                        diffuser.reset();
+                    }
+                    for reverb in &mut self.reverbs {
+                        reverb.reset();
+                    }
+                }
+            }
+
+            // A NaN that made it into the feedback path would otherwise stay in the buffer forever
+            if non_finite {
+                let num_scrubbed: usize = self
+                    .delay_lines
+                    .iter_mut()
+                    .chain(&mut self.engine_b)
+                    .map(DelayLine::scrub)
+                    .sum();
+                nih_debug_assert_failure!(
+                    "Replaced {num_scrubbed} non-finite samples in the delay buffers"
+                );
+            }
+
+            // The scope shows what the dry and the wet signal each add to the output
+            for i in 0..block_len {
+                let dry_sum = dry[..num_channels].iter().map(|dry| dry[i]).sum::<f32>();
+                let wet_sum = wet[..num_channels].iter().map(|wet| wet[i]).sum::<f32>();
+                let scope_timeline = timeline.map(|(pos, beat_samples)| {
+                    let pos_beats = pos + (block_start + i) as f64 / beat_samples as f64;
+                    (pos_beats, playhead.bar_beats.unwrap_or(4.0))
+                });
+                self.scope_capture.record(
+                    dry_sum * dry_gain[i] / num_channels as f32,
+                    wet_sum / num_channels as f32,
+                    scope_timeline,
+                    self.sample_rate,
+                );
+            }
+
+            // The channels still contain the unprocessed input, which is needed for bypassing
+            for (((channel, dry), wet), stutter) in
+                channels.iter_mut().zip(&dry).zip(&wet).zip(&stutter)
+            {
+                let output = &mut channel[block_start..block_end];
+                block::mix(
+                    output,
+                    &dry[..block_len],
+                    &dry_gain[..block_len],
+                    &wet[..block_len],
+                    &bypass[..block_len],
+                );
+                for ((output, stutter), gain) in output.iter_mut().zip(stutter).zip(&stutter_gain) {
+                    *output += (stutter - *output) * gain;
+                }
+                for &sample in output.iter() {
+                    self.output_meter.add(sample);
+                }
+            }
+            // A mono output shows up as a mono signal on the goniometer
+            let left = &channels[0][block_start..block_end];
+            let right = &channels[num_channels.min(2) - 1][block_start..block_end];
+            for (&left, &right) in left.iter().zip(right) {
+                self.goniometer_capture.add(left, right);
+            }
+            for i in block_start..block_end {
+                let mut frame = [0.0; MAX_CHANNELS];
+                for (sample, channel) in frame.iter_mut().zip(channels.iter()) {
+                    *sample = channel[i];
+                }
+                self.loudness_meter.add_frame(&frame[..num_channels]);
+            }
         }
+
+        // Delay time changes that happen during a crossfade are picked up once it has finished, so
+        // the tempo change is only done once no line is crossfading anymore
+        self.last_tempo = tempo;
+        self.tempo_resync = tempo_changed
+            || (tempo_resync && self.delay_lines.iter().any(|line| line.fade_remaining > 0));
+
+        self.input_meter
+            .finish_block(num_samples, self.sample_rate, &self.meters.input);
+        self.wet_meter
+            .finish_block(num_samples, self.sample_rate, &self.meters.wet);
+        self.output_meter
+            .finish_block(num_samples, self.sample_rate, &self.meters.output);
+        self.goniometer_capture
+            .finish_block(num_samples, self.sample_rate);
+        if self.meters.reset_integrated.swap(false, Ordering::Relaxed) {
+            self.loudness_meter.reset_integrated();
+        }
+        self.meters
+            .short_term_lufs
+            .store(self.loudness_meter.short_term(), Ordering::Relaxed);
+        self.meters
+            .integrated_lufs
+            .store(self.loudness_meter.integrated(), Ordering::Relaxed);
+
+        // The lock is only contended while the host saves the state, in which case capturing is
+        // retried on the next block
+        if self.remote.value(&self.params.performance.freeze)
+            && self.remote.value(&self.params.performance.save_frozen)
+        {
+            if !self.frozen_captured && self.freeze_amount > FROZEN_CAPTURE_THRESHOLD {
+                if let Ok(mut frozen) = self.params.frozen_buffer.try_lock() {
+                    frozen.capture(
+                        &self.delay_lines,
+                        self.delay_samples(tempo),
+                        self.sample_rate,
+                    );
+                    self.frozen_captured = true;
+                }
+            }
+        } else if self.frozen_captured {
+            if let Ok(mut frozen) = self.params.frozen_buffer.try_lock() {
+                frozen.clear();
+                self.frozen_captured = false;
+            }
+        }
+
+        if self.params.editor_state.is_open() {
+            if let Some(line) = self.delay_lines.first() {
+                let len = line.buffer.len() as f32;
+                self.waveform_capture
+                    .publish(line.iterdelay as f32 / len, line.iterrepeats as f32 / len);
+            }
+            self.scope_capture.publish();
+        }
+
+        self.sample_clock += num_samples as u64;
+
+        self.tail_status()
     }
 }
 
-impl Plugin for Myplug {
-    const NAME: &'static str = "Myplug2.1";
+/// The gain that brings the output of a mix with `dry` and `wet` gains back to the level of the
+/// input. Every repeat is `feedback` times as loud as the previous one, so the wet signal carries
+/// `1 / (1 - feedback^2)` times the input's energy, and the dry and the wet signals are assumed to
+/// be uncorrelated.
+fn auto_gain_compensation(dry: f32, wet: f32, feedback: f32) -> f32 {
+    let energy = dry * dry + wet * wet / (1.0 - feedback * feedback);
+    if energy > 0.0 {
+        energy.sqrt().recip().clamp(AUTO_GAIN_MIN, AUTO_GAIN_MAX)
+    } else {
+        1.0
+    }
+}
+
+/// The gain of a channel when panning by `pan` in `[-1, 1]`. Negative values pan towards the
+/// first channel, and channels past the second one aren't panned.
+fn pan_gain(channel: usize, num_channels: usize, pan: f32) -> f32 {
+    match channel {
+        0 if num_channels > 1 => (1.0 - pan).min(1.0),
+        1 => (1.0 + pan).min(1.0),
+        _ => 1.0,
+    }
+}
+
+/// The length of a delay buffer that can hold at least `max_delay_seconds` of audio at a sample
+/// rate. This is rounded up to a power of two so the delay lines can wrap positions with a mask.
+fn buffer_len(sample_rate: f32, max_delay_seconds: f32) -> usize {
+    ((sample_rate * max_delay_seconds).ceil() as usize + 1).next_power_of_two()
+}
+
+/// The state of the host's transport at the start of a block.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Playhead {
+    pub tempo: Option<f64>,
+    /// The length of a bar in quarter notes, from the time signature.
+    pub bar_beats: Option<f64>,
+    /// The position in quarter notes.
+    pub pos_beats: Option<f64>,
+    pub playing: bool,
+}
+
+impl Playhead {
+    /// The playhead `num_samples` samples later, assuming the tempo stays the same.
+    pub fn advance(self, num_samples: usize, sample_rate: f32) -> Self {
+        let pos_beats = match (self.pos_beats, self.tempo) {
+            (Some(pos), Some(tempo)) if self.playing => {
+                Some(pos + num_samples as f64 / sample_rate as f64 * tempo / 60.0)
+            }
+            (pos, _) => pos,
+        };
+
+        Self { pos_beats, ..self }
+    }
+}
+
+/// Work that's done on a background thread so the audio thread doesn't need to allocate.
+#[derive(Debug)]
+pub enum BufferTask {
+    /// Allocate new delay buffers after the maximum delay time changed. The result is picked up
+    /// by the audio thread at the start of the next block.
+    Allocate { num_channels: usize, len: usize },
+    /// Drop delay buffers that have been swapped out.
+    Deallocate(Vec<Vec<Sample>>),
+    /// Run the editor's spectrum analyzer on the last block of the wet signal.
+    AnalyzeSpectrum,
+}
+
+impl<I: PluginIds> Plugin for Myplug<I> {
+    const NAME: &'static str = I::NAME;
     const VENDOR: &'static str = "SnovaxZ";
     const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
     const EMAIL: &'static str = "snovaxz@proton.me";
@@ -91,167 +1795,457 @@ impl Plugin for Myplug {
 
     // The first audio IO layout is used as the default. The other layouts may be selected either
     // explicitly or automatically by the host or the user depending on the plugin API/backend.
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: NonZeroU32::new(2),
-        main_output_channels: NonZeroU32::new(2),
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+
+            // The sidechain input can be used to drive the ducker
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[],
+
+            // Individual ports and the layout as a whole can be named here. By default these names
+            // are generated as needed. This layout will be called 'Stereo', while a layout with
+            // only one input and output channel would be called 'Mono'.
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+
+            aux_input_ports: &[new_nonzero_u32(1)],
+            aux_output_ports: &[],
+
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+        },
+        // Lets mono tracks feed the stereo modes like ping-pong
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(2),
+
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[],
 
-        aux_input_ports: &[],
-        aux_output_ports: &[],
+            names: PortNames {
+                layout: Some("Mono to Stereo"),
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(6),
+            main_output_channels: NonZeroU32::new(6),
 
-        // Individual ports and the layout as a whole can be named here. By default these names
-        // are generated as needed. This layout will be called 'Stereo', while a layout with
-        // only one input and output channel would be called 'Mono'.
-        names: PortNames::const_default(),
-    }];
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[],
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::None;
-    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+            names: PortNames {
+                layout: Some("5.1"),
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(8),
+            main_output_channels: NonZeroU32::new(8),
+
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[],
+
+            names: PortNames {
+                layout: Some("7.1"),
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+        },
+    ];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
 
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
-    // If the plugin can send or receive SysEx messages, it can define a type to wrap around those
-    // messages here. The type implements the `SysExMessage` trait, which allows conversion to and
-    // from plain byte buffers.
-    type SysExMessage = ();
-    // More advanced plugins can use this to run expensive background tasks. See the field's
-    // documentation for more information. `()` means that the plugin does not have any background
-    // tasks.
-    type BackgroundTask = ();
+    // Parameter snapshots can be requested and sent over SysEx
+    type SysExMessage = RemoteSysEx;
+    // The delay buffers are reallocated in the background when the maximum delay time changes
+    type BackgroundTask = BufferTask;
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let pending_buffers = self.pending_buffers.clone();
+        let spectrum_analyzer = self.spectrum_analyzer.clone();
+        Box::new(move |task| match task {
+            BufferTask::Allocate { num_channels, len } => {
+                *pending_buffers.lock().unwrap() = Some(vec![vec![0.0; len]; num_channels]);
+            }
+            BufferTask::Deallocate(buffers) => drop(buffers),
+            BufferTask::AnalyzeSpectrum => spectrum_analyzer.lock().unwrap().analyze(),
+        })
+    }
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
+    fn filter_state(state: &mut PluginState) {
+        params::migrate_state(state);
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            self.params.clone(),
+            Displays {
+                waveform: self.waveform_output.clone(),
+                scope: self.scope_output.clone(),
+                goniometer: self.goniometer_output.clone(),
+                spectrum: self.spectrum.clone(),
+            },
+            self.meters.clone(),
+            self.midi_ccs.clone(),
+            self.sysex_snapshot.clone(),
+        )
+    }
+
     fn initialize(
         &mut self,
-        _audio_io_layout: &AudioIOLayout,
-        _buffer_config: &BufferConfig,
-        _context: &mut impl InitContext<Self>,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
+        // Values sent over MIDI belonged to the previous session, or to the state that was just
+        // loaded in its place
+        self.remote.clear();
+        *self.sysex_snapshot.lock().unwrap() = None;
+        // The CC mappings are restored along with the rest of the state
+        self.midi_ccs.map(
+            &self.params.cc_mappings.read().unwrap(),
+            self.params.as_ref(),
+        );
+
+        self.sample_rate = buffer_config.sample_rate;
+        self.latency_samples = self.latency();
+        context.set_latency_samples(self.latency_samples);
+        self.buffer_len = buffer_len(
+            buffer_config.sample_rate,
+            self.remote.value(&self.params.delay_a.max_delay),
+        );
+        // Any buffers still being allocated in the background were sized for the old layout
+        self.requested_buffer_len = None;
+        *self.pending_buffers.lock().unwrap() = None;
+        self.crossfade_len = (buffer_config.sample_rate * CROSSFADE_SECONDS) as usize;
+
+        self.num_input_channels = audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or_default() as usize;
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or_default() as usize;
+        self.delay_lines = (0..num_channels)
+            .map(|_| {
+                DelayLine::new(
+                    self.buffer_len,
+                    (self.sample_rate * SHIMMER_WINDOW_SECONDS) as usize,
+                )
+            })
+            .collect();
+        self.diffusers = (0..num_channels)
+            .map(|channel| Diffuser::new(channel, self.sample_rate))
+            .collect();
+        self.reverbs = (0..num_channels)
+            .map(|channel| Reverb::new(channel, self.sample_rate))
+            .collect();
+        self.loudness_meter = LoudnessMeter::new(num_channels, self.sample_rate);
+        let engine_b_len = buffer_len(self.sample_rate, ENGINE_B_MAX_SECONDS);
+        self.engine_b = (0..num_channels)
+            .map(|_| {
+                DelayLine::new(
+                    engine_b_len,
+                    (self.sample_rate * SHIMMER_WINDOW_SECONDS) as usize,
+                )
+            })
+            .collect();
+        self.params.frozen_buffer.lock().unwrap().allocate(
+            num_channels,
+            (self.sample_rate * FROZEN_STATE_MAX_SECONDS).ceil() as usize,
+        );
+
         true
     }
 
     fn reset(&mut self) {
-        // Reset buffers and envelopes here. This can be called from the audio thread and may not
-        // allocate. You can remove this function if you do not need it.
+        // This can be called from the audio thread, so everything is cleared in place. The
+        // allocation assertions only cover `process()`, hence the explicit check here.
+        for line in &mut self.delay_lines {
+            let (ptr, len) = (line.buffer.as_ptr(), line.buffer.len());
+            line.reset();
+            nih_debug_assert!(
+                line.buffer.as_ptr() == ptr && line.buffer.len() == len,
+                "Resetting a delay line should not reallocate its buffer"
+            );
+        }
+        for line in &mut self.engine_b {
+            line.reset();
+        }
+        for diffuser in &mut self.diffusers {
+            diffuser.reset();
+        }
+        for reverb in &mut self.reverbs {
+            reverb.reset();
+        }
+        self.engine_b_delay = 0.0;
+        self.engine_b_active = false;
+
+        for param in [
+            &self.params.input_gain,
+            &self.params.output.output_gain,
+            &self.params.output.mix,
+            &self.params.feedback_path.feedback,
+            &self.params.feedback_path.feedback_ceiling,
+            &self.params.delay_a.ping_pong_width,
+            &self.params.feedback_path.low_cut,
+            &self.params.feedback_path.high_cut,
+            &self.params.feedback_path.decay_tilt,
+            &self.params.feedback_path.crush_bits,
+            &self.params.feedback_path.crush_rate,
+            &self.params.feedback_path.bell_freq,
+            &self.params.feedback_path.bell_gain,
+            &self.params.feedback_path.bell_q,
+            &self.params.feedback_path.drive,
+            &self.params.output.duck_amount,
+            &self.params.modulation.env_amount,
+            &self.params.feedback_path.shimmer,
+            &self.params.feedback_path.shift_freq,
+            &self.params.modulation.detune,
+            &self.params.delay_a.pre_delay,
+            &self.params.engine_b.feedback,
+            &self.params.engine_b.low_cut,
+            &self.params.engine_b.high_cut,
+            &self.params.engine_b.pan,
+            &self.params.engine_b.level,
+            &self.params.output.width,
+            &self.params.output.diffusion,
+            &self.params.output.reverb_size,
+            &self.params.output.reverb_mix,
+            &self.params.modulation.wow_depth,
+            &self.params.modulation.wow_rate,
+            &self.params.modulation.lfo_rate,
+            &self.params.modulation.lfo_depth,
+            &self.params.modulation.autopan_rate,
+            &self.params.modulation.autopan_depth,
+            &self.params.delay_a.rotation,
+        ] {
+            param.smoothed.reset(self.remote.value(param));
+        }
+        for slot in &self.params.modulation.mod_slots {
+            slot.depth.smoothed.reset(self.remote.value(&slot.depth));
+        }
+
+        self.freeze_amount = if self.remote.value(&self.params.performance.freeze) {
+            1.0
+        } else {
+            0.0
+        };
+        self.infinite_amount = if self.remote.value(&self.params.performance.infinite) {
+            1.0
+        } else {
+            0.0
+        };
+        self.clear_held = self.remote.value(&self.params.performance.clear);
+        self.clear_fade_remaining = 0;
+        self.last_tempo = None;
+        self.tempo_resync = false;
+        self.bypass_amount = if self.remote.value(&self.params.bypass) {
+            1.0
+        } else {
+            0.0
+        };
+        self.auto_gain = 1.0;
+        // A loop saved with the project is written back into the freshly cleared delay lines
+        self.frozen_captured = false;
+        if self.remote.value(&self.params.performance.freeze)
+            && self.remote.value(&self.params.performance.save_frozen)
+        {
+            if let Ok(frozen) = self.params.frozen_buffer.try_lock() {
+                if frozen.sample_rate == self.sample_rate && !frozen.is_empty() {
+                    frozen.restore(&mut self.delay_lines);
+                    self.frozen_captured = true;
+                }
+            }
+        }
+        self.wow_flutter.reset();
+        self.lfo
+            .reset(self.remote.value(&self.params.modulation.lfo_seed) as u32);
+        self.autopan.reset(AUTOPAN_SEED);
+        self.stutter.reset();
+        self.sequencer_send = 1.0;
+        self.duck_envelope = EnvelopeFollower::default();
+        self.input_envelope = EnvelopeFollower::default();
+        self.tap_tempo = TapTempo::default();
+        self.tuned_delay = None;
+        self.sample_clock = 0;
+        self.input_meter = MeterBallistics::default();
+        self.wet_meter = MeterBallistics::default();
+        self.output_meter = MeterBallistics::default();
+        self.goniometer_capture.reset();
+        self.loudness_meter.reset();
     }
 
     fn process(
         &mut self,
         buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        for channel_samples in buffer.iter_samples() {
-            // Smoothing is optionally built into the parameters themselves
-            let gain = self.params.gain.smoothed.next();
-            let mut prevsample;
-            let mut prevsample2;
-            if self.prev != self.params.time.smoothed.next() as usize {
-                self.iterrepeats = (self.iterrepeats as f32
-                    * self.params.time.smoothed.next() as f32
-                    / 1000.0) as usize;
-                self.prev = self.params.time.smoothed.next() as usize;
-            }
-            for sample in channel_samples {
-                prevsample = self.prevsample[self.iterrepeats];
-                prevsample2 = self.prevsample[(self.iterrepeats as f32
-                    * self.params.delay.smoothed.next() as f32
-                    / 1000.0) as usize];
-                *sample *= gain;
-                self.prevsample[self.iterdelay] = *sample;
-                self.iterdelay += 1;
-                self.iterrepeats += 1;
-                match self.params.mode.smoothed.next() {
-                    1 => {
-                        *sample += prevsample;
-                    }
-                    2 => {
-                        *sample += prevsample;
-                        if self.iterdelay > 199999 {
-                            if self.iterdelay % 5 == 0 {
-                                self.iterrepeats -= 1;
-                            } else if self.iterdelay % 7 == 0 {
-                                self.iterrepeats += 2;
-                            };
-                        } else {
-                            self.iterrepeats += 1;
-                        };
-                    }
-                    3 => {
-                        *sample = prevsample;
-                    }
-                    4 => {
-                        *sample *= prevsample;
-                    }
-                    5 => {
-                        *sample += prevsample + prevsample2;
-                    }
-                    6 => {
-                        *sample += prevsample;
-                        if self.iterdelay % self.params.mode6_ratio.smoothed.next() as usize == 0 {
-                            if self.iterdelay % 2 == 0 {
-                                self.iterrepeats -=
-                                    self.params.mode6_ratio.smoothed.next() as usize;
-                            } else {
-                                self.iterrepeats +=
-                                    self.params.mode6_ratio.smoothed.next() as usize;
-                            };
-                        };
-                    }
-                    7 => {
-                        *sample += prevsample + prevsample2;
-                        if self.iterdelay % self.params.mode6_ratio.smoothed.next() as usize == 0 {
-                            if self.iterdelay % 2 == 0 {
-                                self.iterrepeats -=
-                                    self.params.mode6_ratio.smoothed.next() as usize;
-                            } else {
-                                self.iterrepeats +=
-                                    self.params.mode6_ratio.smoothed.next() as usize;
-                            };
-                        };
-                        if self.iterdelay > 199999 {
-                            if self.iterdelay % 5 == 0 {
-                                self.iterrepeats -= 1;
-                            } else if self.iterdelay % 7 == 0 {
-                                self.iterrepeats += 2;
-                            };
-                        } else {
-                            self.iterrepeats += 1;
-                        }
+        self.update_buffer_len(context);
+        let latency = self.latency();
+        if latency != self.latency_samples {
+            self.latency_samples = latency;
+            context.set_latency_samples(latency);
+        }
+
+        let transport = context.transport();
+        let playhead = Playhead {
+            tempo: transport.tempo,
+            bar_beats: transport
+                .time_sig_numerator
+                .zip(transport.time_sig_denominator)
+                .map(|(numerator, denominator)| numerator as f64 * 4.0 / denominator as f64),
+            pos_beats: transport.pos_beats(),
+            playing: transport.playing,
+        };
+        // Without spillover the repeats of the previous preset are cut off
+        if self.params.preset_loaded.swap(false, Ordering::Relaxed)
+            && !self.remote.value(&self.params.spillover)
+        {
+            self.start_clear();
+        }
+
+        let num_samples = buffer.samples();
+        let started = playhead.playing && !self.was_playing;
+        let stopped = !playhead.playing && self.was_playing;
+        let jumped = playhead.playing
+            && self.was_playing
+            && transport.pos_samples() != self.next_pos_samples;
+        if started {
+            self.lfo
+                .reset(self.remote.value(&self.params.modulation.lfo_seed) as u32);
+            self.autopan.reset(AUTOPAN_SEED);
+            self.stutter.reset();
+        }
+        match self.remote.value(&self.params.performance.transport_mode) {
+            TransportMode::KeepTail => (),
+            TransportMode::Clear if started || stopped || jumped => self.start_clear(),
+            TransportMode::Resync if started || jumped => {
+                if let Some(pos) = transport.pos_samples() {
+                    for line in &mut self.delay_lines {
+                        let len = line.buffer.len() as i64;
+                        line.move_write_position(pos.rem_euclid(len) as usize);
                     }
-                    _ => {}
-                };
-                if self.iterdelay >= 399999 {
-                    self.iterdelay = 0;
-                };
-                if self.iterrepeats >= 399999 {
-                    self.iterrepeats = 0;
-                };
+                }
             }
+            TransportMode::Clear | TransportMode::Resync => (),
         }
+        self.was_playing = playhead.playing;
+        self.next_pos_samples = transport
+            .pos_samples()
+            .filter(|_| playhead.playing)
+            .map(|pos| pos + num_samples as i64);
+        let channels = buffer.as_slice();
+        let sidechain = aux
+            .inputs
+            .first()
+            .map(|sidechain| sidechain.as_slice_immutable());
+
+        // The buffer is split at every event so delay time changes from MIDI notes happen at the
+        // exact sample. Parameter changes already split the buffer because of sample accurate
+        // automation, and everything derived from the parameters is recomputed per segment.
+        let mut segment_start = 0;
+        while let Some(event) = context.next_event() {
+            let timing = (event.timing() as usize).min(num_samples);
+            if timing > segment_start {
+                self.process_segment(channels, sidechain, segment_start..timing, playhead);
+                segment_start = timing;
+            }
 
-        ProcessStatus::Normal
+            self.handle_event(event, context);
+        }
+
+        // The last event can land at the very end of the buffer, and some hosts send empty buffers
+        let status = if segment_start < num_samples {
+            self.process_segment(channels, sidechain, segment_start..num_samples, playhead)
+        } else {
+            self.tail_status()
+        };
+        if self.spectrum_capture.take_ready() && self.params.editor_state.is_open() {
+            context.execute_background(BufferTask::AnalyzeSpectrum);
+        }
+
+        status
     }
 }
 
-impl ClapPlugin for Myplug {
+/// The name and the IDs a version of the plugin is registered with in hosts.
+pub trait PluginIds: 'static + Send + Sync {
+    const NAME: &'static str;
+    const CLAP_ID: &'static str;
+    const VST3_CLASS_ID: [u8; 16];
+}
+
+pub struct CurrentIds;
+
+impl PluginIds for CurrentIds {
+    const NAME: &'static str = "Myplug2.1";
+    const CLAP_ID: &'static str = "io.github.snovaxz.myplug";
+    const VST3_CLASS_ID: [u8; 16] = [
+        0x5e, 0x2d, 0x8b, 0x41, 0xc7, 0x93, 0x4f, 0x0a, 0xb6, 0x1e, 0x72, 0xd4, 0x39, 0xa8, 0x05,
+        0xf3,
+    ];
+}
+
+/// The IDs from the plugin template that the first builds shipped with. Hosts look plugins up by
+/// their ID when they load a session, so sessions saved with those builds only find a plugin that
+/// still uses them, and its state goes through [`params::migrate_state()`] like any other.
+pub struct TemplateIds;
+
+impl PluginIds for TemplateIds {
+    const NAME: &'static str = "Myplug2.1 (Legacy)";
     const CLAP_ID: &'static str = "com.your-domain.MYPLUG";
+    const VST3_CLASS_ID: [u8; 16] = *b"Myplug__________";
+}
+
+/// The same plugin registered under the template's IDs, so sessions from the first builds keep
+/// loading.
+pub type LegacyMyplug = Myplug<TemplateIds>;
+
+impl<I: PluginIds> ClapPlugin for Myplug<I> {
+    const CLAP_ID: &'static str = I::CLAP_ID;
     const CLAP_DESCRIPTION: Option<&'static str> = Some("Testplugin for fun");
     const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
     const CLAP_SUPPORT_URL: Option<&'static str> = None;
-
-    // Don't forget to change these features
-    const CLAP_FEATURES: &'static [ClapFeature] = &[ClapFeature::AudioEffect, ClapFeature::Stereo];
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Delay,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+        ClapFeature::Surround,
+    ];
 }
 
-impl Vst3Plugin for Myplug {
-    const VST3_CLASS_ID: [u8; 16] = *b"Myplug__________";
-
-    // And also don't forget to change these categories
-    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
-        &[Vst3SubCategory::Fx, Vst3SubCategory::Dynamics];
+impl<I: PluginIds> Vst3Plugin for Myplug<I> {
+    const VST3_CLASS_ID: [u8; 16] = I::VST3_CLASS_ID;
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[
+        Vst3SubCategory::Fx,
+        Vst3SubCategory::Delay,
+        Vst3SubCategory::Stereo,
+    ];
 }
 
-nih_export_clap!(Myplug);
-nih_export_vst3!(Myplug);
+nih_export_clap!(Myplug, LegacyMyplug);
+nih_export_vst3!(Myplug, LegacyMyplug);
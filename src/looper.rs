@@ -0,0 +1,86 @@
+use crate::params::LooperState;
+
+/// Follows the looper's transport parameters between blocks. The looper itself is the delay line
+/// with its delay time set to the loop length: recording writes the input without feedback,
+/// overdubbing uses the feedback amount as the overdub decay, and playing loops the buffer the
+/// same way freezing does.
+#[derive(Default)]
+pub struct Looper {
+    state: LooperState,
+    /// The sample clock value when the current recording started.
+    record_start: u64,
+    /// The loop length in samples set by the last punch-in recording.
+    punched_len: Option<usize>,
+    /// Whether the clear trigger was held during the last block, so it only fires once per press.
+    clear_held: bool,
+}
+
+impl Looper {
+    /// Update the looper at the start of a block. When `punch_in` is enabled, leaving the record
+    /// state sets the loop length to the length of the recording, up to `max_len` samples.
+    /// Returns whether the loop should be cleared.
+    pub fn update(
+        &mut self,
+        state: LooperState,
+        punch_in: bool,
+        clear: bool,
+        sample_clock: u64,
+        max_len: usize,
+    ) -> bool {
+        let cleared = clear && !self.clear_held;
+        self.clear_held = clear;
+        if cleared {
+            self.punched_len = None;
+        }
+
+        if state != self.state {
+            if state == LooperState::Record {
+                self.record_start = sample_clock;
+            } else if self.state == LooperState::Record && punch_in {
+                let len = sample_clock.saturating_sub(self.record_start) as usize;
+                self.punched_len = Some(len.clamp(1, max_len));
+            }
+            self.state = state;
+        }
+
+        cleared
+    }
+
+    /// The loop length set by punching in, if there is one.
+    pub fn punched_len(&self) -> Option<usize> {
+        self.punched_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn punching_out_sets_the_loop_length() {
+        let mut looper = Looper::default();
+        looper.update(LooperState::Record, true, false, 100, 1000);
+        assert_eq!(looper.punched_len(), None);
+
+        looper.update(LooperState::Play, true, false, 350, 1000);
+        assert_eq!(looper.punched_len(), Some(250));
+
+        // Recordings longer than the buffer are clamped
+        looper.update(LooperState::Record, true, false, 400, 1000);
+        looper.update(LooperState::Overdub, true, false, 5000, 1000);
+        assert_eq!(looper.punched_len(), Some(1000));
+    }
+
+    #[test]
+    fn clearing_only_fires_once_per_press() {
+        let mut looper = Looper::default();
+        looper.update(LooperState::Record, true, false, 0, 1000);
+        looper.update(LooperState::Play, true, false, 10, 1000);
+
+        assert!(looper.update(LooperState::Play, true, true, 20, 1000));
+        assert_eq!(looper.punched_len(), None);
+        assert!(!looper.update(LooperState::Play, true, true, 30, 1000));
+        assert!(!looper.update(LooperState::Play, true, false, 40, 1000));
+        assert!(looper.update(LooperState::Play, true, true, 50, 1000));
+    }
+}
@@ -0,0 +1,7 @@
+use nih_plug::prelude::*;
+
+use MYPLUG::Myplug;
+
+fn main() {
+    nih_export_standalone::<Myplug>();
+}
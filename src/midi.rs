@@ -0,0 +1,467 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The number of tap intervals that are averaged for MIDI tap tempo.
+const TAP_TEMPO_HISTORY: usize = 4;
+
+/// The SysEx manufacturer ID reserved for non-commercial use.
+const SYSEX_MANUFACTURER_ID: u8 = 0x7d;
+
+/// The most parameters that fit in a SysEx snapshot. Both the count and the values are sent as
+/// two 7-bit data bytes, this only bounds the size of the message buffer.
+pub(crate) const SYSEX_MAX_PARAMS: usize = 256;
+
+/// The size of the largest SysEx message: the header, the parameter layout, the parameter count,
+/// two bytes per parameter, and the end byte.
+const SYSEX_BUFFER_LEN: usize = 3 + 2 + 2 + SYSEX_MAX_PARAMS * 2 + 1;
+
+/// The largest 14-bit value a normalized parameter value is scaled to in SysEx snapshots.
+pub const SYSEX_MAX_VALUE: f32 = 16383.0;
+
+/// A remote configuration message sent over MIDI SysEx. Snapshots contain every parameter's
+/// normalized value quantized to 14 bits, in the order the parameters are declared in.
+///
+/// Messages use the non-commercial manufacturer ID: `F0 7D 01 F7` requests a snapshot, and
+/// `F0 7D 02 <layout msb lsb> <count msb lsb> <msb lsb>... F7` is a snapshot that the plugin both
+/// sends in response and accepts to set all parameters at once. The layout identifies the
+/// parameter IDs and their order, so a snapshot taken with another version of the plugin is
+/// rejected instead of setting the wrong parameters.
+// Snapshots are created on the audio thread, so they can't be boxed
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteSysEx {
+    RequestSnapshot,
+    Snapshot {
+        layout: u16,
+        values: [u16; SYSEX_MAX_PARAMS],
+        len: usize,
+    },
+}
+
+/// Split a 14-bit value into two SysEx data bytes, and join them again.
+fn to_data_bytes(value: u16) -> [u8; 2] {
+    [(value >> 7) as u8 & 0x7f, value as u8 & 0x7f]
+}
+
+fn from_data_bytes(bytes: &[u8]) -> u16 {
+    ((bytes[0] as u16 & 0x7f) << 7) | (bytes[1] as u16 & 0x7f)
+}
+
+impl SysExMessage for RemoteSysEx {
+    type Buffer = [u8; SYSEX_BUFFER_LEN];
+
+    fn from_buffer(buffer: &[u8]) -> Option<Self> {
+        match buffer {
+            [0xf0, SYSEX_MANUFACTURER_ID, 0x01, 0xf7] => Some(Self::RequestSnapshot),
+            [0xf0, SYSEX_MANUFACTURER_ID, 0x02, body @ .., 0xf7] if body.len() >= 4 => {
+                let (layout, data) = body.split_at(2);
+                let (len, data) = data.split_at(2);
+                let len = from_data_bytes(len) as usize;
+                if len > SYSEX_MAX_PARAMS || data.len() != len * 2 {
+                    return None;
+                }
+
+                let mut values = [0; SYSEX_MAX_PARAMS];
+                for (value, pair) in values.iter_mut().zip(data.chunks_exact(2)) {
+                    *value = from_data_bytes(pair);
+                }
+
+                Some(Self::Snapshot {
+                    layout: from_data_bytes(layout),
+                    values,
+                    len,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn to_buffer(self) -> (Self::Buffer, usize) {
+        let mut buffer = [0; SYSEX_BUFFER_LEN];
+        buffer[0] = 0xf0;
+        buffer[1] = SYSEX_MANUFACTURER_ID;
+        let len = match self {
+            Self::RequestSnapshot => {
+                buffer[2] = 0x01;
+                3
+            }
+            Self::Snapshot {
+                layout,
+                values,
+                len,
+            } => {
+                buffer[2] = 0x02;
+                buffer[3..5].copy_from_slice(&to_data_bytes(layout));
+                buffer[5..7].copy_from_slice(&to_data_bytes(len as u16));
+                for (pair, value) in buffer[7..].chunks_exact_mut(2).zip(&values[..len]) {
+                    pair.copy_from_slice(&to_data_bytes(*value));
+                }
+                7 + len * 2
+            }
+        };
+        buffer[len] = 0xf7;
+
+        (buffer, len + 1)
+    }
+}
+
+/// A 14-bit hash of the parameter IDs in order, used to tell whether a SysEx snapshot was taken
+/// with the same parameters.
+fn param_layout<'a>(ids: impl IntoIterator<Item = &'a str>) -> u16 {
+    // FNV-1a, with a separator so the IDs can't run into each other
+    let mut hash: u32 = 0x811c_9dc5;
+    for id in ids {
+        for byte in id.bytes().chain([0]) {
+            hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+        }
+    }
+
+    ((hash ^ (hash >> 14) ^ (hash >> 28)) & 0x3fff) as u16
+}
+
+/// Every parameter in `Params::param_map()` order, with the values set over MIDI on the audio
+/// thread. NIH-plug only lets the editor change parameters, so mapped CCs and SysEx snapshots set
+/// these overrides instead, which stand in for a parameter's value until the host or the editor
+/// changes that parameter.
+pub struct RemoteParams {
+    ptrs: Vec<ParamPtr>,
+    /// The address of every parameter along with its index in `ptrs`, sorted by address so
+    /// [`value()`][Self::value()] can find a parameter's override with a binary search.
+    indices: Vec<(usize, usize)>,
+    layout: u16,
+    /// The normalized value set for every parameter, along with the parameter's own normalized
+    /// value at that time.
+    overrides: Vec<Option<(f32, f32)>>,
+    num_overrides: usize,
+}
+
+impl RemoteParams {
+    /// The parameters are only accessed through pointers, so `params` must outlive this.
+    pub fn new(params: &impl Params) -> Self {
+        let param_map = params.param_map();
+        let ptrs: Vec<ParamPtr> = param_map.iter().map(|(_, ptr, _)| *ptr).collect();
+        let mut indices: Vec<(usize, usize)> = ptrs
+            .iter()
+            .enumerate()
+            .map(|(index, ptr)| (param_address(*ptr), index))
+            .collect();
+        indices.sort_unstable();
+
+        Self {
+            layout: param_layout(param_map.iter().map(|(id, _, _)| id.as_str())),
+            overrides: vec![None; ptrs.len()],
+            ptrs,
+            indices,
+            num_overrides: 0,
+        }
+    }
+
+    /// Capture the current values of the parameters, including their overrides.
+    pub fn snapshot(&self) -> RemoteSysEx {
+        let mut values = [0; SYSEX_MAX_PARAMS];
+        for ((value, ptr), set) in values.iter_mut().zip(&self.ptrs).zip(&self.overrides) {
+            let normalized = match set {
+                Some((normalized, _)) => *normalized,
+                None => unsafe { ptr.unmodulated_normalized_value() },
+            };
+            *value = (normalized * SYSEX_MAX_VALUE).round() as u16;
+        }
+
+        RemoteSysEx::Snapshot {
+            layout: self.layout,
+            values,
+            len: self.ptrs.len().min(SYSEX_MAX_PARAMS),
+        }
+    }
+
+    /// Override every parameter with the values from a snapshot. Returns `false` and leaves the
+    /// parameters alone if the snapshot was taken with different parameters.
+    pub fn apply_snapshot(&mut self, snapshot: &RemoteSysEx, sample_rate: f32) -> bool {
+        match snapshot {
+            RemoteSysEx::Snapshot {
+                layout,
+                values,
+                len,
+            } if *layout == self.layout && *len == self.ptrs.len() => {
+                for (index, value) in values[..*len].iter().enumerate() {
+                    self.set(index, *value as f32 / SYSEX_MAX_VALUE, sample_rate);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Override the parameter at `index` with a normalized value. Smoothed parameters glide to
+    /// the new value like they would for a host automation change.
+    pub fn set(&mut self, index: usize, normalized: f32, sample_rate: f32) {
+        let (Some(ptr), Some(set)) = (self.ptrs.get(index), self.overrides.get_mut(index)) else {
+            return;
+        };
+
+        unsafe {
+            match *ptr {
+                ParamPtr::FloatParam(param) => {
+                    let param = &*param;
+                    param
+                        .smoothed
+                        .set_target(sample_rate, param.preview_plain(normalized));
+                }
+                ParamPtr::IntParam(param) => {
+                    let param = &*param;
+                    param
+                        .smoothed
+                        .set_target(sample_rate, param.preview_plain(normalized));
+                }
+                _ => (),
+            }
+
+            if set.is_none() {
+                self.num_overrides += 1;
+            }
+            *set = Some((normalized, ptr.unmodulated_normalized_value()));
+        }
+    }
+
+    /// Drop all overrides, for when the plugin starts a new session.
+    pub fn clear(&mut self) {
+        self.overrides.fill(None);
+        self.num_overrides = 0;
+    }
+
+    /// Drop the overrides of parameters that have since been changed by the host or the editor,
+    /// so [`value()`][Self::value()] skips the lookup again once every override is stale.
+    pub fn prune(&mut self) {
+        if self.num_overrides == 0 {
+            return;
+        }
+
+        for (ptr, set) in self.ptrs.iter().zip(&mut self.overrides) {
+            if let Some((_, own)) = *set {
+                if unsafe { ptr.unmodulated_normalized_value() } != own {
+                    *set = None;
+                    self.num_overrides -= 1;
+                }
+            }
+        }
+    }
+
+    /// The parameter's value, or the value set over MIDI if it hasn't changed since.
+    pub fn value<P: Param>(&self, param: &P) -> P::Plain {
+        if self.num_overrides > 0 {
+            let address = param_address(param.as_ptr());
+            let override_ = self
+                .indices
+                .binary_search_by_key(&address, |(address, _)| *address)
+                .ok()
+                .and_then(|position| self.overrides[self.indices[position].1]);
+            if let Some((normalized, own)) = override_ {
+                if param.unmodulated_normalized_value() == own {
+                    return param.preview_plain(normalized);
+                }
+            }
+        }
+
+        param.modulated_plain_value()
+    }
+}
+
+/// The address a parameter pointer points to, which orders the pointers for
+/// [`RemoteParams`]'s lookup.
+fn param_address(ptr: ParamPtr) -> usize {
+    match ptr {
+        ParamPtr::FloatParam(param) => param as usize,
+        ParamPtr::IntParam(param) => param as usize,
+        ParamPtr::BoolParam(param) => param as usize,
+        ParamPtr::EnumParam(param) => param as usize,
+    }
+}
+
+/// Derives a delay time from the spacing between taps, averaged over the last few taps. A pause
+/// longer than the longest delay time starts a new series of taps.
+#[derive(Default)]
+pub struct TapTempo {
+    /// The time of the last tap in samples since the plugin started processing.
+    last_tap: Option<u64>,
+    /// The most recent intervals between taps in samples, used as a ring buffer.
+    intervals: [u64; TAP_TEMPO_HISTORY],
+    num_intervals: usize,
+    /// The tapped delay time in samples, if there have been at least two taps.
+    pub delay_samples: Option<usize>,
+}
+
+impl TapTempo {
+    pub fn tap(&mut self, time: u64, max_interval: u64) {
+        if let Some(last_tap) = self.last_tap {
+            let interval = time.saturating_sub(last_tap);
+            if interval > max_interval {
+                self.num_intervals = 0;
+            } else if interval > 0 {
+                self.intervals[self.num_intervals % TAP_TEMPO_HISTORY] = interval;
+                self.num_intervals += 1;
+
+                let intervals = &self.intervals[..self.num_intervals.min(TAP_TEMPO_HISTORY)];
+                self.delay_samples =
+                    Some((intervals.iter().sum::<u64>() / intervals.len() as u64) as usize);
+            }
+        }
+
+        self.last_tap = Some(time);
+    }
+}
+
+/// The latest value of every MIDI CC, written by the audio thread and picked up by the editor,
+/// and the parameter every CC is mapped to. The audio thread applies mapped CCs through
+/// [`RemoteParams`], and while the editor is open it also sets the parameters so the host sees
+/// the changes.
+pub struct MidiCcs {
+    values: [AtomicF32; 128],
+    changed: [AtomicBool; 128],
+    /// The index in `Params::param_map()` of every CC's parameter, or `usize::MAX` if the CC
+    /// isn't mapped.
+    targets: [AtomicUsize; 128],
+}
+
+impl Default for MidiCcs {
+    fn default() -> Self {
+        Self {
+            values: std::array::from_fn(|_| AtomicF32::new(0.0)),
+            changed: std::array::from_fn(|_| AtomicBool::new(false)),
+            targets: std::array::from_fn(|_| AtomicUsize::new(usize::MAX)),
+        }
+    }
+}
+
+impl MidiCcs {
+    /// Look up the parameters in `mappings` so the audio thread can apply CCs without going
+    /// through the parameter IDs. This allocates, so it's called whenever the mappings change
+    /// rather than on the audio thread.
+    pub fn map(&self, mappings: &BTreeMap<u8, String>, params: &impl Params) {
+        let param_map = params.param_map();
+        for (cc, target) in self.targets.iter().enumerate() {
+            let index = mappings
+                .get(&(cc as u8))
+                .and_then(|id| param_map.iter().position(|(param_id, _, _)| param_id == id))
+                .unwrap_or(usize::MAX);
+            target.store(index, Ordering::Relaxed);
+        }
+    }
+
+    /// The index of the parameter the CC is mapped to.
+    pub fn target(&self, cc: u8) -> Option<usize> {
+        self.targets
+            .get(cc as usize)
+            .map(|target| target.load(Ordering::Relaxed))
+            .filter(|&index| index != usize::MAX)
+    }
+
+    pub fn set(&self, cc: u8, value: f32) {
+        if let (Some(stored), Some(changed)) =
+            (self.values.get(cc as usize), self.changed.get(cc as usize))
+        {
+            stored.store(value, Ordering::Relaxed);
+            changed.store(true, Ordering::Release);
+        }
+    }
+
+    /// The CC's value if it changed since the last time this was called.
+    pub fn take(&self, cc: u8) -> Option<f32> {
+        let cc = cc as usize;
+        self.changed[cc]
+            .swap(false, Ordering::Acquire)
+            .then(|| self.values[cc].load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::MyplugParams;
+
+    fn snapshot(len: usize) -> RemoteSysEx {
+        RemoteSysEx::Snapshot {
+            layout: 0x2abc,
+            values: std::array::from_fn(|i| (i as u16 * 97) & 0x3fff),
+            len,
+        }
+    }
+
+    fn round_trip(message: RemoteSysEx) -> Option<RemoteSysEx> {
+        let (buffer, len) = message.to_buffer();
+        RemoteSysEx::from_buffer(&buffer[..len])
+    }
+
+    #[test]
+    fn every_parameter_fits_in_a_snapshot() {
+        assert!(MyplugParams::default().param_map().len() <= SYSEX_MAX_PARAMS);
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        assert_eq!(
+            round_trip(RemoteSysEx::RequestSnapshot),
+            Some(RemoteSysEx::RequestSnapshot)
+        );
+        for len in [0, 1, 127, 128, SYSEX_MAX_PARAMS] {
+            let Some(RemoteSysEx::Snapshot {
+                layout,
+                values,
+                len: decoded_len,
+            }) = round_trip(snapshot(len))
+            else {
+                panic!("snapshot of {len} parameters didn't decode");
+            };
+            let RemoteSysEx::Snapshot { values: sent, .. } = snapshot(len) else {
+                unreachable!()
+            };
+
+            assert_eq!(layout, 0x2abc);
+            assert_eq!(decoded_len, len);
+            assert_eq!(values[..len], sent[..len]);
+        }
+    }
+
+    #[test]
+    fn malformed_snapshots_are_rejected() {
+        let (buffer, len) = snapshot(4).to_buffer();
+        let message = &buffer[..len];
+
+        // Missing the end byte, missing or extra data bytes, and a truncated header
+        assert_eq!(RemoteSysEx::from_buffer(&message[..len - 1]), None);
+        let mut short = message[..len - 2].to_vec();
+        short.push(0xf7);
+        assert_eq!(RemoteSysEx::from_buffer(&short), None);
+        let mut long = message[..len - 1].to_vec();
+        long.extend([0x00, 0x00, 0xf7]);
+        assert_eq!(RemoteSysEx::from_buffer(&long), None);
+        assert_eq!(
+            RemoteSysEx::from_buffer(&[0xf0, SYSEX_MANUFACTURER_ID, 0x02, 0x01, 0xf7]),
+            None
+        );
+
+        // A count larger than the buffer can hold, with enough data to match it
+        let count = SYSEX_MAX_PARAMS as u16 + 1;
+        let mut too_many = vec![0xf0, SYSEX_MANUFACTURER_ID, 0x02, 0x00, 0x00];
+        too_many.extend(to_data_bytes(count));
+        too_many.extend(vec![0x00; count as usize * 2]);
+        too_many.push(0xf7);
+        assert_eq!(RemoteSysEx::from_buffer(&too_many), None);
+
+        // Another manufacturer's message
+        let mut other = message.to_vec();
+        other[1] = 0x41;
+        assert_eq!(RemoteSysEx::from_buffer(&other), None);
+    }
+
+    #[test]
+    fn layout_depends_on_the_parameter_order() {
+        let layout = param_layout(["time", "feedback", "mix"]);
+        assert_eq!(layout, param_layout(["time", "feedback", "mix"]));
+        assert_ne!(layout, param_layout(["feedback", "time", "mix"]));
+        assert_ne!(layout, param_layout(["time", "feedback"]));
+        assert_ne!(layout, param_layout(["timefeedback", "mix"]));
+        assert!(layout <= 0x3fff);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::params::{ModDestination, ModSource};
+
+/// The number of slots in the modulation matrix.
+pub const NUM_MOD_SLOTS: usize = 4;
+
+/// How far the cutoff destination moves the high cut filter at full depth, in octaves.
+pub const CUTOFF_OCTAVES: f32 = 4.0;
+
+/// The modulation sources' values for a single sample.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sources {
+    /// The LFO's output in `[-1, 1]`.
+    pub lfo: f32,
+    /// The input's envelope in `[0, 1]`.
+    pub envelope: f32,
+}
+
+/// The modulation matrix's slots summed per destination for a single sample. Every value is in
+/// `[-1, 1]` per slot at full depth.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Modulation {
+    /// Scales the delay time, in octaves.
+    pub time: f32,
+    /// Added to the feedback amount.
+    pub feedback: f32,
+    /// Moves the high cut filter's cutoff, in multiples of `CUTOFF_OCTAVES`.
+    pub cutoff: f32,
+    /// Pans the repeats towards the first channel when negative, and towards the second channel
+    /// when positive.
+    pub pan: f32,
+}
+
+impl Modulation {
+    /// Sum the slots, which route a source to a destination with a bipolar depth.
+    pub fn sum(slots: &[(ModSource, ModDestination, f32)], sources: Sources) -> Self {
+        let mut modulation = Self::default();
+        for &(source, destination, depth) in slots {
+            let value = match source {
+                ModSource::Lfo => sources.lfo,
+                ModSource::Envelope => sources.envelope,
+            } * depth;
+
+            match destination {
+                ModDestination::Off => (),
+                ModDestination::DelayTime => modulation.time += value,
+                ModDestination::Feedback => modulation.feedback += value,
+                ModDestination::Cutoff => modulation.cutoff += value,
+                ModDestination::Pan => modulation.pan += value,
+            }
+        }
+
+        modulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_are_summed_per_destination() {
+        let sources = Sources {
+            lfo: -0.5,
+            envelope: 0.25,
+        };
+        let slots = [
+            (ModSource::Lfo, ModDestination::Feedback, 1.0),
+            (ModSource::Envelope, ModDestination::Feedback, 1.0),
+            (ModSource::Envelope, ModDestination::Pan, -0.5),
+            (ModSource::Lfo, ModDestination::Off, 1.0),
+        ];
+
+        assert_eq!(
+            Modulation::sum(&slots, sources),
+            Modulation {
+                time: 0.0,
+                feedback: -0.25,
+                cutoff: 0.0,
+                pan: -0.125,
+            }
+        );
+    }
+}
@@ -0,0 +1,2297 @@
+use nih_plug::prelude::*;
+use nih_plug_egui::EguiState;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::f32::consts::FRAC_PI_2;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::dsp::euclidean::MAX_STEPS;
+use crate::dsp::{Crusher, Sample};
+use crate::frozen::FrozenBuffer;
+use crate::modulation::NUM_MOD_SLOTS;
+use crate::presets::{AbCompare, Randomizer};
+use crate::sequencer::{StepSequence, NUM_STEPS};
+use crate::taps::{TapPatterns, NUM_TAPS};
+
+/// The longest delay time in seconds that the maximum delay time can be set to.
+pub const MAX_DELAY_SECONDS: f32 = 60.0;
+
+/// The default maximum delay time in seconds.
+pub const DEFAULT_MAX_DELAY_SECONDS: f32 = 10.0;
+
+/// The longest delay time in seconds of the second delay engine. Its buffers are always this
+/// long, independent of the maximum delay time.
+pub const ENGINE_B_MAX_SECONDS: f32 = 10.0;
+
+/// The longest lookahead the ducker can use, in milliseconds.
+pub const DUCK_MAX_LOOKAHEAD_MS: f32 = 10.0;
+
+#[derive(Params)]
+pub struct MyplugParams {
+    /// The editor state, saved together with the parameter state so the custom scaling can be
+    /// restored.
+    #[persist = "editor-state"]
+    pub(crate) editor_state: Arc<EguiState>,
+    /// The editor's zoom on top of the display's scale factor, so it can be made smaller on small
+    /// screens.
+    #[persist = "ui-scale"]
+    pub(crate) ui_scale: Arc<RwLock<f32>>,
+    /// The name of the last loaded preset, so the preset browser shows it again when the project
+    /// is reopened.
+    #[persist = "preset-name"]
+    pub(crate) preset_name: Arc<RwLock<String>>,
+    /// The frozen loop, only filled in while frozen with `save_frozen` enabled.
+    #[persist = "frozen-buffer"]
+    pub(crate) frozen_buffer: Arc<Mutex<FrozenBuffer>>,
+    /// Maps MIDI CC numbers to the IDs of the parameters they control.
+    #[persist = "cc-mappings"]
+    pub(crate) cc_mappings: Arc<RwLock<BTreeMap<u8, String>>>,
+    /// The delay time sequencer's steps.
+    #[persist = "sequence"]
+    pub(crate) sequence: Arc<RwLock<StepSequence>>,
+    /// The editor's A/B comparison slots.
+    #[persist = "ab-compare"]
+    pub(crate) ab_compare: Arc<RwLock<AbCompare>>,
+    /// The editor's randomizer seed and locked parameters.
+    #[persist = "randomizer"]
+    pub(crate) randomizer: Arc<RwLock<Randomizer>>,
+    /// The tap timeline's grid and the user's stored tap patterns.
+    #[persist = "tap-patterns"]
+    pub(crate) tap_patterns: Arc<RwLock<TapPatterns>>,
+    /// Set by the editor when it loads a preset, so the audio thread can clear the delay lines
+    /// if `spillover` is disabled.
+    pub(crate) preset_loaded: Arc<AtomicBool>,
+
+    /// Passes the input through unprocessed while the repeats that are already in the buffer
+    /// ring out. Hosts map their bypass button to this parameter.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+    /// Keeps the repeats ringing out when a preset is loaded, instead of clearing the delay
+    /// lines. This is a global setting that isn't changed by loading presets.
+    #[id = "spillover"]
+    pub spillover: BoolParam,
+
+    /// The parameter's ID is used to identify the parameter in the wrappred plugin API. As long as
+    /// these IDs remain constant, you can rename and reorder these fields as you wish. The
+    /// parameters are exposed to the host in the same order they were defined. In this case, this
+    /// gain parameter is stored as linear gain while the values are displayed in decibels. The
+    /// input gain used to be the only gain parameter, so it keeps its old ID.
+    #[id = "gain"]
+    pub input_gain: FloatParam,
+
+    /// The parameters are grouped so hosts can show them as a tree. Nesting them without an ID
+    /// prefix keeps their original IDs, so existing projects and presets still load.
+    #[nested(group = "Delay A")]
+    pub delay_a: DelayParams,
+    #[nested(group = "Feedback Path")]
+    pub feedback_path: FeedbackParams,
+    #[nested(group = "Modulation")]
+    pub modulation: ModulationParams,
+    #[nested(group = "Output")]
+    pub output: OutputParams,
+    #[nested(group = "Performance")]
+    pub performance: PerformanceParams,
+    /// How the second delay engine is connected to the main one.
+    #[id = "engine-routing"]
+    pub engine_routing: EnumParam<EngineRouting>,
+    /// The second delay engine. Nesting it with a prefix gives its parameters IDs like
+    /// `b_time`.
+    #[nested(id_prefix = "b", group = "Delay B")]
+    pub engine_b: EngineParams,
+}
+
+/// The main delay engine's delay time, mode, and stereo and timing options.
+#[derive(Params)]
+pub struct DelayParams {
+    /// The delay time of the second tap in the Dual Tap and Chaos modes, in milliseconds.
+    #[id = "delay"]
+    pub delay: FloatParam,
+    #[id = "mode"]
+    pub mode: EnumParam<DelayMode>,
+    /// The delay time in milliseconds when not synced to the host's tempo.
+    #[id = "time"]
+    pub time: FloatParam,
+    /// Delays the wet signal, so the first repeat starts later while the repeats after it keep
+    /// their spacing. This is useful for pushing the repeats behind the beat.
+    #[id = "pre-delay"]
+    pub pre_delay: FloatParam,
+    /// When enabled, the pre-delay is `pre_delay_note` at the host's tempo instead of
+    /// `pre_delay`.
+    #[id = "pre-delay-sync"]
+    pub pre_delay_sync: BoolParam,
+    #[id = "pre-delay-note"]
+    pub pre_delay_note: EnumParam<NoteDivision>,
+    /// The pitch the Karplus-Strong mode's string and the comb mode's filter are tuned to.
+    #[id = "pitch"]
+    pub pitch: FloatParam,
+    #[id = "comb-polarity"]
+    pub comb_polarity: EnumParam<CombPolarity>,
+    /// The Haas doubler mode's delay time. Every other channel is delayed by a fraction of this.
+    #[id = "haas-time"]
+    pub haas_time: FloatParam,
+    /// How much the comb filter's loop damps the higher harmonics on every pass.
+    #[id = "comb-damping"]
+    pub comb_damping: FloatParam,
+    /// The longest delay time in seconds the delay buffers are sized for. Longer delay times are
+    /// clamped to this. Changing it reallocates and clears the delay buffers.
+    #[id = "max-delay"]
+    pub max_delay: FloatParam,
+    #[id = "mode6-ratio"]
+    pub mode6_ratio: IntParam,
+    /// Scales how far the read position jumps in the Glitch, Shuffle, and Chaos modes. At 100%
+    /// the jumps match the original modes, and at 0% those modes don't jump at all.
+    #[id = "jitter"]
+    pub jitter: FloatParam,
+    /// The Euclidean mode spreads `euclid_pulses` taps as evenly as possible over `euclid_steps`
+    /// steps of a bar, and `euclid_rotation` shifts the pattern by whole steps.
+    #[id = "euclid-steps"]
+    pub euclid_steps: IntParam,
+    #[id = "euclid-pulses"]
+    pub euclid_pulses: IntParam,
+    #[id = "euclid-rotation"]
+    pub euclid_rotation: IntParam,
+    /// Delays the right channel by this much more than the left channel, or the left channel
+    /// when negative, to add width to mono sources. Ignored while `stereo_link` is enabled.
+    #[id = "stereo-offset"]
+    pub stereo_offset: FloatParam,
+    /// Use the same delay time for both channels.
+    #[id = "stereo-link"]
+    pub stereo_link: BoolParam,
+    /// Lengthens the delay time of every following channel by a larger fraction of the delay
+    /// time, so the repeats spread out over multichannel setups.
+    #[id = "time-spread"]
+    pub time_spread: FloatParam,
+    /// How much of every channel's repeats get fed into the next channel instead, so echoes
+    /// rotate around the speakers.
+    #[id = "rotation"]
+    pub rotation: FloatParam,
+    /// When enabled, the delay time follows the host's tempo using `note` instead of `time`.
+    #[id = "sync"]
+    pub sync: BoolParam,
+    /// Tap the delay time with MIDI notes. The tapped time takes precedence over both the time
+    /// parameter and tempo sync until this is disabled again.
+    #[id = "midi-tap"]
+    pub midi_tap: BoolParam,
+    /// Set the delay time to the period of incoming MIDI notes, so the feedback loop resonates
+    /// at the note's pitch like a plucked string. This takes precedence over MIDI tap.
+    #[id = "note-tuning"]
+    pub note_tuning: BoolParam,
+    /// How the delay reacts to changes in the delay time.
+    #[id = "time-change"]
+    pub time_change: EnumParam<TimeChangeMode>,
+    /// How far the ping-pong mode's repeats bounce between the channels. At 0% this behaves like
+    /// a regular echo, at 100% the input is summed to mono and bounces fully between the sides.
+    #[id = "ping-pong-width"]
+    pub ping_pong_width: FloatParam,
+    #[id = "note"]
+    pub note: EnumParam<NoteDivision>,
+    /// Delays every other repeat while synced. This is where the odd repeats fall between the
+    /// even ones, so 50% is straight and 66.7% is a triplet shuffle.
+    #[id = "swing"]
+    pub swing: FloatParam,
+    /// The interpolation used when reading from fractional positions, for instance while the
+    /// read position is being modulated.
+    #[id = "interpolation"]
+    pub interpolation: EnumParam<Interpolation>,
+    /// The multi-tap mode's taps.
+    #[nested(array, group = "Tap")]
+    pub taps: [TapParams; NUM_TAPS],
+}
+
+impl DelayParams {
+    fn new(overrides: &ParamOverrides) -> Self {
+        let sync = overrides.bool("sync", false);
+        let note = overrides.enumeration("note", NoteDivision::Quarter);
+        let synced_time = SyncedTime::new(sync, note);
+        let pre_delay_sync = overrides.bool("pre-delay-sync", false);
+        let pre_delay_note = overrides.enumeration("pre-delay-note", NoteDivision::Sixteenth);
+        let synced_pre_delay = SyncedTime::new(pre_delay_sync, pre_delay_note);
+
+        Self {
+            delay: FloatParam::new("Delay", overrides.float("delay", 250.0), delay_time_range())
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(1))
+                .with_string_to_value(s2v_f32_ms()),
+            mode: EnumParam::new("Mode", overrides.enumeration("mode", DelayMode::Echo)),
+            time: FloatParam::new("Time", overrides.float("time", 500.0), delay_time_range())
+                .with_value_to_string(synced_time.v2s_f32_ms())
+                .with_string_to_value(s2v_f32_ms()),
+            pre_delay: FloatParam::new(
+                "Pre-Delay",
+                overrides.float("pre-delay", 0.0),
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 500.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_value_to_string(synced_pre_delay.v2s_f32_ms())
+            .with_string_to_value(s2v_f32_ms()),
+            pre_delay_sync: BoolParam::new("Pre-Delay Sync", pre_delay_sync)
+                .with_callback(synced_pre_delay.sync_callback()),
+            pre_delay_note: EnumParam::new("Pre-Delay Note", pre_delay_note)
+                .with_callback(synced_pre_delay.note_callback()),
+            pitch: FloatParam::new(
+                "Pitch",
+                overrides.float("pitch", 220.0),
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz_with_note_name(1, true))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            haas_time: FloatParam::new(
+                "Haas Time",
+                overrides.float("haas-time", 15.0),
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 40.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_ms()),
+            comb_polarity: EnumParam::new(
+                "Comb Polarity",
+                overrides.enumeration("comb-polarity", CombPolarity::Positive),
+            ),
+            comb_damping: FloatParam::new(
+                "Comb Damping",
+                overrides.float("comb-damping", 0.2),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            max_delay: FloatParam::new(
+                "Max Delay",
+                overrides.float("max-delay", DEFAULT_MAX_DELAY_SECONDS),
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: MAX_DELAY_SECONDS,
+                },
+            )
+            .with_step_size(1.0)
+            .with_unit(" s")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_string_to_value(s2v_f32_seconds())
+            .non_automatable(),
+            mode6_ratio: IntParam::new(
+                "Mode6_ratio",
+                overrides.int("mode6-ratio", 3),
+                IntRange::Linear { min: 3, max: 21 },
+            )
+            .with_smoother(SmoothingStyle::None),
+            jitter: FloatParam::new(
+                "Jitter",
+                overrides.float("jitter", 1.0),
+                FloatRange::Linear { min: 0.0, max: 2.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            euclid_steps: IntParam::new(
+                "Euclid Steps",
+                overrides.int("euclid-steps", 8),
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_STEPS as i32,
+                },
+            ),
+            euclid_pulses: IntParam::new(
+                "Euclid Pulses",
+                overrides.int("euclid-pulses", 3),
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_STEPS as i32,
+                },
+            ),
+            euclid_rotation: IntParam::new(
+                "Euclid Rotation",
+                overrides.int("euclid-rotation", 0),
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_STEPS as i32 - 1,
+                },
+            ),
+            stereo_offset: FloatParam::new(
+                "Stereo Offset",
+                overrides.float("stereo-offset", 0.0),
+                FloatRange::Linear {
+                    min: -50.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_ms()),
+            stereo_link: BoolParam::new("Stereo Link", overrides.bool("stereo-link", false)),
+            time_spread: FloatParam::new(
+                "Time Spread",
+                overrides.float("time-spread", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            rotation: FloatParam::new(
+                "Rotation",
+                overrides.float("rotation", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            sync: BoolParam::new("Sync", sync).with_callback(synced_time.sync_callback()),
+            midi_tap: BoolParam::new("MIDI Tap", overrides.bool("midi-tap", false)),
+            note_tuning: BoolParam::new("Note Tuning", overrides.bool("note-tuning", false)),
+            time_change: EnumParam::new(
+                "Time Change",
+                overrides.enumeration("time-change", TimeChangeMode::Crossfade),
+            ),
+            ping_pong_width: FloatParam::new(
+                "Ping-Pong Width",
+                overrides.float("ping-pong-width", 1.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            note: EnumParam::new("Note", note).with_callback(synced_time.note_callback()),
+            swing: FloatParam::new(
+                "Swing",
+                overrides.float("swing", 0.5),
+                FloatRange::Linear {
+                    min: 0.5,
+                    max: 0.75,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            interpolation: EnumParam::new(
+                "Interpolation",
+                overrides.enumeration("interpolation", Interpolation::Cubic),
+            ),
+            taps: std::array::from_fn(|tap| TapParams::new(tap, overrides)),
+        }
+    }
+}
+
+/// A tap of the multi-tap mode. Nesting these as an array appends the tap number to the
+/// parameter IDs, so the first tap's time is `tap-time_1`.
+#[derive(Params)]
+pub struct TapParams {
+    #[id = "tap-enabled"]
+    pub enabled: BoolParam,
+    /// Where the tap lands as a fraction of the pattern, which spans a bar.
+    #[id = "tap-time"]
+    pub time: FloatParam,
+    #[id = "tap-level"]
+    pub level: FloatParam,
+}
+
+impl TapParams {
+    /// Every other tap starts out enabled, for a tap on every beat of a bar in 4/4.
+    fn new(tap: usize, overrides: &ParamOverrides) -> Self {
+        let number = tap + 1;
+        Self {
+            enabled: BoolParam::new(
+                format!("Tap {number} Enabled"),
+                overrides.bool(&format!("tap-enabled_{number}"), number.is_multiple_of(2)),
+            ),
+            time: FloatParam::new(
+                format!("Tap {number} Time"),
+                overrides.float(
+                    &format!("tap-time_{number}"),
+                    number as f32 / NUM_TAPS as f32,
+                ),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            level: FloatParam::new(
+                format!("Tap {number} Level"),
+                overrides.float(&format!("tap-level_{number}"), 0.5),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+/// Everything the repeats pass through before they're written back into the main delay.
+#[derive(Params)]
+pub struct FeedbackParams {
+    /// How much of the delayed signal gets written back into the delay buffer. Above 100% the
+    /// repeats swell until they're held back by the feedback limiter.
+    #[id = "feedback"]
+    pub feedback: FloatParam,
+    /// The level the soft limiter in the feedback path never lets the repeats exceed, stored as
+    /// linear gain.
+    #[id = "feedback-ceiling"]
+    pub feedback_ceiling: FloatParam,
+    /// Cutoff of the high-pass filter in the feedback path. Every repeat loses more low end.
+    #[id = "low-cut"]
+    pub low_cut: FloatParam,
+    /// Cutoff of the low-pass filter in the feedback path. Every repeat gets darker.
+    #[id = "high-cut"]
+    pub high_cut: FloatParam,
+    /// How much darker every repeat gets than the one before it. This high shelf is applied once
+    /// per pass, so the third repeat has lost three times as much top end as the first, like
+    /// on a tape or bucket brigade delay.
+    #[id = "decay-tilt"]
+    pub decay_tilt: FloatParam,
+    /// The center frequency of the parametric bell in the feedback path. Cutting a resonance
+    /// that builds up over the repeats keeps it from taking over the tail.
+    #[id = "bell-freq"]
+    pub bell_freq: FloatParam,
+    /// The bit depth the feedback path is quantized to. Lower bit depths add more noise and
+    /// distortion on every repeat.
+    #[id = "crush-bits"]
+    pub crush_bits: FloatParam,
+    /// The sample rate the feedback path is reduced to, which folds the top end back down as
+    /// aliasing.
+    #[id = "crush-rate"]
+    pub crush_rate: FloatParam,
+    /// How much the bell boosts or cuts. Every repeat passes through it again, so small amounts
+    /// add up quickly.
+    #[id = "bell-gain"]
+    pub bell_gain: FloatParam,
+    #[id = "bell-q"]
+    pub bell_q: FloatParam,
+    /// The vintage delay the feedback path emulates.
+    #[id = "character"]
+    pub character: EnumParam<Character>,
+    /// How worn the tape character model's tape is. Older tape hisses louder along with the
+    /// repeats.
+    #[id = "age"]
+    pub age: FloatParam,
+    /// The waveshaper applied to the signal re-entering the delay line.
+    #[id = "saturation"]
+    pub saturation: EnumParam<Saturation>,
+    /// How hard the feedback path is driven into the waveshaper.
+    #[id = "drive"]
+    pub drive: FloatParam,
+    /// How much of the feedback path is pitch shifted, so every repeat climbs or falls further.
+    #[id = "shimmer"]
+    pub shimmer: FloatParam,
+    #[id = "shimmer-shift"]
+    pub shimmer_shift: EnumParam<ShimmerShift>,
+    /// Ring modulates or frequency shifts the repeats in the feedback path. The shift adds up
+    /// with every repeat.
+    #[id = "feedback-shift"]
+    pub feedback_shift: EnumParam<FeedbackShift>,
+    #[id = "shift-freq"]
+    pub shift_freq: FloatParam,
+    /// Runs the nonlinear parts, Ring Feed's multiplication and the feedback path's waveshaping,
+    /// at a higher sample rate to reduce aliasing. This adds latency.
+    #[id = "oversampling"]
+    pub oversampling: EnumParam<Oversampling>,
+}
+
+impl FeedbackParams {
+    fn new(overrides: &ParamOverrides) -> Self {
+        Self {
+            feedback: FloatParam::new(
+                "Feedback",
+                overrides.float("feedback", 0.5),
+                FloatRange::Linear { min: 0.0, max: 1.1 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            feedback_ceiling: FloatParam::new(
+                "Feedback Ceiling",
+                overrides.float("feedback-ceiling", util::db_to_gain(0.0)),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(0.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 0.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            low_cut: FloatParam::new(
+                "Low Cut",
+                overrides.float("low-cut", 20.0),
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            high_cut: FloatParam::new(
+                "High Cut",
+                overrides.float("high-cut", 20000.0),
+                FloatRange::Skewed {
+                    min: 500.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            decay_tilt: FloatParam::new(
+                "Decay Tilt",
+                overrides.float("decay-tilt", 0.0),
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 12.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_step_size(0.1)
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_unit("dB")),
+            bell_freq: FloatParam::new(
+                "Bell Freq",
+                overrides.float("bell-freq", 1000.0),
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            crush_bits: FloatParam::new(
+                "Crush Bits",
+                overrides.float("crush-bits", Crusher::MAX_BITS),
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: Crusher::MAX_BITS,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_step_size(0.1)
+            .with_value_to_string(v2s_f32_bits())
+            .with_string_to_value(s2v_f32_bits()),
+            crush_rate: FloatParam::new(
+                "Crush Rate",
+                overrides.float("crush-rate", Crusher::MAX_RATE),
+                FloatRange::Skewed {
+                    min: 500.0,
+                    max: Crusher::MAX_RATE,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_value_to_string(v2s_f32_crush_rate())
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            bell_gain: FloatParam::new(
+                "Bell Gain",
+                overrides.float("bell-gain", 0.0),
+                FloatRange::Linear {
+                    min: -18.0,
+                    max: 18.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_step_size(0.1)
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_unit("dB")),
+            bell_q: FloatParam::new(
+                "Bell Q",
+                overrides.float("bell-q", 1.0),
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 10.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_step_size(0.01)
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_unit("Q")),
+            character: EnumParam::new(
+                "Character",
+                overrides.enumeration("character", Character::Clean),
+            ),
+            age: FloatParam::new(
+                "Age",
+                overrides.float("age", 0.3),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            saturation: EnumParam::new(
+                "Saturation",
+                overrides.enumeration("saturation", Saturation::Off),
+            ),
+            drive: FloatParam::new(
+                "Drive",
+                overrides.float("drive", util::db_to_gain(0.0)),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 24.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            shimmer: FloatParam::new(
+                "Shimmer",
+                overrides.float("shimmer", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            shimmer_shift: EnumParam::new(
+                "Shimmer Shift",
+                overrides.enumeration("shimmer-shift", ShimmerShift::OctaveUp),
+            ),
+            feedback_shift: EnumParam::new(
+                "Feedback Shift",
+                overrides.enumeration("feedback-shift", FeedbackShift::Off),
+            ),
+            shift_freq: FloatParam::new(
+                "Shift Freq",
+                overrides.float("shift-freq", 5.0),
+                FloatRange::SymmetricalSkewed {
+                    min: -1000.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                    center: 0.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            oversampling: EnumParam::new(
+                "Oversampling",
+                overrides.enumeration("oversampling", Oversampling::Off),
+            )
+            .non_automatable(),
+        }
+    }
+}
+
+/// The modulation sources, and the modulation matrix that routes them.
+#[derive(Params)]
+pub struct ModulationParams {
+    /// Follows the input's level and modulates `env_target` by up to `env_amount`. With a
+    /// negative amount loud passages get fewer repeats and quiet passages bloom.
+    #[id = "env-target"]
+    pub env_target: EnumParam<EnvelopeTarget>,
+    #[id = "env-amount"]
+    pub env_amount: FloatParam,
+    #[id = "env-attack"]
+    pub env_attack: FloatParam,
+    #[id = "env-release"]
+    pub env_release: FloatParam,
+    /// Detunes the repeats so they chorus against the dry signal. Every other channel is detuned
+    /// in the opposite direction.
+    #[id = "detune"]
+    pub detune: FloatParam,
+    /// How far the tape wow and flutter can move the read position.
+    #[id = "wow-depth"]
+    pub wow_depth: FloatParam,
+    #[id = "wow-rate"]
+    pub wow_rate: FloatParam,
+    /// The modulation LFO pushes the read position back by up to `lfo_depth`. With short delay
+    /// times this turns the delay into a chorus or a flanger.
+    #[id = "lfo-shape"]
+    pub lfo_shape: EnumParam<LfoShape>,
+    #[id = "lfo-rate"]
+    pub lfo_rate: FloatParam,
+    /// When enabled, one LFO cycle lasts `lfo_note` at the host's tempo instead of using
+    /// `lfo_rate`.
+    #[id = "lfo-sync"]
+    pub lfo_sync: BoolParam,
+    #[id = "lfo-note"]
+    pub lfo_note: EnumParam<NoteDivision>,
+    #[id = "lfo-depth"]
+    pub lfo_depth: FloatParam,
+    /// How long the LFO's output takes to follow a change. This mostly matters for the S&H
+    /// shape's jumps.
+    #[id = "lfo-slew"]
+    pub lfo_slew: FloatParam,
+    /// Seeds the random shapes, so the same seed produces the same modulation every time the LFO
+    /// restarts.
+    #[id = "lfo-seed"]
+    pub lfo_seed: IntParam,
+    /// Pans the repeats back and forth between the channels, leaving the dry signal where it is.
+    #[id = "autopan-shape"]
+    pub autopan_shape: EnumParam<LfoShape>,
+    #[id = "autopan-rate"]
+    pub autopan_rate: FloatParam,
+    /// When enabled, one autopan cycle lasts `autopan_note` at the host's tempo instead of using
+    /// `autopan_rate`.
+    #[id = "autopan-sync"]
+    pub autopan_sync: BoolParam,
+    #[id = "autopan-note"]
+    pub autopan_note: EnumParam<NoteDivision>,
+    #[id = "autopan-depth"]
+    pub autopan_depth: FloatParam,
+    /// The modulation matrix, which routes the LFO and the input envelope to other parameters.
+    #[nested(array, group = "Mod Slot")]
+    pub mod_slots: [ModSlotParams; NUM_MOD_SLOTS],
+}
+
+impl ModulationParams {
+    fn new(overrides: &ParamOverrides) -> Self {
+        Self {
+            env_target: EnumParam::new(
+                "Env Target",
+                overrides.enumeration("env-target", EnvelopeTarget::Feedback),
+            ),
+            env_amount: FloatParam::new(
+                "Env Amount",
+                overrides.float("env-amount", 0.0),
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            env_attack: FloatParam::new(
+                "Env Attack",
+                overrides.float("env-attack", 10.0),
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 500.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_ms()),
+            env_release: FloatParam::new(
+                "Env Release",
+                overrides.float("env-release", 300.0),
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_string_to_value(s2v_f32_ms()),
+            wow_depth: FloatParam::new(
+                "Wow Depth",
+                overrides.float("wow-depth", 0.0),
+                FloatRange::Linear { min: 0.0, max: 5.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_ms()),
+            detune: FloatParam::new(
+                "Detune",
+                overrides.float("detune", 0.0),
+                FloatRange::Linear {
+                    min: -50.0,
+                    max: 50.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_step_size(0.1)
+            .with_unit(" ct")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_unit("ct")),
+            wow_rate: FloatParam::new(
+                "Wow Rate",
+                overrides.float("wow-rate", 1.0),
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 10.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            lfo_shape: EnumParam::new(
+                "LFO Shape",
+                overrides.enumeration("lfo-shape", LfoShape::Sine),
+            ),
+            lfo_rate: FloatParam::new(
+                "LFO Rate",
+                overrides.float("lfo-rate", 0.5),
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            lfo_sync: BoolParam::new("LFO Sync", overrides.bool("lfo-sync", false)),
+            lfo_note: EnumParam::new(
+                "LFO Note",
+                overrides.enumeration("lfo-note", NoteDivision::Whole),
+            ),
+            lfo_depth: FloatParam::new(
+                "LFO Depth",
+                overrides.float("lfo-depth", 0.0),
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 10.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_ms()),
+            lfo_slew: FloatParam::new(
+                "LFO Slew",
+                overrides.float("lfo-slew", 0.0),
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 500.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_ms()),
+            lfo_seed: IntParam::new(
+                "LFO Seed",
+                overrides.int("lfo-seed", 1),
+                IntRange::Linear { min: 1, max: 9999 },
+            ),
+            autopan_shape: EnumParam::new(
+                "Autopan Shape",
+                overrides.enumeration("autopan-shape", LfoShape::Sine),
+            ),
+            autopan_rate: FloatParam::new(
+                "Autopan Rate",
+                overrides.float("autopan-rate", 0.5),
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            autopan_sync: BoolParam::new("Autopan Sync", overrides.bool("autopan-sync", false)),
+            autopan_note: EnumParam::new(
+                "Autopan Note",
+                overrides.enumeration("autopan-note", NoteDivision::Half),
+            ),
+            autopan_depth: FloatParam::new(
+                "Autopan Depth",
+                overrides.float("autopan-depth", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            mod_slots: std::array::from_fn(|slot| ModSlotParams::new(slot, overrides)),
+        }
+    }
+}
+
+/// How the wet signal is processed and mixed with the dry signal.
+#[derive(Params)]
+pub struct OutputParams {
+    /// The level after the dry and wet signals have been mixed.
+    #[id = "output-gain"]
+    pub output_gain: FloatParam,
+    /// The balance between the dry input and the delayed signal, crossfaded using `mix_law`.
+    #[id = "mix"]
+    pub mix: FloatParam,
+    #[id = "mix-law"]
+    pub mix_law: EnumParam<MixLaw>,
+    /// Mutes the dry signal regardless of the mix amount, for using the plugin on an effect send.
+    #[id = "kill-dry"]
+    pub kill_dry: BoolParam,
+    /// Compensates the output for the level the mix and the feedback are estimated to add or
+    /// take away, so settings can be compared without the louder one sounding better.
+    #[id = "auto-gain"]
+    pub auto_gain: BoolParam,
+    /// How much the repeats are turned down while the key signal is above the threshold.
+    #[id = "duck-amount"]
+    pub duck_amount: FloatParam,
+    #[id = "duck-threshold"]
+    pub duck_threshold: FloatParam,
+    /// Whether the ducker listens to the plugin's own input or to the sidechain input.
+    #[id = "duck-source"]
+    pub duck_source: EnumParam<DuckSource>,
+    /// How quickly the ducker reacts to the key signal, and how quickly the repeats come back
+    /// once it has been below the threshold for `duck_hold`.
+    #[id = "duck-attack"]
+    pub duck_attack: FloatParam,
+    #[id = "duck-release"]
+    pub duck_release: FloatParam,
+    #[id = "duck-hold"]
+    pub duck_hold: FloatParam,
+    /// Delays the audio so the ducker can react before the key signal's transients are heard.
+    /// This adds to the plugin's latency.
+    #[id = "duck-lookahead"]
+    pub duck_lookahead: FloatParam,
+    /// The stereo width of the repeats. At 0% they're summed to mono, and above 100% their
+    /// sides are exaggerated.
+    #[id = "width"]
+    pub width: FloatParam,
+    /// Folds the repeats below `mono_below` to mono, so wide delays stay mono compatible in
+    /// the low end.
+    #[id = "mono-low"]
+    pub mono_low: BoolParam,
+    #[id = "mono-below"]
+    pub mono_below: FloatParam,
+    /// Smears the repeats into a wash with a chain of modulated allpass filters on the wet
+    /// signal.
+    #[id = "diffusion"]
+    pub diffusion: FloatParam,
+    /// Runs the wet signal through a small reverb. This costs some CPU, so it's only processed
+    /// while enabled.
+    #[id = "reverb"]
+    pub reverb: BoolParam,
+    /// Scales the reverb's delay times, from a small room to a large hall.
+    #[id = "reverb-size"]
+    pub reverb_size: FloatParam,
+    /// How long it takes the reverb's tail to decay by 60 dB.
+    #[id = "reverb-decay"]
+    pub reverb_decay: FloatParam,
+    /// How much of the wet signal is replaced by the reverb.
+    #[id = "reverb-mix"]
+    pub reverb_mix: FloatParam,
+}
+
+impl OutputParams {
+    fn new(overrides: &ParamOverrides) -> Self {
+        Self {
+            output_gain: FloatParam::new(
+                "Output Gain",
+                overrides.float("output-gain", util::db_to_gain(0.0)),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(30.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            mix: FloatParam::new(
+                "Mix",
+                overrides.float("mix", 0.5),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            mix_law: EnumParam::new(
+                "Mix Law",
+                overrides.enumeration("mix-law", MixLaw::EqualPower),
+            ),
+            kill_dry: BoolParam::new("Kill Dry", overrides.bool("kill-dry", false))
+                .non_automatable(),
+            auto_gain: BoolParam::new("Auto Gain", overrides.bool("auto-gain", false)),
+            duck_amount: FloatParam::new(
+                "Duck Amount",
+                overrides.float("duck-amount", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            duck_threshold: FloatParam::new(
+                "Duck Threshold",
+                overrides.float("duck-threshold", -30.0),
+                FloatRange::Linear {
+                    min: -60.0,
+                    max: 0.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_unit("dB")),
+            duck_source: EnumParam::new(
+                "Duck Source",
+                overrides.enumeration("duck-source", DuckSource::Input),
+            ),
+            duck_attack: FloatParam::new(
+                "Duck Attack",
+                overrides.float("duck-attack", 5.0),
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 100.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_ms()),
+            duck_release: FloatParam::new(
+                "Duck Release",
+                overrides.float("duck-release", 250.0),
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_string_to_value(s2v_f32_ms()),
+            duck_hold: FloatParam::new(
+                "Duck Hold",
+                overrides.float("duck-hold", 0.0),
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_string_to_value(s2v_f32_ms()),
+            duck_lookahead: FloatParam::new(
+                "Duck Lookahead",
+                overrides.float("duck-lookahead", 0.0),
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: DUCK_MAX_LOOKAHEAD_MS,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_ms())
+            .non_automatable(),
+            width: FloatParam::new(
+                "Width",
+                overrides.float("width", 1.0),
+                FloatRange::Linear { min: 0.0, max: 2.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            mono_low: BoolParam::new("Mono Low End", overrides.bool("mono-low", false)),
+            mono_below: FloatParam::new(
+                "Mono Below",
+                overrides.float("mono-below", 120.0),
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            diffusion: FloatParam::new(
+                "Diffusion",
+                overrides.float("diffusion", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            reverb: BoolParam::new("Reverb", overrides.bool("reverb", false)),
+            reverb_size: FloatParam::new(
+                "Reverb Size",
+                overrides.float("reverb-size", 0.5),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(200.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            reverb_decay: FloatParam::new(
+                "Reverb Decay",
+                overrides.float("reverb-decay", 2.0),
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" s")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_seconds()),
+            reverb_mix: FloatParam::new(
+                "Reverb Mix",
+                overrides.float("reverb-mix", 0.3),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+/// Controls for playing the delay live, like freezing, looping and stuttering.
+#[derive(Params)]
+pub struct PerformanceParams {
+    #[id = "transport-mode"]
+    pub transport_mode: EnumParam<TransportMode>,
+    /// Stops recording new audio and loops the buffer's current contents indefinitely.
+    #[id = "freeze"]
+    pub freeze: BoolParam,
+    /// Holds the repeats indefinitely while it's enabled, for dub style throws. Unlike freeze
+    /// the input keeps going into the delay. The feedback is raised to exactly unity, and the
+    /// feedback limiter keeps anything that builds up on top below the ceiling.
+    #[id = "infinite"]
+    pub infinite: BoolParam,
+    /// Fades out and empties the delay lines when this is switched on, to cut off runaway
+    /// feedback without bypassing the plugin.
+    #[id = "clear"]
+    pub clear: BoolParam,
+    /// Store a compressed copy of the frozen loop in the plugin state.
+    #[id = "save-frozen"]
+    pub save_frozen: BoolParam,
+    /// Turns the delay into a looper. The loop length is the delay time, or the length of the
+    /// last recording when `loop_punch_in` is enabled.
+    #[id = "looper"]
+    pub looper: EnumParam<LooperState>,
+    #[id = "loop-punch-in"]
+    pub loop_punch_in: BoolParam,
+    /// Erases the loop when this is switched on.
+    #[id = "loop-clear"]
+    pub loop_clear: BoolParam,
+    /// Beat repeat. While the host is playing, every `stutter_slice` of the timeline has a
+    /// `stutter_chance` chance of being replaced by repeats of the slice that started
+    /// `stutter_capture` beats earlier.
+    #[id = "stutter"]
+    pub stutter: BoolParam,
+    #[id = "stutter-capture"]
+    pub stutter_capture: IntParam,
+    #[id = "stutter-slice"]
+    pub stutter_slice: EnumParam<NoteDivision>,
+    #[id = "stutter-chance"]
+    pub stutter_chance: FloatParam,
+    /// Plays the repeats faster or slower, which also shifts their pitch.
+    #[id = "stutter-pitch"]
+    pub stutter_pitch: FloatParam,
+    /// Steps through the sequence while the host is playing, with every step setting the delay
+    /// time and whether the input is sent into the delay.
+    #[id = "sequencer"]
+    pub sequencer: BoolParam,
+    /// How long every step lasts.
+    #[id = "sequencer-rate"]
+    pub sequencer_rate: EnumParam<NoteDivision>,
+    /// The number of steps before the sequence starts over.
+    #[id = "sequencer-length"]
+    pub sequencer_length: IntParam,
+}
+
+impl PerformanceParams {
+    fn new(overrides: &ParamOverrides) -> Self {
+        Self {
+            transport_mode: EnumParam::new(
+                "On Transport",
+                overrides.enumeration("transport-mode", TransportMode::KeepTail),
+            ),
+            freeze: BoolParam::new("Freeze", overrides.bool("freeze", false)),
+            infinite: BoolParam::new("Infinite", overrides.bool("infinite", false)),
+            clear: BoolParam::new("Clear", overrides.bool("clear", false)),
+            save_frozen: BoolParam::new("Save Frozen Buffer", overrides.bool("save-frozen", false))
+                .non_automatable(),
+            looper: EnumParam::new("Looper", overrides.enumeration("looper", LooperState::Off)),
+            loop_punch_in: BoolParam::new("Loop Punch In", overrides.bool("loop-punch-in", true)),
+            loop_clear: BoolParam::new("Loop Clear", overrides.bool("loop-clear", false)),
+            stutter: BoolParam::new("Stutter", overrides.bool("stutter", false)),
+            stutter_capture: IntParam::new(
+                "Stutter Capture",
+                overrides.int("stutter-capture", 1),
+                IntRange::Linear { min: 1, max: 16 },
+            )
+            .with_value_to_string(Arc::new(|beats| match beats {
+                1 => String::from("1 beat"),
+                _ => format!("{beats} beats"),
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                let string = string.trim().to_lowercase();
+                string
+                    .trim_end_matches("beats")
+                    .trim_end_matches("beat")
+                    .trim()
+                    .parse()
+                    .ok()
+            })),
+            stutter_slice: EnumParam::new(
+                "Stutter Slice",
+                overrides.enumeration("stutter-slice", NoteDivision::Sixteenth),
+            ),
+            stutter_chance: FloatParam::new(
+                "Stutter Chance",
+                overrides.float("stutter-chance", 0.5),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            stutter_pitch: FloatParam::new(
+                "Stutter Pitch",
+                overrides.float("stutter-pitch", 0.0),
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_step_size(1.0)
+            .with_unit(" st")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_string_to_value(s2v_f32_unit("st")),
+            sequencer: BoolParam::new("Sequencer", overrides.bool("sequencer", false)),
+            sequencer_rate: EnumParam::new(
+                "Sequencer Rate",
+                overrides.enumeration("sequencer-rate", NoteDivision::Sixteenth),
+            ),
+            sequencer_length: IntParam::new(
+                "Sequencer Length",
+                overrides.int("sequencer-length", NUM_STEPS as i32),
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_STEPS as i32,
+                },
+            ),
+        }
+    }
+}
+
+/// A second, simpler delay engine with its own delay time, feedback, filters and pan, which
+/// runs next to or after the main delay.
+#[derive(Params)]
+pub struct EngineParams {
+    #[id = "enabled"]
+    pub enabled: BoolParam,
+    #[id = "time"]
+    pub time: FloatParam,
+    /// When enabled, the delay time follows the host's tempo using `note` instead of `time`.
+    #[id = "sync"]
+    pub sync: BoolParam,
+    #[id = "note"]
+    pub note: EnumParam<NoteDivision>,
+    #[id = "feedback"]
+    pub feedback: FloatParam,
+    #[id = "low-cut"]
+    pub low_cut: FloatParam,
+    #[id = "high-cut"]
+    pub high_cut: FloatParam,
+    /// Pans the engine's repeats towards the first channel when negative, and towards the
+    /// second channel when positive.
+    #[id = "pan"]
+    pub pan: FloatParam,
+    /// The engine's output level, stored as linear gain.
+    #[id = "level"]
+    pub level: FloatParam,
+}
+
+impl EngineParams {
+    /// Create an engine's parameters. `prefix` is the engine's ID prefix, which is also used in
+    /// its parameter names and to look up overrides.
+    fn new(prefix: &str, name: &str, overrides: &ParamOverrides) -> Self {
+        let id = |id: &str| format!("{prefix}_{id}");
+        let sync = overrides.bool(&id("sync"), false);
+        let note = overrides.enumeration(&id("note"), NoteDivision::EighthDotted);
+        let synced_time = SyncedTime::new(sync, note);
+
+        Self {
+            enabled: BoolParam::new(name.to_string(), overrides.bool(&id("enabled"), false)),
+            time: FloatParam::new(
+                format!("{name} Time"),
+                overrides.float(&id("time"), 375.0),
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: ENGINE_B_MAX_SECONDS * 1000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(synced_time.v2s_f32_ms())
+            .with_string_to_value(s2v_f32_ms()),
+            sync: BoolParam::new(format!("{name} Sync"), sync)
+                .with_callback(synced_time.sync_callback()),
+            note: EnumParam::new(format!("{name} Note"), note)
+                .with_callback(synced_time.note_callback()),
+            feedback: FloatParam::new(
+                format!("{name} Feedback"),
+                overrides.float(&id("feedback"), 0.4),
+                FloatRange::Linear { min: 0.0, max: 1.1 },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            low_cut: FloatParam::new(
+                format!("{name} Low Cut"),
+                overrides.float(&id("low-cut"), 20.0),
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            high_cut: FloatParam::new(
+                format!("{name} High Cut"),
+                overrides.float(&id("high-cut"), 20000.0),
+                FloatRange::Skewed {
+                    min: 500.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            pan: FloatParam::new(
+                format!("{name} Pan"),
+                overrides.float(&id("pan"), 0.0),
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_value_to_string(formatters::v2s_f32_panning())
+            .with_string_to_value(formatters::s2v_f32_panning()),
+            level: FloatParam::new(
+                format!("{name} Level"),
+                overrides.float(&id("level"), util::db_to_gain(0.0)),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-36.0),
+                    max: util::db_to_gain(6.0),
+                    factor: FloatRange::gain_skew_factor(-36.0, 6.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+        }
+    }
+}
+
+/// How the second delay engine is connected to the main one.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EngineRouting {
+    /// Delay B repeats the main delay's repeats.
+    #[id = "serial"]
+    #[name = "A > B"]
+    Serial,
+    /// Both engines delay the input side by side.
+    #[id = "parallel"]
+    #[name = "A + B"]
+    Parallel,
+    /// Every repeat alternates between the engines. The main delay's repeats are fed into delay
+    /// B, and delay B's are fed back into the main delay.
+    #[id = "ping"]
+    #[name = "A <> B"]
+    Ping,
+}
+
+/// A slot in the modulation matrix. Nesting these as an array appends the slot number to the
+/// parameter IDs, so the first slot's source is `mod-source_1`.
+#[derive(Params)]
+pub struct ModSlotParams {
+    #[id = "mod-source"]
+    pub source: EnumParam<ModSource>,
+    #[id = "mod-destination"]
+    pub destination: EnumParam<ModDestination>,
+    /// How far the source moves the destination, in either direction.
+    #[id = "mod-depth"]
+    pub depth: FloatParam,
+}
+
+impl ModSlotParams {
+    fn new(slot: usize, overrides: &ParamOverrides) -> Self {
+        let number = slot + 1;
+        Self {
+            source: EnumParam::new(
+                format!("Mod {number} Source"),
+                overrides.enumeration(&format!("mod-source_{number}"), ModSource::Lfo),
+            ),
+            destination: EnumParam::new(
+                format!("Mod {number} Destination"),
+                overrides.enumeration(&format!("mod-destination_{number}"), ModDestination::Off),
+            ),
+            depth: FloatParam::new(
+                format!("Mod {number} Depth"),
+                overrides.float(&format!("mod-depth_{number}"), 0.0),
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+/// The modulation matrix's sources.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ModSource {
+    #[id = "lfo"]
+    #[name = "LFO"]
+    Lfo,
+    /// The input's envelope, using the envelope follower's attack and release times.
+    #[id = "envelope"]
+    Envelope,
+}
+
+/// The parameters the modulation matrix can modulate.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ModDestination {
+    #[id = "off"]
+    Off,
+    /// Scales the delay time by up to an octave in either direction.
+    #[id = "delay-time"]
+    #[name = "Delay Time"]
+    DelayTime,
+    #[id = "feedback"]
+    Feedback,
+    /// Moves the high cut filter by up to four octaves in either direction.
+    #[id = "cutoff"]
+    Cutoff,
+    /// Pans the repeats between the first two channels.
+    #[id = "pan"]
+    Pan,
+}
+
+/// Interpolation algorithms for reading between samples.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Interpolation {
+    #[id = "linear"]
+    Linear,
+    /// Four-point Hermite interpolation. This is smoother at a slightly higher cost.
+    #[id = "cubic"]
+    Cubic,
+}
+
+impl Interpolation {
+    /// Read from a fractional position in a ring buffer. The position is wrapped to the buffer's
+    /// length.
+    pub(crate) fn read(self, buffer: &[Sample], pos: f32) -> Sample {
+        let len = buffer.len() as isize;
+        self.read_with(pos, |index| buffer[index.rem_euclid(len) as usize])
+    }
+
+    /// Interpolate between the samples around a fractional position. `sample` returns the sample
+    /// at a whole index, and it's up to that function to wrap indices that are out of range.
+    pub(crate) fn read_with(self, pos: f32, sample: impl Fn(isize) -> Sample) -> Sample {
+        let index = pos.floor() as isize;
+        let t = (pos - pos.floor()) as Sample;
+        let y0 = sample(index);
+        let y1 = sample(index + 1);
+
+        match self {
+            Interpolation::Linear => y0 + (y1 - y0) * t,
+            Interpolation::Cubic => {
+                let y_1 = sample(index - 1);
+                let y2 = sample(index + 2);
+
+                let c1 = 0.5 * (y1 - y_1);
+                let c2 = y_1 - 2.5 * y0 + 2.0 * y1 - 0.5 * y2;
+                let c3 = 0.5 * (y2 - y_1) + 1.5 * (y0 - y1);
+
+                ((c3 * t + c2) * t + c1) * t + y0
+            }
+        }
+    }
+}
+
+/// The vintage delay the feedback path emulates. Every repeat passes through the model's band
+/// limiting, companding, converters, noise and saturation.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Character {
+    #[id = "clean"]
+    Clean,
+    /// An early digital delay with 12-bit converters running at 31.25 kHz, and a compander to
+    /// make the most of those bits.
+    #[id = "digital-12"]
+    #[name = "12-Bit 31 kHz"]
+    Digital12,
+    /// A bucket brigade delay, which is dark, companded, and noisy.
+    #[id = "bbd"]
+    #[name = "BBD"]
+    Bbd,
+    /// A tape echo, with gentle saturation and a little hiss.
+    #[id = "tape"]
+    Tape,
+}
+
+/// What the feedback path's shifter does to the repeats.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FeedbackShift {
+    #[id = "off"]
+    Off,
+    /// Ring modulates the repeats with a sine at the shift frequency.
+    #[id = "ring"]
+    #[name = "Ring Mod"]
+    Ring,
+    /// Moves every frequency of the repeats by the shift frequency, so the echoes spiral up or
+    /// down like a barberpole.
+    #[id = "frequency"]
+    #[name = "Frequency Shift"]
+    Frequency,
+}
+
+/// The intervals the shimmer pitch shifter can shift the repeats by.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ShimmerShift {
+    #[id = "octave-up"]
+    #[name = "+12 st"]
+    OctaveUp,
+    #[id = "fifth-up"]
+    #[name = "+7 st"]
+    FifthUp,
+    #[id = "octave-down"]
+    #[name = "-12 st"]
+    OctaveDown,
+}
+
+impl ShimmerShift {
+    /// The playback rate ratio for this interval.
+    pub(crate) fn ratio(self) -> f32 {
+        let semitones = match self {
+            ShimmerShift::OctaveUp => 12.0,
+            ShimmerShift::FifthUp => 7.0,
+            ShimmerShift::OctaveDown => -12.0,
+        };
+
+        2.0f32.powf(semitones / 12.0)
+    }
+}
+
+/// The crossfade curves the mix parameter can use.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MixLaw {
+    /// The gains add up to one, which sounds best when the dry and wet signals are correlated.
+    #[id = "linear"]
+    Linear,
+    /// The gains' squares add up to one, so 50% keeps the perceived loudness roughly the same as
+    /// either extreme for uncorrelated signals.
+    #[id = "equal-power"]
+    #[name = "Equal Power"]
+    EqualPower,
+    /// Both signals stay at full level at 50%, and only the other one is faded out towards
+    /// either extreme.
+    #[id = "transition"]
+    Transition,
+}
+
+impl MixLaw {
+    /// The dry and wet gains for a mix amount between zero and one.
+    pub(crate) fn gains(self, mix: f32) -> (f32, f32) {
+        match self {
+            MixLaw::Linear => (1.0 - mix, mix),
+            MixLaw::EqualPower => ((mix * FRAC_PI_2).cos(), (mix * FRAC_PI_2).sin()),
+            MixLaw::Transition => (((1.0 - mix) * 2.0).min(1.0), (mix * 2.0).min(1.0)),
+        }
+    }
+}
+
+/// The looper's transport.
+#[derive(Enum, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum LooperState {
+    /// The plugin is a regular delay.
+    #[default]
+    #[id = "off"]
+    Off,
+    /// Records the input over the loop. Only the dry signal is heard while recording.
+    #[id = "record"]
+    Record,
+    /// Adds the input to the loop, with the feedback amount setting how much of the existing
+    /// loop is kept on every pass.
+    #[id = "overdub"]
+    Overdub,
+    /// Plays the loop back without changing it.
+    #[id = "play"]
+    Play,
+}
+
+/// The waveforms the modulation LFO can use.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LfoShape {
+    #[id = "sine"]
+    Sine,
+    #[id = "triangle"]
+    Triangle,
+    /// Smoothly moves to a new random value every cycle.
+    #[id = "random"]
+    Random,
+    /// Jumps to a new random value every cycle and holds it.
+    #[id = "sample-and-hold"]
+    #[name = "S&H"]
+    SampleAndHold,
+}
+
+/// How much the nonlinear parts of the signal chain are oversampled.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Oversampling {
+    #[id = "off"]
+    Off,
+    #[id = "2x"]
+    #[name = "2x"]
+    X2,
+    #[id = "4x"]
+    #[name = "4x"]
+    X4,
+}
+
+impl Oversampling {
+    pub(crate) fn factor(self) -> usize {
+        match self {
+            Oversampling::Off => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+        }
+    }
+}
+
+/// The signal that drives the ducker.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DuckSource {
+    #[id = "input"]
+    Input,
+    #[id = "sidechain"]
+    Sidechain,
+}
+
+/// The parameter the input's envelope modulates.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EnvelopeTarget {
+    #[id = "feedback"]
+    Feedback,
+    #[id = "mix"]
+    Mix,
+    /// Scales the delay time by up to an octave in either direction, bending the pitch of the
+    /// repeats while it changes.
+    #[id = "time"]
+    Time,
+}
+
+/// The different ways the delayed signal is read back and combined with the input.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DelayMode {
+    /// A plain delay.
+    #[id = "echo"]
+    Echo,
+    /// A delay that pitch shifts up and then down as the buffer fills.
+    #[id = "glitch"]
+    Glitch,
+    /// Outputs only the delayed signal, replacing the input.
+    #[id = "replace"]
+    Replace,
+    /// Multiplies the input with the delayed signal for a ring modulator-like distortion.
+    #[id = "ring-feed"]
+    #[name = "Ring Feed"]
+    RingFeed,
+    /// Adds a second tap that's controlled by the delay parameter.
+    #[id = "dual-tap"]
+    #[name = "Dual Tap"]
+    DualTap,
+    /// Jumps the read position around every n-th sample, set with the mode6_ratio parameter.
+    #[id = "shuffle"]
+    Shuffle,
+    /// The dual tap and the glitch and shuffle read position movements combined.
+    #[id = "chaos"]
+    Chaos,
+    /// A delay where every repeat alternates between the left and the right channel.
+    #[id = "ping-pong"]
+    #[name = "Ping-Pong"]
+    PingPong,
+    /// Taps spread over a bar following a Euclidean rhythm, set with the euclid parameters. The
+    /// delay time is one bar at the host's tempo so the pattern repeats every bar, or the regular
+    /// delay time when the host doesn't provide a tempo.
+    #[id = "euclidean"]
+    Euclidean,
+    /// A Karplus-Strong string resonator. The delay time is the period of the pitch parameter,
+    /// or of the last MIDI note with note tuning enabled, and the input plucks the string.
+    #[id = "karplus"]
+    #[name = "Karplus-Strong"]
+    Karplus,
+    /// A comb filter tuned like the Karplus-Strong mode, with a selectable feedback polarity and
+    /// damping inside the loop.
+    #[id = "comb"]
+    Comb,
+    /// Very short delays without feedback for doubling a mono source. Every other channel is
+    /// delayed by less and the channels are detuned against each other, set with the Haas time
+    /// parameter.
+    #[id = "doubler"]
+    #[name = "Haas Doubler"]
+    Doubler,
+    /// Up to eight freely placed taps, set with the tap parameters or on the editor's tap
+    /// timeline. Like the Euclidean mode the taps span a bar at the host's tempo, or the regular
+    /// delay time when the host doesn't provide a tempo.
+    #[id = "multi-tap"]
+    #[name = "Multi-Tap"]
+    MultiTap,
+}
+
+/// The sign of the comb filter's feedback.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CombPolarity {
+    /// Resonates at the pitch and all of its harmonics.
+    #[id = "positive"]
+    Positive,
+    /// Resonates an octave lower, at only the odd harmonics, for a hollower sound.
+    #[id = "negative"]
+    Negative,
+}
+
+/// What happens when the delay time changes while audio is running.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TimeChangeMode {
+    /// Crossfade from the old read position to the new one.
+    #[id = "crossfade"]
+    #[name = "Digital (Crossfade)"]
+    Crossfade,
+    /// Slew the read position to the new delay time like a tape or analog delay would, bending
+    /// the pitch of the repeats.
+    #[id = "glide"]
+    #[name = "Analog (Glide)"]
+    Glide,
+}
+
+/// What happens to the delay lines when the host's transport starts, stops, or jumps to another
+/// position, like at the end of a loop.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TransportMode {
+    /// The repeats keep ringing out.
+    #[id = "keep-tail"]
+    #[name = "Keep Tail"]
+    KeepTail,
+    /// The delay lines are cleared like with the clear trigger.
+    #[id = "clear"]
+    Clear,
+    /// The write position jumps to the timeline position when playback starts or jumps, so every
+    /// position in the buffer belongs to the same place in the song. The glitchy modes then jump
+    /// around the same way on every pass.
+    #[id = "resync"]
+    Resync,
+}
+
+/// The waveshapers available for the feedback path's saturation stage.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Saturation {
+    #[id = "off"]
+    Off,
+    /// Symmetrical soft clipping.
+    #[id = "tanh"]
+    Tanh,
+    /// Asymmetrical clipping where the negative half clips earlier, adding even harmonics.
+    #[id = "diode"]
+    Diode,
+    /// Folds the signal back on itself instead of clipping it.
+    #[id = "foldback"]
+    Foldback,
+}
+
+impl Saturation {
+    /// Apply the waveshaper to `sample` after amplifying it by `drive`. The signal is passed
+    /// through untouched when saturation is disabled.
+    pub(crate) fn process(self, sample: Sample, drive: f32) -> Sample {
+        let driven = sample * drive as Sample;
+        match self {
+            Saturation::Off => sample,
+            Saturation::Tanh => driven.tanh(),
+            Saturation::Diode => {
+                if driven >= 0.0 {
+                    1.0 - (-driven).exp()
+                } else {
+                    -0.5 * (1.0 - (2.0 * driven).exp())
+                }
+            }
+            // A triangle wave with a period of four, so it's the identity between -1 and 1
+            Saturation::Foldback => {
+                1.0 - 4.0 * (((driven + 1.0) / 4.0).rem_euclid(1.0) - 0.5).abs()
+            }
+        }
+    }
+}
+
+/// Note values for tempo synced delay times.
+#[derive(Enum, Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NoteDivision {
+    #[id = "1-1"]
+    #[name = "1/1"]
+    Whole,
+    #[id = "1-1d"]
+    #[name = "1/1 dotted"]
+    WholeDotted,
+    #[id = "1-1t"]
+    #[name = "1/1 triplet"]
+    WholeTriplet,
+    #[id = "1-2"]
+    #[name = "1/2"]
+    Half,
+    #[id = "1-2d"]
+    #[name = "1/2 dotted"]
+    HalfDotted,
+    #[id = "1-2t"]
+    #[name = "1/2 triplet"]
+    HalfTriplet,
+    #[id = "1-4"]
+    #[name = "1/4"]
+    Quarter,
+    #[id = "1-4d"]
+    #[name = "1/4 dotted"]
+    QuarterDotted,
+    #[id = "1-4t"]
+    #[name = "1/4 triplet"]
+    QuarterTriplet,
+    #[id = "1-8"]
+    #[name = "1/8"]
+    Eighth,
+    #[id = "1-8d"]
+    #[name = "1/8 dotted"]
+    EighthDotted,
+    #[id = "1-8t"]
+    #[name = "1/8 triplet"]
+    EighthTriplet,
+    #[id = "1-16"]
+    #[name = "1/16"]
+    Sixteenth,
+    #[id = "1-16d"]
+    #[name = "1/16 dotted"]
+    SixteenthDotted,
+    #[id = "1-16t"]
+    #[name = "1/16 triplet"]
+    SixteenthTriplet,
+    #[id = "1-32"]
+    #[name = "1/32"]
+    ThirtySecond,
+    #[id = "1-32d"]
+    #[name = "1/32 dotted"]
+    ThirtySecondDotted,
+    #[id = "1-32t"]
+    #[name = "1/32 triplet"]
+    ThirtySecondTriplet,
+}
+
+impl NoteDivision {
+    /// The length of this note value in quarter notes.
+    pub fn beats(self) -> f32 {
+        match self {
+            NoteDivision::Whole => 4.0,
+            NoteDivision::WholeDotted => 4.0 * 1.5,
+            NoteDivision::WholeTriplet => 4.0 * 2.0 / 3.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::HalfDotted => 2.0 * 1.5,
+            NoteDivision::HalfTriplet => 2.0 * 2.0 / 3.0,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::QuarterDotted => 1.5,
+            NoteDivision::QuarterTriplet => 2.0 / 3.0,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::EighthDotted => 0.5 * 1.5,
+            NoteDivision::EighthTriplet => 0.5 * 2.0 / 3.0,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::SixteenthDotted => 0.25 * 1.5,
+            NoteDivision::SixteenthTriplet => 0.25 * 2.0 / 3.0,
+            NoteDivision::ThirtySecond => 0.125,
+            NoteDivision::ThirtySecondDotted => 0.125 * 1.5,
+            NoteDivision::ThirtySecondTriplet => 0.125 * 2.0 / 3.0,
+        }
+    }
+}
+
+impl Default for MyplugParams {
+    fn default() -> Self {
+        Self::with_overrides(&ParamOverrides::default())
+    }
+}
+
+impl MyplugParams {
+    /// Create the parameters with the default values of the parameters in `overrides` replaced.
+    /// This is how parameters are set when processing audio without a host.
+    pub fn with_overrides(overrides: &ParamOverrides) -> Self {
+        Self {
+            editor_state: EguiState::from_size(400, 600),
+            ui_scale: Arc::new(RwLock::new(1.0)),
+            preset_name: Arc::new(RwLock::new(String::new())),
+            frozen_buffer: Arc::new(Mutex::new(FrozenBuffer::default())),
+            cc_mappings: Arc::new(RwLock::new(BTreeMap::new())),
+            sequence: Arc::new(RwLock::new(StepSequence::default())),
+            ab_compare: Arc::new(RwLock::new(AbCompare::default())),
+            randomizer: Arc::new(RwLock::new(Randomizer::default())),
+            tap_patterns: Arc::new(RwLock::new(TapPatterns::default())),
+            preset_loaded: Arc::new(AtomicBool::new(false)),
+
+            bypass: BoolParam::new("Bypass", overrides.bool("bypass", false)).make_bypass(),
+            spillover: BoolParam::new("Spillover", overrides.bool("spillover", true))
+                .non_automatable(),
+
+            // This gain is stored as linear gain. NIH-plug comes with useful conversion functions
+            // to treat these kinds of parameters as if we were dealing with decibels. Storing this
+            // as decibels is easier to work with, but requires a conversion for every sample.
+            input_gain: FloatParam::new(
+                "Input Gain",
+                overrides.float("gain", util::db_to_gain(0.0)),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(30.0),
+                    // This makes the range appear as if it was linear when displaying the values as
+                    // decibels
+                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            delay_a: DelayParams::new(overrides),
+            feedback_path: FeedbackParams::new(overrides),
+            modulation: ModulationParams::new(overrides),
+            output: OutputParams::new(overrides),
+            performance: PerformanceParams::new(overrides),
+            engine_routing: EnumParam::new(
+                "Engine Routing",
+                overrides.enumeration("engine-routing", EngineRouting::Parallel),
+            ),
+            engine_b: EngineParams::new("b", "Delay B", overrides),
+        }
+    }
+}
+
+/// Convert the state saved by the first versions of the plugin, back when it still used the
+/// template's CLAP ID and VST3 class ID, which [`crate::LegacyMyplug`] is still registered with.
+/// Those stored the delay times as integer parameters and the mode as a number from 1 to 7, which
+/// would otherwise be dropped or read as the wrong mode.
+///
+/// This keeps those sessions loading, but it doesn't make them sound the same. The old `time` and
+/// `delay` values from 1 to 1000 weren't milliseconds but per-mille factors that scaled the read
+/// index (`iterrepeats * time / 1000`), which no fixed delay time reproduces. They carry over as
+/// the same number of milliseconds, which at least stays within the delay time's range.
+pub fn migrate_state(state: &mut PluginState) {
+    for id in ["delay", "time"] {
+        if let Some(ParamValue::I32(ms)) = state.params.get(id) {
+            let ms = *ms as f32;
+            state.params.insert(id.to_string(), ParamValue::F32(ms));
+        }
+    }
+
+    if let Some(ParamValue::I32(mode)) = state.params.get("mode") {
+        let id = usize::try_from(*mode - 1)
+            .ok()
+            .and_then(|index| DelayMode::ids()?.get(index))
+            .copied()
+            .unwrap_or("echo");
+        state
+            .params
+            .insert("mode".to_string(), ParamValue::String(id.to_string()));
+    }
+}
+
+/// Parameter values keyed by parameter ID that replace the parameters' default values. Values
+/// are plain values, so gains are linear unless they're suffixed with `dB`, booleans are `true`
+/// or `false`, and enums use their ID or name.
+#[derive(Debug, Default)]
+pub struct ParamOverrides {
+    values: BTreeMap<String, String>,
+    /// The IDs of values that could not be parsed.
+    invalid: RefCell<Vec<String>>,
+}
+
+impl ParamOverrides {
+    pub fn insert(&mut self, id: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(id.into(), value.into());
+    }
+
+    /// Describe the overrides that didn't apply to any of `params`, either because the ID is
+    /// unknown or because the value could not be parsed.
+    pub fn errors(&self, params: &MyplugParams) -> Vec<String> {
+        let known_ids: Vec<String> = params
+            .param_map()
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect();
+        let unknown = self
+            .values
+            .keys()
+            .filter(|id| !known_ids.contains(id))
+            .map(|id| format!("unknown parameter '{id}'"));
+        let invalid = self
+            .invalid
+            .borrow()
+            .iter()
+            .map(|id| format!("invalid value '{}' for parameter '{id}'", self.values[id]))
+            .collect::<Vec<_>>();
+
+        unknown.chain(invalid).collect()
+    }
+
+    fn parse<T>(&self, id: &str, default: T, parse: impl FnOnce(&str) -> Option<T>) -> T {
+        match self.values.get(id) {
+            Some(value) => parse(value.trim()).unwrap_or_else(|| {
+                self.invalid.borrow_mut().push(id.to_string());
+                default
+            }),
+            None => default,
+        }
+    }
+
+    fn float(&self, id: &str, default: f32) -> f32 {
+        self.parse(id, default, |value| {
+            match value
+                .strip_suffix("dB")
+                .or_else(|| value.strip_suffix("db"))
+            {
+                Some(db) => db.trim().parse().ok().map(util::db_to_gain),
+                None => value.parse().ok(),
+            }
+        })
+    }
+
+    fn int(&self, id: &str, default: i32) -> i32 {
+        self.parse(id, default, |value| value.parse().ok())
+    }
+
+    fn bool(&self, id: &str, default: bool) -> bool {
+        self.parse(id, default, |value| value.parse().ok())
+    }
+
+    fn enumeration<T: Enum>(&self, id: &str, default: T) -> T {
+        self.parse(id, default, |value| {
+            let matches = |names: &[&str]| {
+                names
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(value))
+            };
+            T::ids()
+                .and_then(matches)
+                .or_else(|| matches(T::variants()))
+                .map(T::from_index)
+        })
+    }
+}
+
+/// The range for the delay time parameters in milliseconds. This is skewed so the short times
+/// get most of the range.
+fn delay_time_range() -> FloatRange {
+    FloatRange::Skewed {
+        min: 1.0,
+        max: MAX_DELAY_SECONDS * 1000.0,
+        factor: FloatRange::skew_factor(-2.0),
+    }
+}
+
+/// A parameter's string to value conversion function.
+type StringToValue = dyn Fn(&str) -> Option<f32> + Send + Sync;
+
+/// A parameter's value to string conversion function.
+type ValueToString = dyn Fn(f32) -> String + Send + Sync;
+
+/// Mirrors a delay's sync switch and note value, so its time parameter can show the note value
+/// that's used instead of the time while it's synced.
+struct SyncedTime {
+    sync: AtomicBool,
+    /// The note value's index in `NoteDivision::variants()`.
+    note: AtomicUsize,
+}
+
+impl SyncedTime {
+    fn new(sync: bool, note: NoteDivision) -> Arc<Self> {
+        Arc::new(Self {
+            sync: AtomicBool::new(sync),
+            note: AtomicUsize::new(note.to_index()),
+        })
+    }
+
+    fn sync_callback(self: &Arc<Self>) -> Arc<dyn Fn(bool) + Send + Sync> {
+        let synced = self.clone();
+        Arc::new(move |sync| synced.sync.store(sync, Ordering::Relaxed))
+    }
+
+    fn note_callback(self: &Arc<Self>) -> Arc<dyn Fn(NoteDivision) + Send + Sync> {
+        let synced = self.clone();
+        Arc::new(move |note| synced.note.store(note.to_index(), Ordering::Relaxed))
+    }
+
+    /// Format a delay time in milliseconds, or as the note value while synced.
+    fn v2s_f32_ms(self: &Arc<Self>) -> Arc<ValueToString> {
+        let synced = self.clone();
+        Arc::new(move |ms| {
+            if synced.sync.load(Ordering::Relaxed) {
+                NoteDivision::variants()[synced.note.load(Ordering::Relaxed)].to_string()
+            } else {
+                format!("{ms:.1} ms")
+            }
+        })
+    }
+}
+
+/// Format a bit depth. The bit crusher is off at the highest bit depth.
+fn v2s_f32_bits() -> Arc<ValueToString> {
+    Arc::new(|bits| {
+        if bits >= Crusher::MAX_BITS {
+            String::from("Off")
+        } else {
+            format!("{bits:.1} bits")
+        }
+    })
+}
+
+/// Format the bit crusher's sample rate, which is off at the highest rate.
+fn v2s_f32_crush_rate() -> Arc<ValueToString> {
+    let hz_then_khz = formatters::v2s_f32_hz_then_khz(1);
+    Arc::new(move |rate| {
+        if rate >= Crusher::MAX_RATE {
+            String::from("Off")
+        } else {
+            hz_then_khz(rate)
+        }
+    })
+}
+
+/// Parse a bit depth formatted by `v2s_f32_bits()`.
+fn s2v_f32_bits() -> Arc<StringToValue> {
+    Arc::new(|string| {
+        let string = string.trim().to_lowercase();
+        if string == "off" {
+            Some(Crusher::MAX_BITS)
+        } else {
+            string.trim_end_matches("bits").trim().parse().ok()
+        }
+    })
+}
+
+/// Parse a number that may be followed by `unit`, in any case.
+fn s2v_f32_unit(unit: &'static str) -> Arc<StringToValue> {
+    Arc::new(move |string| {
+        let string = string.trim().to_lowercase();
+        string
+            .strip_suffix(&unit.to_lowercase())
+            .unwrap_or(&string)
+            .trim()
+            .parse()
+            .ok()
+    })
+}
+
+/// Parse a time in seconds. Values with an `ms` suffix are taken to be in milliseconds.
+fn s2v_f32_seconds() -> Arc<StringToValue> {
+    Arc::new(|string| {
+        let string = string.trim().to_lowercase();
+        if let Some(ms) = string.strip_suffix("ms") {
+            ms.trim().parse::<f32>().ok().map(|ms| ms / 1000.0)
+        } else {
+            string.trim_end_matches('s').trim().parse().ok()
+        }
+    })
+}
+
+/// Parse a time in milliseconds. Values with an `s` suffix are taken to be in seconds.
+fn s2v_f32_ms() -> Arc<StringToValue> {
+    Arc::new(|string| {
+        let string = string.trim().to_lowercase();
+        if let Some(ms) = string.strip_suffix("ms") {
+            ms.trim().parse().ok()
+        } else if let Some(seconds) = string.strip_suffix('s') {
+            seconds
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .map(|seconds| seconds * 1000.0)
+        } else {
+            string.parse().ok()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn old_state(params: impl IntoIterator<Item = (&'static str, ParamValue)>) -> PluginState {
+        PluginState {
+            version: "0.1.0".to_string(),
+            params: params
+                .into_iter()
+                .map(|(id, value)| (id.to_string(), value))
+                .collect(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn integer_delay_times_become_milliseconds() {
+        let mut state = old_state([
+            ("time", ParamValue::I32(500)),
+            ("delay", ParamValue::I32(1000)),
+            ("gain", ParamValue::F32(0.5)),
+        ]);
+        migrate_state(&mut state);
+
+        assert!(matches!(state.params["time"], ParamValue::F32(ms) if ms == 500.0));
+        assert!(matches!(state.params["delay"], ParamValue::F32(ms) if ms == 1000.0));
+        assert!(matches!(state.params["gain"], ParamValue::F32(gain) if gain == 0.5));
+    }
+
+    #[test]
+    fn numbered_modes_become_mode_ids() {
+        let expected = [
+            "echo",
+            "glitch",
+            "replace",
+            "ring-feed",
+            "dual-tap",
+            "shuffle",
+            "chaos",
+        ];
+        for (mode, id) in (1..=7).zip(expected) {
+            let mut state = old_state([("mode", ParamValue::I32(mode))]);
+            migrate_state(&mut state);
+
+            assert!(
+                matches!(&state.params["mode"], ParamValue::String(migrated) if migrated == id),
+                "mode {mode} became {:?}",
+                state.params["mode"]
+            );
+        }
+    }
+
+    #[test]
+    fn current_state_is_left_alone() {
+        let mut state = old_state([
+            ("time", ParamValue::F32(375.0)),
+            ("mode", ParamValue::String("karplus".to_string())),
+        ]);
+        migrate_state(&mut state);
+
+        assert!(matches!(state.params["time"], ParamValue::F32(ms) if ms == 375.0));
+        assert!(matches!(&state.params["mode"], ParamValue::String(id) if id == "karplus"));
+    }
+}
@@ -0,0 +1,257 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::Ordering;
+
+use crate::dsp::Rng;
+use crate::editor::set_normalized;
+use crate::params::{
+    DelayMode, MyplugParams, NoteDivision, Saturation, ShimmerShift, TimeChangeMode,
+};
+
+/// A serialized snapshot of the plugin's parameters. Values are stored normalized and keyed by
+/// parameter ID so presets survive range changes and can be shared as JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    params: BTreeMap<String, f32>,
+}
+
+impl Preset {
+    /// A preset with every parameter at its default value.
+    pub fn from_defaults(name: &str, params: &MyplugParams) -> Self {
+        let params = params
+            .param_map()
+            .into_iter()
+            .map(|(id, ptr, _)| (id, unsafe { ptr.default_normalized_value() }))
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            params,
+        }
+    }
+
+    /// Capture the current values of all parameters.
+    pub fn capture(name: &str, params: &MyplugParams) -> Self {
+        let params = params
+            .param_map()
+            .into_iter()
+            .map(|(id, ptr, _)| (id, unsafe { ptr.unmodulated_normalized_value() }))
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            params,
+        }
+    }
+
+    /// Override a single parameter's value, given as a plain value.
+    pub fn with<P: Param>(mut self, params: &MyplugParams, param: &P, value: P::Plain) -> Self {
+        let ptr = param.as_ptr();
+        if let Some((id, _, _)) = params.param_map().into_iter().find(|(_, p, _)| *p == ptr) {
+            self.params.insert(id, param.preview_normalized(value));
+        }
+
+        self
+    }
+
+    /// Set all parameters stored in this preset through the host. Unknown IDs are ignored so
+    /// presets from older versions can still be loaded. Spillover is a global setting, so it's
+    /// never changed by a preset, and with spillover enabled the maximum delay time is kept too
+    /// since changing it would reallocate and clear the delay buffers.
+    pub fn apply(&self, params: &MyplugParams, setter: &ParamSetter) {
+        let spillover = params.spillover.value();
+        for (id, ptr, _) in params.param_map() {
+            if id == "spillover" || (spillover && id == "max-delay") {
+                continue;
+            }
+            if let Some(&value) = self.params.get(&id) {
+                set_normalized(setter, ptr, value);
+            }
+        }
+        params.preset_loaded.store(true, Ordering::Relaxed);
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Two slots for comparing settings. The active slot is always the current state of the
+/// parameters, so only the other slot needs to be stored.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AbCompare {
+    /// Whether B is the active slot.
+    pub b_active: bool,
+    /// The inactive slot, or `None` until it's first used.
+    inactive: Option<Preset>,
+}
+
+impl AbCompare {
+    /// Make the other slot active. The current parameters are stored in the slot that's left
+    /// behind and the other slot is recalled. A slot that's never been used starts out as a copy
+    /// of the current settings.
+    pub fn switch(&mut self, params: &MyplugParams, setter: &ParamSetter) {
+        let current = Preset::capture(&params.preset_name.read().unwrap(), params);
+        if let Some(other) = self.inactive.replace(current) {
+            other.apply(params, setter);
+            *params.preset_name.write().unwrap() = other.name;
+        }
+        self.b_active = !self.b_active;
+    }
+
+    /// Copy the current parameters into the inactive slot.
+    pub fn copy_to_inactive(&mut self, params: &MyplugParams) {
+        self.inactive = Some(Preset::capture(&params.preset_name.read().unwrap(), params));
+    }
+}
+
+/// Parameters the randomizer never changes. These are global settings, the gains that could make
+/// the output jump in level, settings that change the latency or reallocate the buffers, and the
+/// performance controls that would hold, loop or wipe the buffer. Engine B's parameters have a
+/// `b_` prefix, and switching it on adds a second set of repeats on top of the first.
+const NEVER_RANDOMIZED: &[&str] = &[
+    "bypass",
+    "spillover",
+    "gain",
+    "output-gain",
+    "kill-dry",
+    "auto-gain",
+    "b_enabled",
+    "b_level",
+    "max-delay",
+    "oversampling",
+    "duck-lookahead",
+    "transport-mode",
+    "freeze",
+    "infinite",
+    "clear",
+    "save-frozen",
+    "looper",
+    "loop-punch-in",
+    "loop-clear",
+    "stutter",
+    "sequencer",
+    "midi-tap",
+    "note-tuning",
+];
+
+/// The randomizer keeps the feedback amounts below this so random settings don't run away.
+const MAX_RANDOM_FEEDBACK: f32 = 0.9;
+
+/// The editor's randomizer. The seed and the locked parameters are saved with the project, and
+/// the same seed always produces the same settings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Randomizer {
+    /// The seed of the next randomization, which moves on by one every time.
+    pub seed: u32,
+    /// How far mutating moves the parameters at most, as a fraction of their ranges.
+    pub mutate_amount: f32,
+    /// The IDs of the parameters that are left alone.
+    pub locked: BTreeSet<String>,
+}
+
+impl Default for Randomizer {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            mutate_amount: 0.1,
+            locked: BTreeSet::new(),
+        }
+    }
+}
+
+impl Randomizer {
+    /// Set every parameter that isn't locked to a random value.
+    pub fn randomize(&mut self, params: &MyplugParams, setter: &ParamSetter) {
+        self.next(params, None).apply(params, setter);
+    }
+
+    /// Move every parameter that isn't locked by a random amount of up to `mutate_amount`.
+    pub fn mutate(&mut self, params: &MyplugParams, setter: &ParamSetter) {
+        self.next(params, Some(self.mutate_amount))
+            .apply(params, setter);
+    }
+
+    /// Generate the next random preset from the current parameters, and advance the seed.
+    fn next(&mut self, params: &MyplugParams, mutate_amount: Option<f32>) -> Preset {
+        let mut rng = Rng::new(self.seed);
+        self.seed = self.seed.wrapping_add(1);
+
+        let mut preset = Preset::capture("", params);
+        preset.params.retain(|id, value| {
+            if NEVER_RANDOMIZED.contains(&id.as_str()) || self.locked.contains(id) {
+                return false;
+            }
+
+            *value = match mutate_amount {
+                Some(amount) => (*value + rng.next_bipolar() * amount).clamp(0.0, 1.0),
+                None => (rng.next_bipolar() + 1.0) / 2.0,
+            };
+            true
+        });
+
+        let feedbacks = [
+            ("feedback", &params.feedback_path.feedback),
+            ("b_feedback", &params.engine_b.feedback),
+        ];
+        for (id, param) in feedbacks {
+            if let Some(value) = preset.params.get_mut(id) {
+                *value = value.min(param.preview_normalized(MAX_RANDOM_FEEDBACK));
+            }
+        }
+
+        preset
+    }
+}
+
+/// The built-in presets. Everything a preset doesn't mention is left at its default value.
+pub fn factory_presets(params: &MyplugParams) -> Vec<Preset> {
+    let p = params;
+    vec![
+        Preset::from_defaults("Slap", p)
+            .with(p, &p.delay_a.mode, DelayMode::Echo)
+            .with(p, &p.delay_a.time, 90.0)
+            .with(p, &p.feedback_path.feedback, 0.1)
+            .with(p, &p.output.mix, 0.35),
+        Preset::from_defaults("Dub", p)
+            .with(p, &p.delay_a.mode, DelayMode::Echo)
+            .with(p, &p.delay_a.time, 380.0)
+            .with(p, &p.delay_a.time_change, TimeChangeMode::Glide)
+            .with(p, &p.feedback_path.feedback, 0.75)
+            .with(p, &p.output.mix, 0.45)
+            .with(p, &p.feedback_path.low_cut, 150.0)
+            .with(p, &p.feedback_path.high_cut, 2500.0)
+            .with(p, &p.feedback_path.saturation, Saturation::Tanh)
+            .with(p, &p.feedback_path.drive, util::db_to_gain(6.0))
+            .with(p, &p.modulation.wow_depth, 0.8),
+        Preset::from_defaults("Ping-Pong", p)
+            .with(p, &p.delay_a.mode, DelayMode::PingPong)
+            .with(p, &p.delay_a.sync, true)
+            .with(p, &p.delay_a.note, NoteDivision::EighthDotted)
+            .with(p, &p.feedback_path.feedback, 0.55)
+            .with(p, &p.output.mix, 0.4),
+        Preset::from_defaults("Ambient Wash", p)
+            .with(p, &p.delay_a.mode, DelayMode::Echo)
+            .with(p, &p.delay_a.time, 600.0)
+            .with(p, &p.feedback_path.feedback, 0.85)
+            .with(p, &p.output.mix, 0.5)
+            .with(p, &p.feedback_path.high_cut, 6000.0)
+            .with(p, &p.feedback_path.shimmer, 0.35)
+            .with(p, &p.feedback_path.shimmer_shift, ShimmerShift::OctaveUp)
+            .with(p, &p.modulation.wow_depth, 1.5)
+            .with(p, &p.modulation.wow_rate, 0.3),
+        Preset::from_defaults("Glitch", p)
+            .with(p, &p.delay_a.mode, DelayMode::Chaos)
+            .with(p, &p.delay_a.time, 250.0)
+            .with(p, &p.delay_a.delay, 125.0)
+            .with(p, &p.delay_a.mode6_ratio, 7)
+            .with(p, &p.feedback_path.feedback, 0.5)
+            .with(p, &p.output.mix, 0.5),
+    ]
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use crate::params::NoteDivision;
+
+/// The number of steps in the delay time sequencer.
+pub const NUM_STEPS: usize = 16;
+
+/// A single step of the sequencer.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// Whether the input is sent into the delay during this step.
+    pub enabled: bool,
+    /// The delay time during this step.
+    pub note: NoteDivision,
+}
+
+/// The sequencer's steps. These are edited in the GUI and stored in the plugin state, and the
+/// audio thread keeps a copy that it updates whenever the lock isn't contended.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepSequence {
+    pub steps: [Step; NUM_STEPS],
+}
+
+impl Default for StepSequence {
+    fn default() -> Self {
+        Self {
+            steps: [Step {
+                enabled: true,
+                note: NoteDivision::Eighth,
+            }; NUM_STEPS],
+        }
+    }
+}
+
+impl StepSequence {
+    /// The step that's playing at `pos_beats` on the host's timeline when every step lasts
+    /// `step_beats` and the sequence starts over after `len` steps.
+    pub fn step_at(&self, pos_beats: f64, step_beats: f64, len: usize) -> Step {
+        let len = len.clamp(1, NUM_STEPS) as i64;
+        let step = (pos_beats / step_beats).floor() as i64;
+
+        self.steps[step.rem_euclid(len) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_follow_the_timeline() {
+        let mut sequence = StepSequence::default();
+        for (i, step) in sequence.steps.iter_mut().enumerate() {
+            step.enabled = i % 2 == 0;
+        }
+        sequence.steps[1].note = NoteDivision::Quarter;
+
+        assert_eq!(sequence.step_at(0.0, 0.25, 16), sequence.steps[0]);
+        assert_eq!(sequence.step_at(0.3, 0.25, 16).note, NoteDivision::Quarter);
+        assert!(!sequence.step_at(0.3, 0.25, 16).enabled);
+        // The sequence loops after `len` steps
+        assert_eq!(sequence.step_at(1.25, 0.25, 4), sequence.steps[1]);
+        assert!(!sequence.step_at(4.25, 0.25, 16).enabled);
+    }
+}
@@ -0,0 +1,92 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::editor::set_normalized;
+use crate::params::{MyplugParams, NoteDivision};
+
+/// The number of taps in the multi-tap mode.
+pub const NUM_TAPS: usize = 8;
+
+/// The number of tap patterns that can be stored with a project.
+pub const NUM_TAP_SLOTS: usize = 4;
+
+/// A single tap of a stored tap pattern.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Tap {
+    pub enabled: bool,
+    /// Where the tap lands as a fraction of the pattern.
+    pub time: f32,
+    pub level: f32,
+}
+
+/// The tap timeline's settings and the user's tap patterns. These are edited in the GUI and
+/// stored in the plugin state. The taps themselves are parameters, so a stored pattern is copied
+/// into those parameters when it's recalled.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TapPatterns {
+    /// The spacing of the timeline's grid.
+    pub grid: NoteDivision,
+    /// Whether taps that are moved or added on the timeline snap to the grid.
+    pub quantize: bool,
+    /// The stored patterns, or `None` for slots that haven't been used.
+    pub slots: [Option<[Tap; NUM_TAPS]>; NUM_TAP_SLOTS],
+}
+
+impl Default for TapPatterns {
+    fn default() -> Self {
+        Self {
+            grid: NoteDivision::Sixteenth,
+            quantize: true,
+            slots: [None; NUM_TAP_SLOTS],
+        }
+    }
+}
+
+impl TapPatterns {
+    /// Store the current taps in `slot`.
+    pub fn store(&mut self, slot: usize, params: &MyplugParams) {
+        self.slots[slot] = Some(params.delay_a.taps.each_ref().map(|tap| Tap {
+            enabled: tap.enabled.value(),
+            time: tap.time.value(),
+            level: tap.level.value(),
+        }));
+    }
+
+    /// Set the taps to the pattern stored in `slot`, if there is one.
+    pub fn recall(&self, slot: usize, params: &MyplugParams, setter: &ParamSetter) {
+        let Some(pattern) = self.slots[slot] else {
+            return;
+        };
+
+        for (tap, param) in pattern.iter().zip(&params.delay_a.taps) {
+            let enabled = param.enabled.preview_normalized(tap.enabled);
+            set_normalized(setter, param.enabled.as_ptr(), enabled);
+            let time = param.time.preview_normalized(tap.time);
+            set_normalized(setter, param.time.as_ptr(), time);
+            let level = param.level.preview_normalized(tap.level);
+            set_normalized(setter, param.level.as_ptr(), level);
+        }
+    }
+}
+
+/// Snap a tap's time, as a fraction of a bar that's `bar_beats` long, to the nearest line of a
+/// grid spaced `grid` apart.
+pub fn quantize(time: f32, grid: NoteDivision, bar_beats: f32) -> f32 {
+    let step = grid.beats() / bar_beats;
+    ((time / step).round() * step).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taps_snap_to_the_grid() {
+        assert_eq!(quantize(0.3, NoteDivision::Quarter, 4.0), 0.25);
+        assert_eq!(quantize(0.2, NoteDivision::Eighth, 4.0), 0.25);
+        assert_eq!(quantize(0.05, NoteDivision::Eighth, 4.0), 0.0);
+        // The grid follows the number of beats in a bar
+        assert_eq!(quantize(0.3, NoteDivision::Quarter, 3.0), 1.0 / 3.0);
+        assert_eq!(quantize(0.9, NoteDivision::Quarter, 3.0), 1.0);
+    }
+}